@@ -1,11 +1,20 @@
 //! This program lets users create a personal vault account (PDA) to deposit and withdraw SOL securely.
-//! Each user has their own vault + state PDA.
+//! Each user can have many vaults, each a vault + state PDA numbered by a `vault_id` and given a label,
+//! and a registry PDA listing them.
+//! SPL and Token-2022 tokens can be held too, in associated token accounts owned by the `vault` PDA.
+//! A vault's SOL can be time-locked, unlocking at a cliff or vesting linearly until an end date.
+//! Team treasuries can use a multisig vault instead, where M of N signers must approve each withdrawal and closing the vault.
+//! Owners can also grant other wallets capped allowances to spend SOL from their vault.
 
 #![allow(deprecated)]
 #![allow(unexpected_cfgs)]
 
 use anchor_lang::prelude::*;
 use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::associated_token::{get_associated_token_address_with_program_id, AssociatedToken};
+use anchor_spl::token_interface::{
+    close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
 
 declare_id!("heiD65tNjyZVxNARhVVsrsa1HPzFThbaxoAmiyV1vzd");
 
@@ -13,32 +22,143 @@ declare_id!("heiD65tNjyZVxNARhVVsrsa1HPzFThbaxoAmiyV1vzd");
 pub mod vault {
     use super::*;
 
-    /// Initializes a vault for the calling user.
-    /// This creates two PDAs:
+    /// Initializes vault number `vault_id`, named `label`, for the calling user.
+    /// This creates two PDAs, and the user's registry on their first vault:
     ///     - `vault_state`: stores bump seeds.
     ///     - `vault`: holds SOL.
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        ctx.accounts.initialize(&ctx.bumps)
+    /// If `lock` is set, the vault's SOL is time-locked from the start.
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        vault_id: u64,
+        label: String,
+        lock: Option<LockSchedule>,
+    ) -> Result<()> {
+        ctx.accounts.initialize(&ctx.bumps, vault_id, label, lock)
+    }
+
+    /// Initializes multisig vault number `vault_id`, named `label`, created by the calling user, owned by `signers`.
+    /// Its SOL only leaves through withdrawal proposals approved by `threshold` of them.
+    pub fn initialize_multisig(
+        ctx: Context<Initialize>,
+        vault_id: u64,
+        label: String,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        ctx.accounts.initialize_multisig(&ctx.bumps, vault_id, label, signers, threshold)
+    }
+
+    /// Time-locks the vault's SOL, current balance and future deposits, on `schedule`.
+    /// A vesting schedule takes no deposits until it has fully unlocked, so fund the vault first.
+    /// Fails while an earlier lock is still in force, so a lock can't be lifted early.
+    pub fn set_lock(ctx: Context<SetLock>, schedule: LockSchedule) -> Result<()> {
+        ctx.accounts.set_lock(schedule)
     }
 
     /// Deposits `amount` lamports into the user's vault.
+    /// Deposits into a vault whose time lock has a cliff only are locked too. Deposits into a vault
+    /// vesting under a time lock are refused, since they would vest from the lock's start.
     pub fn deposit(ctx: Context<Payment>, amount: u64) -> Result<()> {
         ctx.accounts.deposit(amount)
     }
 
     /// Withdraws `amount` lamports from the user's vault back to their wallet.
+    /// A time-locked vault only releases what has unlocked so far.
     pub fn withdraw(ctx: Context<Payment>, amount: u64) -> Result<()> {
         ctx.accounts.withdraw(amount)
     }
 
-    /// Closes the vault by transferring remaining SOL and reclaiming rent.
-    pub fn close(ctx: Context<Close>) -> Result<()> {
-        ctx.accounts.close()
+    /// Deposits `amount` tokens of `mint` into the vault's token account for that mint,
+    /// creating it on the first deposit.
+    pub fn deposit_token(ctx: Context<TokenPayment>, amount: u64) -> Result<()> {
+        ctx.accounts.deposit_token(amount)
+    }
+
+    /// Withdraws `amount` tokens of `mint` from the vault back to the user's token account.
+    pub fn withdraw_token(ctx: Context<TokenPayment>, amount: u64) -> Result<()> {
+        ctx.accounts.withdraw_token(amount)
+    }
+
+    /// Closes the vault by transferring remaining SOL and reclaiming rent, and removes it from the registry.
+    /// Multisig vaults close through `propose_close` instead. Fails while a time lock is in force, or while any allowance on the vault hasn't been revoked.
+    /// The vault's token account for every mint it has held must be passed as a writable remaining account,
+    /// followed by the token program owning it. All of them must be empty, and are closed with the vault.
+    pub fn close<'info>(ctx: Context<'_, '_, 'info, 'info, Close<'info>>) -> Result<()> {
+        ctx.accounts.close(ctx.remaining_accounts)
+    }
+
+    /// Proposes sending `amount` lamports from a multisig vault to `destination`.
+    /// The proposal expires at the `expires_at` unix timestamp, and counts as approved by the proposer.
+    pub fn propose_withdrawal(
+        ctx: Context<ProposeWithdrawal>,
+        amount: u64,
+        destination: Pubkey,
+        expires_at: i64,
+    ) -> Result<()> {
+        ctx.accounts.propose_withdrawal(amount, destination, expires_at, ctx.bumps.proposal)
+    }
+
+    /// Proposes closing a multisig vault, sending all its SOL and the state account's rent to `destination`.
+    /// The proposal expires at the `expires_at` unix timestamp, and counts as approved by the proposer.
+    pub fn propose_close(ctx: Context<ProposeWithdrawal>, destination: Pubkey, expires_at: i64) -> Result<()> {
+        ctx.accounts.propose_close(destination, expires_at, ctx.bumps.proposal)
+    }
+
+    /// Approves a pending withdrawal or close proposal as one of the vault's signers.
+    pub fn approve(ctx: Context<Approve>) -> Result<()> {
+        ctx.accounts.approve()
+    }
+
+    /// Executes a withdrawal proposal approved by at least `threshold` signers, before it expires.
+    /// The proposal account is closed and its rent refunded to the proposer.
+    pub fn execute(ctx: Context<Execute>) -> Result<()> {
+        ctx.accounts.execute()
+    }
+
+    /// Executes a close proposal approved by at least `threshold` signers, before it expires.
+    /// Fails while any other proposal on the vault is open. The vault is removed from its creator's registry.
+    pub fn execute_close(ctx: Context<ExecuteClose>) -> Result<()> {
+        ctx.accounts.execute_close()
+    }
+
+    /// Withdraws a proposal, closing it and refunding its rent. Only callable by its proposer.
+    pub fn cancel_proposal(ctx: Context<CancelProposal>) -> Result<()> {
+        ctx.accounts.cancel_proposal()
+    }
+
+    /// Closes an expired proposal, refunding its rent to the proposer. Callable by anyone,
+    /// so an absent proposer can't keep a multisig vault from closing.
+    pub fn close_expired_proposal(ctx: Context<CloseExpiredProposal>) -> Result<()> {
+        ctx.accounts.close_expired_proposal()
+    }
+
+    /// Lets `delegate` spend up to `cap` lamports from the user's vault, at most `period.limit`
+    /// every `period.duration` seconds if set, until the `expires_at` unix timestamp if set.
+    /// Granting again to the same delegate replaces the allowance and resets what was spent.
+    pub fn grant_allowance(
+        ctx: Context<GrantAllowance>,
+        delegate: Pubkey,
+        cap: u64,
+        period: Option<AllowancePeriod>,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+        ctx.accounts.grant_allowance(delegate, cap, period, expires_at, ctx.bumps.allowance)
+    }
+
+    /// Sends `amount` lamports from the vault to `destination`, within the delegate's allowance.
+    pub fn spend(ctx: Context<Spend>, amount: u64) -> Result<()> {
+        ctx.accounts.spend(amount)
+    }
+
+    /// Revokes a delegate's allowance, closing it and refunding its rent to the owner.
+    pub fn revoke(ctx: Context<Revoke>) -> Result<()> {
+        ctx.accounts.revoke()
     }
 }
 
 /// Initialize context: invoked during `initialize` instruction.
 #[derive(Accounts)]
+#[instruction(vault_id: u64)]
 pub struct Initialize<'info> {
     /// The user who pays for account creation and owns the vault.
     #[account(mut)]
@@ -48,21 +168,21 @@ pub struct Initialize<'info> {
     /// this is for initializing the PDA
     // --------------------
     // VAULT STATE ACCOUNT (Initialization)
-    // This account is a PDA (Program Derived Address) unique to each user.
+    // This account is a PDA (Program Derived Address) unique to each user and vault id.
     // - 'init': Creates the account.
     // - 'payer': User pays for their own state account.
     // - 'space': Allocates enough space for VaultState struct.
-    // - 'seeds': [b"state", user.key().as_ref()] ensures uniqueness per user.
+    // - 'seeds': [b"state", user.key().as_ref(), vault_id] ensures uniqueness per user and vault id.
     // - 'bump': Anchor finds and stores the bump for PDA security.
     //
     // This pattern ensures:
     // - Only the program can create/sign for this PDA.
-    // - Each user gets a unique, deterministic state account.
+    // - Each of a user's vaults gets a unique, deterministic state account.
     #[account(
         init, //to create a new account (initialize)
         payer = user, // the user will pay the rent for creating this account
         space = VaultState::INIT_SPACE,
-        seeds = [b"state", user.key().as_ref()], // ensures the account is unique per user and can be deterministically derived
+        seeds = [b"state", user.key().as_ref(), vault_id.to_le_bytes().as_ref()], // ensures the account is unique per user and vault id, and can be deterministically derived
         bump // required for PDA security and allow the program to sign for the account
     )]
     pub vault_state: Account<'info, VaultState>,
@@ -75,20 +195,123 @@ pub struct Initialize<'info> {
     )]
     pub vault: SystemAccount<'info>,
 
+    /// PDA listing the user's vaults. Created with their first vault.
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = VaultRegistry::INIT_SPACE,
+        seeds = [b"registry", user.key().as_ref()],
+        bump
+    )]
+    pub registry: Account<'info, VaultRegistry>,
+
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Initialize<'info> {
     /// Handles initialization logic.
-    pub fn initialize(&mut self, bumps: &InitializeBumps) -> Result<()> {
-        // Save bump seeds to the state account.
+    pub fn initialize(
+        &mut self,
+        bumps: &InitializeBumps,
+        vault_id: u64,
+        label: String,
+        lock: Option<LockSchedule>,
+    ) -> Result<()> {
+        self.register(bumps, vault_id, label)?;
+
+        if let Some(schedule) = lock {
+            let now = Clock::get()?.unix_timestamp;
+            self.vault_state.time_lock = Some(TimeLock::new(schedule, now, self.vault.lamports())?);
+        }
+
+        Ok(())
+    }
+}
+
+impl<'info> Initialize<'info> {
+    /// Handles multisig initialization logic.
+    pub fn initialize_multisig(
+        &mut self,
+        bumps: &InitializeBumps,
+        vault_id: u64,
+        label: String,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        require!(
+            (1..=VaultState::MAX_SIGNERS).contains(&signers.len())
+                && threshold > 0
+                && threshold as usize <= signers.len(),
+            VaultError::InvalidMultisig
+        );
+        for (i, signer) in signers.iter().enumerate() {
+            require!(!signers[..i].contains(signer), VaultError::InvalidMultisig);
+        }
+
+        self.register(bumps, vault_id, label)?;
+        self.vault_state.signers = signers;
+        self.vault_state.threshold = threshold;
+
+        Ok(())
+    }
+
+    /// Saves the vault's bump seeds, id and label to the state account, and adds it to the user's registry.
+    fn register(&mut self, bumps: &InitializeBumps, vault_id: u64, label: String) -> Result<()> {
+        require!(label.len() <= VaultState::MAX_LABEL_LEN, VaultError::LabelTooLong);
+        require!(
+            self.registry.vault_ids.len() < VaultRegistry::MAX_VAULTS,
+            VaultError::TooManyVaults
+        );
+
         self.vault_state.vault_bump = bumps.vault;
         self.vault_state.state_bump = bumps.vault_state;
+        self.vault_state.vault_id = vault_id;
+        self.vault_state.label = label;
+
+        self.registry.owner = self.user.key();
+        self.registry.bump = bumps.registry;
+        self.registry.vault_ids.push(vault_id);
 
         Ok(())
     }
 }
 
+/// Set lock context: used to time-lock an existing vault.
+#[derive(Accounts)]
+pub struct SetLock<'info> {
+    /// The user who owns the vault.
+    pub user: Signer<'info>,
+
+    /// PDA that stores the time lock.
+    #[account(
+        mut,
+        seeds = [b"state", user.key().as_ref(), vault_state.vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// PDA holding SOL. Its balance becomes subject to the lock.
+    #[account(
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+}
+
+impl<'info> SetLock<'info> {
+    /// Replaces the vault's time lock, once any previous one has fully unlocked.
+    pub fn set_lock(&mut self, schedule: LockSchedule) -> Result<()> {
+        require!(!self.vault_state.is_multisig(), VaultError::MultisigVault);
+        let now = Clock::get()?.unix_timestamp;
+        if let Some(time_lock) = &self.vault_state.time_lock {
+            require!(time_lock.is_unlocked(now), VaultError::VaultLocked);
+        }
+
+        self.vault_state.time_lock = Some(TimeLock::new(schedule, now, self.vault.lamports())?);
+        Ok(())
+    }
+}
+
 /// Payment context: used for deposit & withdraw instructions.
 #[derive(Accounts)]
 pub struct Payment<'info> {
@@ -103,10 +326,12 @@ pub struct Payment<'info> {
     // This constraint is used when accessing (not creating) the vault_state PDA.
     // - 'seeds': Must match the initialization seeds.
     // - 'bump': Must match the bump stored in the account.
+    // - 'mut': The time lock, if any, tracks what goes in and out.
     //
     // This ensures you are referencing the correct PDA and prevents spoofing.
     #[account(
-        seeds = [b"state", user.key().as_ref()],
+        mut,
+        seeds = [b"state", user.key().as_ref(), vault_state.vault_id.to_le_bytes().as_ref()],
         bump = vault_state.state_bump // the bump must match the one stored in the vault_state account (during initializing)
     )]
     pub vault_state: Account<'info, VaultState>,
@@ -125,6 +350,16 @@ pub struct Payment<'info> {
 impl<'info> Payment<'info> {
     /// Deposits SOL from user wallet into vault PDA.
     pub fn deposit(&mut self, amount: u64) -> Result<()> {
+        // Deposits into a time-locked vault are locked too, but can't join vesting already under way:
+        // they'd vest from the lock's start, and part of them could leave straight away
+        let now = Clock::get()?.unix_timestamp;
+        if let Some(time_lock) = self.vault_state.time_lock.as_mut() {
+            if !time_lock.is_unlocked(now) {
+                require!(time_lock.schedule.end_ts.is_none(), VaultError::VestingUnderway);
+                time_lock.total = time_lock.total.checked_add(amount).ok_or(VaultError::Overflow)?;
+            }
+        }
+
         let cpi_ctx = CpiContext::new(
             self.system_program.to_account_info(),
             Transfer {
@@ -137,6 +372,17 @@ impl<'info> Payment<'info> {
 
     /// Withdraws SOL from vault PDA back to user wallet.
     pub fn withdraw(&mut self, amount: u64) -> Result<()> {
+        // A multisig vault's SOL only leaves through an approved proposal
+        require!(!self.vault_state.is_multisig(), VaultError::MultisigVault);
+
+        // Only what the time lock has released so far can leave
+        let now = Clock::get()?.unix_timestamp;
+        let balance = self.vault.lamports();
+        if let Some(time_lock) = self.vault_state.time_lock.as_mut() {
+            require!(amount <= time_lock.available(balance, now), VaultError::FundsLocked);
+            time_lock.withdrawn = time_lock.withdrawn.saturating_add(amount);
+        }
+
         // --------------------
         // PDA SIGNER SEEDS USAGE
         //
@@ -175,7 +421,115 @@ impl<'info> Payment<'info> {
     }
 }
 
-/// Close context: closes both vault_state PDA and vault PDA.
+/// Token payment context: used for deposit_token & withdraw_token instructions.
+/// Works with both the SPL Token and Token-2022 programs.
+#[derive(Accounts)]
+pub struct TokenPayment<'info> {
+    /// The user who owns the vault.
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// PDA that holds bump seeds and the mints the vault has held.
+    #[account(
+        mut,
+        seeds = [b"state", user.key().as_ref(), vault_state.vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// PDA that owns the vault's token accounts.
+    #[account(
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// The mint of the token being moved.
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The user's token account for `mint`.
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The vault's token account for `mint`: the associated token account of the `vault` PDA.
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TokenPayment<'info> {
+    /// Deposits tokens from the user's token account into the vault's.
+    pub fn deposit_token(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        // Multisig proposals only move SOL, so tokens could never leave
+        require!(!self.vault_state.is_multisig(), VaultError::MultisigVault);
+
+        // Remember the mint, so `close` can check the vault holds none of it
+        let mint = self.mint.key();
+        if !self.vault_state.token_mints.contains(&mint) {
+            require!(
+                self.vault_state.token_mints.len() < VaultState::MAX_TOKEN_MINTS,
+                VaultError::TooManyTokenMints
+            );
+            self.vault_state.token_mints.push(mint);
+        }
+
+        let cpi_ctx = CpiContext::new(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from: self.user_token_account.to_account_info(),
+                mint: self.mint.to_account_info(),
+                to: self.vault_token_account.to_account_info(),
+                authority: self.user.to_account_info(),
+            },
+        );
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+
+    /// Withdraws tokens from the vault's token account back to the user's.
+    /// The `vault` PDA signs as the owner of its token account.
+    pub fn withdraw_token(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        require!(!self.vault_state.is_multisig(), VaultError::MultisigVault);
+
+        let seeds = &[
+            b"vault",
+            self.vault_state.to_account_info().key.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from: self.vault_token_account.to_account_info(),
+                mint: self.mint.to_account_info(),
+                to: self.user_token_account.to_account_info(),
+                authority: self.vault.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+}
+
+/// Close context: closes the vault_state PDA, the vault PDA and the vault's token accounts.
 #[derive(Accounts)]
 pub struct Close<'info> {
     /// User who owns the vault.
@@ -185,7 +539,7 @@ pub struct Close<'info> {
     /// PDA that stores bump seeds. Closed and rent refunded to user.
     #[account(
         mut,
-        seeds = [b"state", user.key().as_ref()],
+        seeds = [b"state", user.key().as_ref(), vault_state.vault_id.to_le_bytes().as_ref()],
         bump = vault_state.state_bump,
         close = user
     )]
@@ -199,12 +553,28 @@ pub struct Close<'info> {
     )]
     pub vault: SystemAccount<'info>,
 
+    /// PDA listing the user's vaults. The closed vault is removed from it.
+    #[account(
+        mut,
+        seeds = [b"registry", user.key().as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, VaultRegistry>,
+
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Close<'info> {
     /// Transfers all SOL from vault PDA to user and closes PDAs.
-    pub fn close(&mut self) -> Result<()> {
+    /// Closes the vault's token accounts, passed in `token_accounts`, refusing while any of them holds tokens.
+    pub fn close(&mut self, token_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        require!(!self.vault_state.is_multisig(), VaultError::MultisigVault);
+        if let Some(time_lock) = &self.vault_state.time_lock {
+            require!(time_lock.is_unlocked(Clock::get()?.unix_timestamp), VaultError::VaultLocked);
+        }
+        // A vault reopened under the same id would otherwise revive its old allowances
+        require!(self.vault_state.allowance_count == 0, VaultError::AllowancesOutstanding);
+
         let seeds = &[
             b"vault",
             self.vault_state.to_account_info().key.as_ref(),
@@ -212,6 +582,11 @@ impl<'info> Close<'info> {
         ];
         let signer_seeds = &[&seeds[..]];
 
+        self.close_token_accounts(token_accounts, signer_seeds)?;
+
+        let vault_id = self.vault_state.vault_id;
+        self.registry.vault_ids.retain(|id| *id != vault_id);
+
         let cpi_ctx = CpiContext::new_with_signer(
             self.system_program.to_account_info(),
             Transfer {
@@ -222,6 +597,506 @@ impl<'info> Close<'info> {
         );
         transfer(cpi_ctx, self.vault.lamports())
     }
+
+    /// Closes the vault's token account for every mint it has held, refunding their rent to the user.
+    /// Each must be among `token_accounts`, with its token program, and be empty. Closing with tokens left
+    /// would strand them, and leaving the accounts open would strand their rent, since only this program
+    /// can sign for the vault.
+    fn close_token_accounts(
+        &self,
+        token_accounts: &'info [AccountInfo<'info>],
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        for mint in &self.vault_state.token_mints {
+            let info = token_accounts
+                .iter()
+                .find(|info| {
+                    info.key() == get_associated_token_address_with_program_id(&self.vault.key(), mint, info.owner)
+                })
+                .ok_or(VaultError::MissingTokenAccount)?;
+            let token_account = InterfaceAccount::<TokenAccount>::try_from(info)?;
+            require!(token_account.amount == 0, VaultError::TokenBalanceRemaining);
+
+            // The account was just loaded as a token account, so its owner is a token program
+            let token_program = token_accounts
+                .iter()
+                .find(|program| program.key == info.owner)
+                .ok_or(VaultError::MissingTokenAccount)?;
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                token_program.clone(),
+                CloseAccount {
+                    account: info.clone(),
+                    destination: self.user.to_account_info(),
+                    authority: self.vault.to_account_info(),
+                },
+                signer_seeds,
+            );
+            close_account(cpi_ctx)?;
+        }
+        Ok(())
+    }
+}
+
+/// Propose withdrawal context: a multisig signer proposes a withdrawal from the vault.
+#[derive(Accounts)]
+pub struct ProposeWithdrawal<'info> {
+    /// One of the vault's signers. Pays for the proposal account.
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+
+    /// The multisig vault's state. Numbers the proposals.
+    #[account(mut)]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// PDA for the new proposal, numbered by the vault's proposal count.
+    #[account(
+        init,
+        payer = proposer,
+        space = Proposal::INIT_SPACE,
+        seeds = [b"proposal", vault_state.key().as_ref(), vault_state.proposal_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ProposeWithdrawal<'info> {
+    /// Records the withdrawal proposal, approved by the proposer.
+    pub fn propose_withdrawal(&mut self, amount: u64, destination: Pubkey, expires_at: i64, bump: u8) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        self.propose(amount, destination, expires_at, false, bump)
+    }
+
+    /// Records the close proposal, approved by the proposer.
+    pub fn propose_close(&mut self, destination: Pubkey, expires_at: i64, bump: u8) -> Result<()> {
+        self.propose(0, destination, expires_at, true, bump)
+    }
+
+    /// Records a proposal and counts it as open on the vault.
+    fn propose(&mut self, amount: u64, destination: Pubkey, expires_at: i64, close: bool, bump: u8) -> Result<()> {
+        self.vault_state.check_signer(&self.proposer.key())?;
+        require!(expires_at > Clock::get()?.unix_timestamp, VaultError::ProposalExpired);
+
+        self.proposal.set_inner(Proposal {
+            vault_state: self.vault_state.key(),
+            proposer: self.proposer.key(),
+            destination,
+            amount,
+            close,
+            expires_at,
+            approvals: vec![self.proposer.key()],
+            bump,
+        });
+        self.vault_state.proposal_count += 1;
+        self.vault_state.open_proposals += 1;
+
+        Ok(())
+    }
+}
+
+/// Approve context: a multisig signer approves a pending withdrawal or close proposal.
+#[derive(Accounts)]
+pub struct Approve<'info> {
+    /// One of the vault's signers.
+    pub signer: Signer<'info>,
+
+    /// The multisig vault's state, holding the signer set.
+    pub vault_state: Account<'info, VaultState>,
+
+    /// The proposal being approved.
+    #[account(mut, has_one = vault_state)]
+    pub proposal: Account<'info, Proposal>,
+}
+
+impl<'info> Approve<'info> {
+    /// Adds the signer's approval to the proposal.
+    pub fn approve(&mut self) -> Result<()> {
+        let signer = self.signer.key();
+        self.vault_state.check_signer(&signer)?;
+        require!(!self.proposal.is_expired(Clock::get()?.unix_timestamp), VaultError::ProposalExpired);
+        require!(!self.proposal.approvals.contains(&signer), VaultError::AlreadyApproved);
+
+        self.proposal.approvals.push(signer);
+        Ok(())
+    }
+}
+
+/// Execute context: sends an approved proposal's lamports to its destination.
+#[derive(Accounts)]
+pub struct Execute<'info> {
+    /// One of the vault's signers.
+    pub executor: Signer<'info>,
+
+    /// The multisig vault's state.
+    #[account(mut)]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// PDA holding the vault's SOL.
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// The proposal being executed. Closed, with its rent refunded to the proposer.
+    #[account(
+        mut,
+        has_one = vault_state,
+        has_one = proposer,
+        has_one = destination,
+        close = proposer
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    /// CHECK: Receives the lamports; checked against the proposal's destination.
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+
+    /// The proposal's creator, refunded its rent.
+    #[account(mut)]
+    pub proposer: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Execute<'info> {
+    /// Transfers the proposed amount from the vault PDA to the destination.
+    pub fn execute(&mut self) -> Result<()> {
+        require!(!self.proposal.close, VaultError::WrongProposal);
+        self.vault_state.check_approved(&self.executor.key(), &self.proposal)?;
+        self.vault_state.open_proposals = self.vault_state.open_proposals.saturating_sub(1);
+
+        let seeds = &[
+            b"vault",
+            self.vault_state.to_account_info().key.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            Transfer {
+                from: self.vault.to_account_info(),
+                to: self.destination.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer(cpi_ctx, self.proposal.amount)
+    }
+}
+
+/// Execute close context: closes a multisig vault once a close proposal is approved.
+#[derive(Accounts)]
+pub struct ExecuteClose<'info> {
+    /// One of the vault's signers.
+    pub executor: Signer<'info>,
+
+    /// The multisig vault's state. Closed, with its rent sent to the destination.
+    #[account(
+        mut,
+        seeds = [b"state", registry.owner.as_ref(), vault_state.vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        close = destination
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// PDA holding the vault's SOL. All of it goes to the destination.
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// PDA listing the vault creator's vaults. The closed vault is removed from it.
+    #[account(
+        mut,
+        seeds = [b"registry", registry.owner.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, VaultRegistry>,
+
+    /// The close proposal being executed. Closed, with its rent refunded to the proposer.
+    #[account(
+        mut,
+        has_one = vault_state,
+        has_one = proposer,
+        has_one = destination,
+        close = proposer
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    /// CHECK: Receives the lamports; checked against the proposal's destination.
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+
+    /// The proposal's creator, refunded its rent.
+    #[account(mut)]
+    pub proposer: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ExecuteClose<'info> {
+    /// Transfers all SOL from the vault PDA to the destination and removes the vault from the registry.
+    pub fn execute_close(&mut self) -> Result<()> {
+        require!(self.proposal.close, VaultError::WrongProposal);
+        self.vault_state.check_approved(&self.executor.key(), &self.proposal)?;
+        // A vault reopened under the same id would otherwise revive the other proposals
+        require!(self.vault_state.open_proposals == 1, VaultError::ProposalsOutstanding);
+
+        let vault_id = self.vault_state.vault_id;
+        self.registry.vault_ids.retain(|id| *id != vault_id);
+
+        let seeds = &[
+            b"vault",
+            self.vault_state.to_account_info().key.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            Transfer {
+                from: self.vault.to_account_info(),
+                to: self.destination.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer(cpi_ctx, self.vault.lamports())
+    }
+}
+
+/// Cancel proposal context: the proposer withdraws their proposal.
+#[derive(Accounts)]
+pub struct CancelProposal<'info> {
+    /// The proposal's creator, refunded its rent.
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+
+    /// The multisig vault's state. Counts its open proposals.
+    #[account(mut)]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// The proposal being cancelled.
+    #[account(mut, has_one = proposer, has_one = vault_state, close = proposer)]
+    pub proposal: Account<'info, Proposal>,
+}
+
+impl<'info> CancelProposal<'info> {
+    /// Stops counting the proposal as open. Anchor closes the account afterwards.
+    pub fn cancel_proposal(&mut self) -> Result<()> {
+        self.vault_state.open_proposals = self.vault_state.open_proposals.saturating_sub(1);
+        Ok(())
+    }
+}
+
+/// Close expired proposal context: anyone clears a proposal that can no longer be executed.
+#[derive(Accounts)]
+pub struct CloseExpiredProposal<'info> {
+    /// Whoever clears the proposal.
+    pub caller: Signer<'info>,
+
+    /// The multisig vault's state. Counts its open proposals.
+    #[account(mut)]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// The expired proposal. Closed, with its rent refunded to the proposer.
+    #[account(mut, has_one = proposer, has_one = vault_state, close = proposer)]
+    pub proposal: Account<'info, Proposal>,
+
+    /// The proposal's creator, refunded its rent.
+    #[account(mut)]
+    pub proposer: SystemAccount<'info>,
+}
+
+impl<'info> CloseExpiredProposal<'info> {
+    /// Stops counting the proposal as open, once it has expired. Anchor closes the account afterwards.
+    pub fn close_expired_proposal(&mut self) -> Result<()> {
+        require!(self.proposal.is_expired(Clock::get()?.unix_timestamp), VaultError::ProposalNotExpired);
+        self.vault_state.open_proposals = self.vault_state.open_proposals.saturating_sub(1);
+        Ok(())
+    }
+}
+
+/// Grant allowance context: the vault owner lets a delegate spend from the vault.
+#[derive(Accounts)]
+#[instruction(delegate: Pubkey)]
+pub struct GrantAllowance<'info> {
+    /// The user who owns the vault. Pays for the allowance account.
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// PDA that stores the vault's state. Counts the vault's allowances.
+    #[account(
+        mut,
+        seeds = [b"state", user.key().as_ref(), vault_state.vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// PDA for the delegate's allowance on this vault.
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Allowance::INIT_SPACE,
+        seeds = [b"allowance", vault_state.key().as_ref(), delegate.as_ref()],
+        bump
+    )]
+    pub allowance: Account<'info, Allowance>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> GrantAllowance<'info> {
+    /// Writes the allowance's terms, with nothing spent yet, counting it on the vault if it's new.
+    pub fn grant_allowance(
+        &mut self,
+        delegate: Pubkey,
+        cap: u64,
+        period: Option<AllowancePeriod>,
+        expires_at: Option<i64>,
+        bump: u8,
+    ) -> Result<()> {
+        // A multisig vault's SOL only leaves through an approved proposal
+        require!(!self.vault_state.is_multisig(), VaultError::MultisigVault);
+        // The zeroed delegate marks a freshly created allowance below, so it can't be granted to
+        require_keys_neq!(delegate, Pubkey::default(), VaultError::InvalidAllowance);
+        require!(cap > 0, VaultError::InvalidAllowance);
+        if let Some(period) = period {
+            require!(period.duration > 0 && period.limit > 0, VaultError::InvalidAllowance);
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        if let Some(expires_at) = expires_at {
+            require!(expires_at > now, VaultError::InvalidAllowance);
+        }
+
+        // A freshly created allowance is still zeroed; a replaced one is already counted
+        if self.allowance.delegate == Pubkey::default() {
+            self.vault_state.allowance_count =
+                self.vault_state.allowance_count.checked_add(1).ok_or(VaultError::Overflow)?;
+        }
+
+        self.allowance.set_inner(Allowance {
+            vault_state: self.vault_state.key(),
+            delegate,
+            cap,
+            spent: 0,
+            period,
+            period_start: now,
+            period_spent: 0,
+            expires_at,
+            bump,
+        });
+
+        Ok(())
+    }
+}
+
+/// Spend context: a delegate spends from a vault within their allowance.
+#[derive(Accounts)]
+pub struct Spend<'info> {
+    /// The delegate the allowance was granted to.
+    pub delegate: Signer<'info>,
+
+    /// The vault's state.
+    #[account(mut)]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// PDA holding the vault's SOL.
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// The delegate's allowance on this vault.
+    #[account(
+        mut,
+        has_one = vault_state,
+        has_one = delegate,
+        seeds = [b"allowance", vault_state.key().as_ref(), delegate.key().as_ref()],
+        bump = allowance.bump
+    )]
+    pub allowance: Account<'info, Allowance>,
+
+    /// CHECK: Any account chosen by the delegate to receive the lamports.
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Spend<'info> {
+    /// Charges the allowance and transfers the lamports from the vault PDA to the destination.
+    pub fn spend(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        let now = Clock::get()?.unix_timestamp;
+        self.allowance.charge(amount, now)?;
+
+        // Spending never bypasses the vault's time lock
+        let balance = self.vault.lamports();
+        if let Some(time_lock) = self.vault_state.time_lock.as_mut() {
+            require!(amount <= time_lock.available(balance, now), VaultError::FundsLocked);
+            time_lock.withdrawn = time_lock.withdrawn.saturating_add(amount);
+        }
+
+        let seeds = &[
+            b"vault",
+            self.vault_state.to_account_info().key.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            Transfer {
+                from: self.vault.to_account_info(),
+                to: self.destination.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer(cpi_ctx, amount)
+    }
+}
+
+/// Revoke context: the vault owner closes a delegate's allowance.
+#[derive(Accounts)]
+pub struct Revoke<'info> {
+    /// The user who owns the vault. Refunded the allowance's rent.
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// PDA that stores the vault's state. Counts the vault's allowances.
+    #[account(
+        mut,
+        seeds = [b"state", user.key().as_ref(), vault_state.vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// The allowance being revoked.
+    #[account(
+        mut,
+        has_one = vault_state,
+        seeds = [b"allowance", vault_state.key().as_ref(), allowance.delegate.as_ref()],
+        bump = allowance.bump,
+        close = user
+    )]
+    pub allowance: Account<'info, Allowance>,
+}
+
+impl<'info> Revoke<'info> {
+    /// Stops counting the allowance on the vault. Anchor closes the account afterwards.
+    pub fn revoke(&mut self) -> Result<()> {
+        self.vault_state.allowance_count = self.vault_state.allowance_count.saturating_sub(1);
+        Ok(())
+    }
 }
 
 /// The PDA that stores bump seeds for this user's vault.
@@ -232,10 +1107,324 @@ pub struct VaultState {
 
     /// Bump for the `vault_state` PDA itself.
     pub state_bump: u8,
+
+    /// The vault's number among its owner's vaults, part of the `vault_state` seeds.
+    pub vault_id: u64,
+
+    /// The owner's name for the vault, e.g. "savings", at most `MAX_LABEL_LEN` bytes.
+    pub label: String,
+
+    /// Every mint the vault has held tokens of, at most `MAX_TOKEN_MINTS`.
+    pub token_mints: Vec<Pubkey>,
+
+    /// The time lock on the vault's SOL, if any.
+    pub time_lock: Option<TimeLock>,
+
+    /// For multisig vaults, the signers who approve withdrawals, at most `MAX_SIGNERS`. Empty otherwise.
+    pub signers: Vec<Pubkey>,
+
+    /// For multisig vaults, the number of signers that must approve a withdrawal.
+    pub threshold: u8,
+
+    /// Number of proposals made so far, and the index of the next one.
+    pub proposal_count: u64,
+
+    /// Number of proposals neither executed, cancelled nor closed after expiring. A multisig vault only closes
+    /// once its close proposal is the last one open.
+    pub open_proposals: u32,
+
+    /// Number of allowances granted on the vault and not yet revoked. The vault can't close while any remain.
+    pub allowance_count: u32,
 }
 
 impl VaultState {
+    /// Maximum length of a vault's label, in bytes.
+    pub const MAX_LABEL_LEN: usize = 32;
+
+    /// Maximum number of different mints a vault can hold.
+    pub const MAX_TOKEN_MINTS: usize = 8;
+
+    /// Maximum number of signers of a multisig vault.
+    pub const MAX_SIGNERS: usize = 10;
+
     /// Space needed for VaultState account.
-    /// Anchor discriminator: 8 bytes + 1 byte for each bump.
-    pub const INIT_SPACE: usize = 8 + 1 + 1;
+    /// Anchor discriminator: 8 bytes + 1 byte for each bump + 8 byte vault id + the label (4 byte length + its bytes)
+    /// + the token mints vector (4 byte length + 32 bytes per mint)
+    /// + the optional time lock (1 byte tag + `TimeLock::SPACE`) + the signers vector (4 byte length + 32 bytes per signer)
+    /// + 1 byte threshold + 8 byte proposal count + 4 byte open proposal count + 4 byte allowance count.
+    pub const INIT_SPACE: usize = 8 + 1 + 1 + 8 + 4 + Self::MAX_LABEL_LEN + 4 + 32 * Self::MAX_TOKEN_MINTS
+        + 1 + TimeLock::SPACE + 4 + 32 * Self::MAX_SIGNERS + 1 + 8 + 4 + 4;
+
+    /// Returns true for multisig vaults.
+    pub fn is_multisig(&self) -> bool {
+        !self.signers.is_empty()
+    }
+
+    /// Checks that `key` is one of the multisig vault's signers.
+    pub fn check_signer(&self, key: &Pubkey) -> Result<()> {
+        require!(self.signers.contains(key), VaultError::NotASigner);
+        Ok(())
+    }
+
+    /// Checks that `executor` is one of the vault's signers, and that `proposal` is unexpired
+    /// and approved by at least `threshold` of them.
+    pub fn check_approved(&self, executor: &Pubkey, proposal: &Proposal) -> Result<()> {
+        self.check_signer(executor)?;
+        require!(!proposal.is_expired(Clock::get()?.unix_timestamp), VaultError::ProposalExpired);
+        require!(proposal.approvals.len() >= self.threshold as usize, VaultError::NotEnoughApprovals);
+        Ok(())
+    }
+}
+
+/// The PDA listing a user's vaults, so clients can find them all.
+#[account]
+pub struct VaultRegistry {
+    /// The user whose vaults are listed.
+    pub owner: Pubkey,
+
+    /// The id of each of the user's open vaults, at most `MAX_VAULTS`.
+    pub vault_ids: Vec<u64>,
+
+    /// Bump for the `registry` PDA.
+    pub bump: u8,
+}
+
+impl VaultRegistry {
+    /// Maximum number of open vaults per user.
+    pub const MAX_VAULTS: usize = 16;
+
+    /// Space needed for VaultRegistry account.
+    /// Anchor discriminator: 8 bytes + 32 byte owner + the vault ids vector (4 byte length + 8 bytes per id) + 1 byte bump.
+    pub const INIT_SPACE: usize = 8 + 32 + 4 + 8 * Self::MAX_VAULTS + 1;
+}
+
+/// A pending withdrawal from, or closing of, a multisig vault.
+#[account]
+pub struct Proposal {
+    /// The multisig vault the lamports come from.
+    pub vault_state: Pubkey,
+
+    /// The signer who made the proposal, and paid its rent.
+    pub proposer: Pubkey,
+
+    /// Where the lamports go.
+    pub destination: Pubkey,
+
+    /// Lamports to withdraw. Zero for close proposals, which send everything.
+    pub amount: u64,
+
+    /// True to close the vault instead of withdrawing from it.
+    pub close: bool,
+
+    /// Unix timestamp after which the proposal can no longer be approved or executed.
+    pub expires_at: i64,
+
+    /// Signers that approved the proposal, the proposer included.
+    pub approvals: Vec<Pubkey>,
+
+    /// Bump for the `proposal` PDA.
+    pub bump: u8,
+}
+
+impl Proposal {
+    /// Space needed for Proposal account.
+    /// Anchor discriminator: 8 bytes + 3 pubkeys + 8 byte amount + 1 byte close flag + 8 byte expiry
+    /// + the approvals vector (4 byte length + 32 bytes per signer) + 1 byte bump.
+    pub const INIT_SPACE: usize = 8 + 32 * 3 + 8 + 1 + 8 + 4 + 32 * VaultState::MAX_SIGNERS + 1;
+
+    /// Returns true once `now` is past the proposal's expiry.
+    pub fn is_expired(&self, now: i64) -> bool {
+        now > self.expires_at
+    }
+}
+
+/// A delegate's allowance to spend SOL from a vault.
+#[account]
+pub struct Allowance {
+    /// The vault the allowance is on.
+    pub vault_state: Pubkey,
+
+    /// The wallet allowed to spend.
+    pub delegate: Pubkey,
+
+    /// Lamports the delegate may spend in total.
+    pub cap: u64,
+
+    /// Lamports spent so far.
+    pub spent: u64,
+
+    /// The per-period limit, if any.
+    pub period: Option<AllowancePeriod>,
+
+    /// Unix timestamp the current period started.
+    pub period_start: i64,
+
+    /// Lamports spent in the current period.
+    pub period_spent: u64,
+
+    /// Unix timestamp after which the allowance can't be spent, if any.
+    pub expires_at: Option<i64>,
+
+    /// Bump for the `allowance` PDA.
+    pub bump: u8,
+}
+
+impl Allowance {
+    /// Space needed for Allowance account.
+    /// Anchor discriminator: 8 bytes + 2 pubkeys + cap and spent (8 bytes each) + the optional period (1 + 8 + 8 bytes)
+    /// + period start and spent (8 bytes each) + the optional expiry (1 + 8 bytes) + 1 byte bump.
+    pub const INIT_SPACE: usize = 8 + 32 * 2 + 8 + 8 + 1 + 8 + 8 + 8 + 8 + 1 + 8 + 1;
+
+    /// Records spending `amount` at `now`, failing if it's over the cap or the current period's limit,
+    /// or the allowance has expired. A new period starts once the current one has lasted its duration.
+    pub fn charge(&mut self, amount: u64, now: i64) -> Result<()> {
+        if let Some(expires_at) = self.expires_at {
+            require!(now <= expires_at, VaultError::AllowanceExpired);
+        }
+
+        let spent = self.spent.checked_add(amount).ok_or(VaultError::Overflow)?;
+        require!(spent <= self.cap, VaultError::AllowanceExceeded);
+        self.spent = spent;
+
+        if let Some(period) = self.period {
+            if now.saturating_sub(self.period_start) >= period.duration {
+                self.period_start = now;
+                self.period_spent = 0;
+            }
+            let period_spent = self.period_spent.checked_add(amount).ok_or(VaultError::Overflow)?;
+            require!(period_spent <= period.limit, VaultError::AllowanceExceeded);
+            self.period_spent = period_spent;
+        }
+
+        Ok(())
+    }
+}
+
+/// A limit on how much of an allowance can be spent per period.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllowancePeriod {
+    /// Length of a period, in seconds.
+    pub duration: i64,
+
+    /// Lamports that may be spent per period.
+    pub limit: u64,
+}
+
+/// When a time-locked vault's SOL unlocks.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockSchedule {
+    /// Nothing unlocks before this unix timestamp.
+    pub cliff_ts: i64,
+
+    /// Without an end, everything unlocks at the cliff. With one, the locked SOL vests linearly
+    /// from when the lock was set until this unix timestamp, and what has vested unlocks at the cliff.
+    pub end_ts: Option<i64>,
+}
+
+/// A time lock in force on a vault's SOL.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeLock {
+    pub schedule: LockSchedule,
+
+    /// When the lock was set, and vesting started.
+    pub start_ts: i64,
+
+    /// Lamports subject to the lock: the vault's balance when it was set, plus any deposits since
+    /// under a cliff-only schedule. A vesting schedule refuses deposits until it has fully unlocked.
+    pub total: u64,
+
+    /// Lamports withdrawn since the lock was set.
+    pub withdrawn: u64,
+}
+
+impl TimeLock {
+    /// Serialized size: the schedule (8 + 1 + 8 bytes) + start, total and withdrawn (8 bytes each).
+    pub const SPACE: usize = 8 + 1 + 8 + 8 + 8 + 8;
+
+    /// Starts a lock on `schedule` at `now`, over the vault's current `balance`.
+    pub fn new(schedule: LockSchedule, now: i64, balance: u64) -> Result<Self> {
+        if let Some(end_ts) = schedule.end_ts {
+            require!(end_ts >= schedule.cliff_ts && end_ts > now, VaultError::InvalidLockSchedule);
+        }
+        Ok(Self { schedule, start_ts: now, total: balance, withdrawn: 0 })
+    }
+
+    /// Returns true once everything has unlocked, at the cliff or at the end of vesting.
+    pub fn is_unlocked(&self, now: i64) -> bool {
+        now >= self.schedule.end_ts.unwrap_or(self.schedule.cliff_ts)
+    }
+
+    /// Returns the lamports unlocked by `now`, including those already withdrawn.
+    pub fn vested(&self, now: i64) -> u64 {
+        match self.schedule.end_ts {
+            _ if now < self.schedule.cliff_ts => 0,
+            Some(end_ts) if now < end_ts => {
+                let elapsed = (now - self.start_ts) as u128;
+                let duration = (end_ts - self.start_ts) as u128;
+                (self.total as u128 * elapsed / duration) as u64
+            }
+            _ => self.total,
+        }
+    }
+
+    /// Returns how much of the vault's `balance` can be withdrawn at `now`.
+    /// Once fully unlocked that's everything, including SOL sent to the vault without `deposit`.
+    pub fn available(&self, balance: u64, now: i64) -> u64 {
+        if self.is_unlocked(now) {
+            return balance;
+        }
+        self.vested(now).saturating_sub(self.withdrawn).min(balance)
+    }
+}
+
+#[error_code]
+pub enum VaultError {
+    #[msg("Amount must be greater than zero.")]
+    InvalidAmount,
+    #[msg("The vault already holds the maximum number of different mints.")]
+    TooManyTokenMints,
+    #[msg("A vault token account, or its token program, was not passed to close.")]
+    MissingTokenAccount,
+    #[msg("The vault still holds tokens; withdraw them before closing.")]
+    TokenBalanceRemaining,
+    #[msg("Vesting must end at or after the cliff, and in the future.")]
+    InvalidLockSchedule,
+    #[msg("The vault is time-locked.")]
+    VaultLocked,
+    #[msg("Amount exceeds what the time lock has unlocked.")]
+    FundsLocked,
+    #[msg("The vault is vesting; it takes no deposits until fully unlocked.")]
+    VestingUnderway,
+    #[msg("Arithmetic overflow.")]
+    Overflow,
+    #[msg("Multisig vaults need 1 to 10 distinct signers and a threshold between 1 and their number.")]
+    InvalidMultisig,
+    #[msg("Multisig vaults only release SOL through approved proposals.")]
+    MultisigVault,
+    #[msg("Signer is not one of the vault's signers.")]
+    NotASigner,
+    #[msg("The proposal has expired.")]
+    ProposalExpired,
+    #[msg("The proposal has not expired yet.")]
+    ProposalNotExpired,
+    #[msg("The signer already approved this proposal.")]
+    AlreadyApproved,
+    #[msg("The proposal does not have enough approvals.")]
+    NotEnoughApprovals,
+    #[msg("The proposal is for a different action.")]
+    WrongProposal,
+    #[msg("The vault has other open proposals; execute, cancel or clear expired ones before closing.")]
+    ProposalsOutstanding,
+    #[msg("Allowances need a delegate, a cap, a positive period and limit if any, and a future expiry if any.")]
+    InvalidAllowance,
+    #[msg("The allowance has expired.")]
+    AllowanceExpired,
+    #[msg("Amount exceeds what the allowance has left.")]
+    AllowanceExceeded,
+    #[msg("Vault labels can be at most 32 bytes.")]
+    LabelTooLong,
+    #[msg("The user already has the maximum number of vaults.")]
+    TooManyVaults,
+    #[msg("The vault still has allowances; revoke them before closing.")]
+    AllowancesOutstanding,
 }
@@ -2,6 +2,14 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import type { Vault } from "../target/types/vault";
 import { LAMPORTS_PER_SOL, PublicKey } from "@solana/web3.js";
+import {
+  TOKEN_PROGRAM_ID,
+  createMint,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from "@solana/spl-token";
 import { assert } from "chai";
 import "dotenv/config";
 import BN from "bn.js";
@@ -18,9 +26,14 @@ describe("Vault", () => {
 
   const program = anchor.workspace.vault as Program<Vault>;
 
+  // A vault's id, as used in its state PDA's seeds
+  const vaultIdSeed = (id: number) => new BN(id).toArrayLike(Buffer, "le", 8);
+  const registryFor = (owner: PublicKey) =>
+    PublicKey.findProgramAddressSync([Buffer.from("registry"), owner.toBytes()], program.programId)[0];
+
   // Derive PDAs for state & vault
   const [vaultStatePDA, vaultStateBump] = PublicKey.findProgramAddressSync(
-    [Buffer.from("state"), user.publicKey.toBytes()],
+    [Buffer.from("state"), user.publicKey.toBytes(), vaultIdSeed(0)],
     program.programId
   );
 
@@ -29,6 +42,8 @@ describe("Vault", () => {
     program.programId
   );
 
+  const registryPDA = registryFor(user.publicKey);
+
   // Helper: bumps struct expected by the program
   const vaultBumps = {
     stateBump: vaultStateBump,
@@ -37,11 +52,12 @@ describe("Vault", () => {
 
   it("Initializes the vault", async () => {
     const tx = await program.methods
-      .initialize() // single struct argument
+      .initialize(new BN(0), "main", null) // no time lock
       .accounts({
         user: user.publicKey,
         vaultState: vaultStatePDA,
         vault: vaultPDA,
+        registry: registryPDA,
         systemProgram: anchor.web3.SystemProgram.programId,
       })
       .rpc();
@@ -50,6 +66,25 @@ describe("Vault", () => {
 
     const stateAccount = await program.account.vaultState.fetch(vaultStatePDA);
     assert.isOk(stateAccount, "Vault state not initialized properly");
+    assert.equal(stateAccount.label, "main");
+  });
+
+  it("Creates a second, independent vault", async () => {
+    const [savingsStatePDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("state"), user.publicKey.toBytes(), vaultIdSeed(1)],
+      program.programId
+    );
+    const [savingsVaultPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault"), savingsStatePDA.toBytes()],
+      program.programId
+    );
+    await program.methods
+      .initialize(new BN(1), "savings", null)
+      .accounts({ user: user.publicKey, vaultState: savingsStatePDA, vault: savingsVaultPDA, registry: registryPDA })
+      .rpc();
+
+    const registry = await program.account.vaultRegistry.fetch(registryPDA);
+    assert.deepEqual(registry.vaultIds.map((id) => id.toNumber()), [0, 1], "Registry does not list both vaults");
   });
 
   it("Deposits funds", async () => {
@@ -101,17 +136,74 @@ describe("Vault", () => {
     assert(balance >= 0, "Withdraw failed");
   });
 
-  it("Closes the vault", async () => {
-    const tx = await program.methods
+  // SPL token held by the vault, in the vault PDA's associated token account
+  let mint: PublicKey;
+  let userAta: PublicKey;
+  let vaultAta: PublicKey;
+
+  const tokenAccounts = () => ({
+    user: user.publicKey,
+    vaultState: vaultStatePDA,
+    vault: vaultPDA,
+    mint,
+    userTokenAccount: userAta,
+    vaultTokenAccount: vaultAta,
+    tokenProgram: TOKEN_PROGRAM_ID,
+  });
+
+  const closeVault = () =>
+    program.methods
       .close()
       .accounts({
         user: user.publicKey,
         vaultState: vaultStatePDA,
         vault: vaultPDA,
+        registry: registryPDA,
         systemProgram: anchor.web3.SystemProgram.programId,
       })
+      .remainingAccounts([
+        { pubkey: vaultAta, isSigner: false, isWritable: true },
+        { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+      ])
       .rpc();
 
+  it("Deposits SPL tokens", async () => {
+    mint = await createMint(provider.connection, user, user.publicKey, null, 6);
+    userAta = (await getOrCreateAssociatedTokenAccount(provider.connection, user, mint, user.publicKey)).address;
+    vaultAta = getAssociatedTokenAddressSync(mint, vaultPDA, true);
+    await mintTo(provider.connection, user, mint, userAta, user, 1_000_000);
+
+    const tx = await program.methods.depositToken(new BN(400_000)).accounts(tokenAccounts()).rpc();
+    console.log("Deposit token TX:", tx);
+
+    const vaultTokens = await getAccount(provider.connection, vaultAta);
+    assert.equal(vaultTokens.amount.toString(), "400000", "Vault did not receive the tokens");
+    const stateAccount = await program.account.vaultState.fetch(vaultStatePDA);
+    assert.isTrue(stateAccount.tokenMints[0].equals(mint), "Mint was not recorded");
+  });
+
+  it("Refuses to close while tokens remain", async () => {
+    try {
+      await closeVault();
+      assert.fail("Close should have failed");
+    } catch (err) {
+      assert.include(String(err), "TokenBalanceRemaining");
+    }
+  });
+
+  it("Withdraws SPL tokens", async () => {
+    const tx = await program.methods.withdrawToken(new BN(400_000)).accounts(tokenAccounts()).rpc();
+    console.log("Withdraw token TX:", tx);
+
+    const vaultTokens = await getAccount(provider.connection, vaultAta);
+    const userTokens = await getAccount(provider.connection, userAta);
+    assert.equal(vaultTokens.amount.toString(), "0", "Vault still holds tokens");
+    assert.equal(userTokens.amount.toString(), "1000000", "User did not get the tokens back");
+  });
+
+  it("Closes the vault", async () => {
+    const tx = await closeVault();
+
     console.log("Close TX:", tx);
 
     const accountInfo = await provider.connection.getAccountInfo(vaultStatePDA);
@@ -119,5 +211,427 @@ describe("Vault", () => {
       accountInfo === null,
       "Vault state account still exists after close"
     );
+    assert.isNull(await provider.connection.getAccountInfo(vaultAta), "Vault token account was not closed");
+    const registry = await program.account.vaultRegistry.fetch(registryPDA);
+    assert.deepEqual(registry.vaultIds.map((id) => id.toNumber()), [1], "Closed vault still registered");
+  });
+
+  describe("Time lock", () => {
+    // A separate wallet, so its vault can stay locked
+    const owner = anchor.web3.Keypair.generate();
+    const [lockedStatePDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("state"), owner.publicKey.toBytes(), vaultIdSeed(0)],
+      program.programId
+    );
+    const [lockedVaultPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault"), lockedStatePDA.toBytes()],
+      program.programId
+    );
+    const accounts = {
+      user: owner.publicKey,
+      vaultState: lockedStatePDA,
+      vault: lockedVaultPDA,
+      registry: registryFor(owner.publicKey),
+      systemProgram: anchor.web3.SystemProgram.programId,
+    };
+
+    const expectError = async (promise: Promise<unknown>, code: string) => {
+      try {
+        await promise;
+        assert.fail(`Expected ${code}`);
+      } catch (err) {
+        assert.include(String(err), code);
+      }
+    };
+
+    before(async () => {
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig, "confirmed");
+    });
+
+    it("Initializes a vault locked until a cliff", async () => {
+      const cliffTs = new BN(Math.floor(Date.now() / 1000) + 3600);
+      await program.methods
+        .initialize(new BN(0), "vesting", { cliffTs, endTs: null })
+        .accounts(accounts)
+        .signers([owner])
+        .rpc();
+      await program.methods
+        .deposit(new BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts(accounts)
+        .signers([owner])
+        .rpc();
+
+      const state = await program.account.vaultState.fetch(lockedStatePDA);
+      assert.isTrue(state.timeLock.schedule.cliffTs.eq(cliffTs), "Cliff not stored");
+      assert.equal(state.timeLock.total.toNumber(), 0.1 * LAMPORTS_PER_SOL, "Deposit not locked");
+    });
+
+    it("Refuses to release SOL before the cliff", async () => {
+      await expectError(
+        program.methods.withdraw(new BN(1)).accounts(accounts).signers([owner]).rpc(),
+        "FundsLocked"
+      );
+      await expectError(
+        program.methods.close().accounts(accounts).signers([owner]).rpc(),
+        "VaultLocked"
+      );
+    });
+
+    it("Refuses to replace a lock still in force", async () => {
+      await expectError(
+        program.methods
+          .setLock({ cliffTs: new BN(0), endTs: null })
+          .accounts({ user: owner.publicKey, vaultState: lockedStatePDA, vault: lockedVaultPDA })
+          .signers([owner])
+          .rpc(),
+        "VaultLocked"
+      );
+    });
+
+    it("Refuses deposits once vesting is under way", async () => {
+      const [vestingStatePDA] = PublicKey.findProgramAddressSync(
+        [Buffer.from("state"), owner.publicKey.toBytes(), vaultIdSeed(1)],
+        program.programId
+      );
+      const [vestingVaultPDA] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), vestingStatePDA.toBytes()],
+        program.programId
+      );
+      const vestingAccounts = { ...accounts, vaultState: vestingStatePDA, vault: vestingVaultPDA };
+      const now = Math.floor(Date.now() / 1000);
+
+      // Fund first, then lock: the balance vests from when the lock is set
+      await program.methods.initialize(new BN(1), "grant", null).accounts(vestingAccounts).signers([owner]).rpc();
+      await program.methods
+        .deposit(new BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts(vestingAccounts)
+        .signers([owner])
+        .rpc();
+      await program.methods
+        .setLock({ cliffTs: new BN(now), endTs: new BN(now + 3600) })
+        .accounts({ user: owner.publicKey, vaultState: vestingStatePDA, vault: vestingVaultPDA })
+        .signers([owner])
+        .rpc();
+
+      await expectError(
+        program.methods.deposit(new BN(1)).accounts(vestingAccounts).signers([owner]).rpc(),
+        "VestingUnderway"
+      );
+    });
+  });
+
+  describe("Multisig", () => {
+    const creator = anchor.web3.Keypair.generate();
+    const [alice, bob, carol] = [0, 1, 2].map(() => anchor.web3.Keypair.generate());
+    const destination = anchor.web3.Keypair.generate().publicKey;
+    const [msStatePDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("state"), creator.publicKey.toBytes(), vaultIdSeed(0)],
+      program.programId
+    );
+    const [msVaultPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault"), msStatePDA.toBytes()],
+      program.programId
+    );
+    const [proposalPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("proposal"), msStatePDA.toBytes(), new BN(0).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const amount = new BN(0.05 * LAMPORTS_PER_SOL);
+
+    const executeAccounts = (executor: PublicKey) => ({
+      executor,
+      vaultState: msStatePDA,
+      vault: msVaultPDA,
+      proposal: proposalPDA,
+      destination,
+      proposer: alice.publicKey,
+    });
+
+    before(async () => {
+      for (const wallet of [creator, alice, bob, carol]) {
+        const sig = await provider.connection.requestAirdrop(wallet.publicKey, LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig, "confirmed");
+      }
+    });
+
+    it("Initializes a 2-of-3 vault", async () => {
+      await program.methods
+        .initializeMultisig(new BN(0), "treasury", [alice.publicKey, bob.publicKey, carol.publicKey], 2)
+        .accounts({
+          user: creator.publicKey,
+          vaultState: msStatePDA,
+          vault: msVaultPDA,
+          registry: registryFor(creator.publicKey),
+        })
+        .signers([creator])
+        .rpc();
+      await program.methods
+        .deposit(new BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({ user: creator.publicKey, vaultState: msStatePDA, vault: msVaultPDA })
+        .signers([creator])
+        .rpc();
+
+      const state = await program.account.vaultState.fetch(msStatePDA);
+      assert.equal(state.threshold, 2);
+      assert.equal(state.signers.length, 3);
+    });
+
+    it("Refuses a direct withdrawal", async () => {
+      try {
+        await program.methods
+          .withdraw(amount)
+          .accounts({ user: creator.publicKey, vaultState: msStatePDA, vault: msVaultPDA })
+          .signers([creator])
+          .rpc();
+        assert.fail("Withdraw should have failed");
+      } catch (err) {
+        assert.include(String(err), "MultisigVault");
+      }
+    });
+
+    it("Withdraws once enough signers approve", async () => {
+      const expiresAt = new BN(Math.floor(Date.now() / 1000) + 3600);
+      await program.methods
+        .proposeWithdrawal(amount, destination, expiresAt)
+        .accounts({ proposer: alice.publicKey, vaultState: msStatePDA, proposal: proposalPDA })
+        .signers([alice])
+        .rpc();
+
+      // The proposer's own approval is one of two
+      try {
+        await program.methods.execute().accounts(executeAccounts(carol.publicKey)).signers([carol]).rpc();
+        assert.fail("Execute should have failed");
+      } catch (err) {
+        assert.include(String(err), "NotEnoughApprovals");
+      }
+
+      await program.methods
+        .approve()
+        .accounts({ signer: bob.publicKey, vaultState: msStatePDA, proposal: proposalPDA })
+        .signers([bob])
+        .rpc();
+      await program.methods.execute().accounts(executeAccounts(carol.publicKey)).signers([carol]).rpc();
+
+      assert.equal(await provider.connection.getBalance(destination), amount.toNumber());
+      assert.isNull(await provider.connection.getAccountInfo(proposalPDA), "Proposal was not closed");
+    });
+
+    it("Lets anyone clear an expired proposal", async () => {
+      const [stalePDA] = PublicKey.findProgramAddressSync(
+        [Buffer.from("proposal"), msStatePDA.toBytes(), new BN(1).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const chainTime = async () =>
+        (await provider.connection.getBlockTime(await provider.connection.getSlot())) ?? 0;
+      const expiresAt = new BN((await chainTime()) + 2);
+      await program.methods
+        .proposeWithdrawal(amount, destination, expiresAt)
+        .accounts({ proposer: alice.publicKey, vaultState: msStatePDA, proposal: stalePDA })
+        .signers([alice])
+        .rpc();
+
+      const clearStale = () =>
+        program.methods
+          .closeExpiredProposal()
+          .accounts({ caller: carol.publicKey, vaultState: msStatePDA, proposal: stalePDA, proposer: alice.publicKey })
+          .signers([carol])
+          .rpc();
+      try {
+        await clearStale();
+        assert.fail("Clearing should have failed");
+      } catch (err) {
+        assert.include(String(err), "ProposalNotExpired");
+      }
+
+      while ((await chainTime()) <= expiresAt.toNumber()) {
+        await new Promise((resolve) => setTimeout(resolve, 500));
+      }
+      await clearStale();
+      assert.isNull(await provider.connection.getAccountInfo(stalePDA), "Proposal was not closed");
+      const state = await program.account.vaultState.fetch(msStatePDA);
+      assert.equal(state.openProposals, 0);
+    });
+
+    it("Closes the vault once enough signers approve", async () => {
+      const [closePDA] = PublicKey.findProgramAddressSync(
+        [Buffer.from("proposal"), msStatePDA.toBytes(), new BN(2).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const expiresAt = new BN(Math.floor(Date.now() / 1000) + 3600);
+      await program.methods
+        .proposeClose(destination, expiresAt)
+        .accounts({ proposer: bob.publicKey, vaultState: msStatePDA, proposal: closePDA })
+        .signers([bob])
+        .rpc();
+      await program.methods
+        .approve()
+        .accounts({ signer: carol.publicKey, vaultState: msStatePDA, proposal: closePDA })
+        .signers([carol])
+        .rpc();
+
+      // A close proposal can't be executed as a withdrawal
+      try {
+        await program.methods
+          .execute()
+          .accounts({ ...executeAccounts(alice.publicKey), proposal: closePDA, proposer: bob.publicKey })
+          .signers([alice])
+          .rpc();
+        assert.fail("Execute should have failed");
+      } catch (err) {
+        assert.include(String(err), "WrongProposal");
+      }
+
+      const before = await provider.connection.getBalance(destination);
+      const vaultBalance = await provider.connection.getBalance(msVaultPDA);
+      await program.methods
+        .executeClose()
+        .accounts({
+          executor: alice.publicKey,
+          vaultState: msStatePDA,
+          vault: msVaultPDA,
+          registry: registryFor(creator.publicKey),
+          proposal: closePDA,
+          destination,
+          proposer: bob.publicKey,
+        })
+        .signers([alice])
+        .rpc();
+
+      assert.isNull(await provider.connection.getAccountInfo(msStatePDA), "Vault state was not closed");
+      assert.isNull(await provider.connection.getAccountInfo(closePDA), "Proposal was not closed");
+      assert.isAtLeast(await provider.connection.getBalance(destination), before + vaultBalance);
+      const registry = await program.account.vaultRegistry.fetch(registryFor(creator.publicKey));
+      assert.deepEqual(registry.vaultIds, [], "Closed vault still registered");
+    });
+  });
+
+  describe("Allowances", () => {
+    const owner = anchor.web3.Keypair.generate();
+    const delegate = anchor.web3.Keypair.generate();
+    const destination = anchor.web3.Keypair.generate().publicKey;
+    const [ownerStatePDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("state"), owner.publicKey.toBytes(), vaultIdSeed(0)],
+      program.programId
+    );
+    const [ownerVaultPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault"), ownerStatePDA.toBytes()],
+      program.programId
+    );
+    const [allowancePDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("allowance"), ownerStatePDA.toBytes(), delegate.publicKey.toBytes()],
+      program.programId
+    );
+    const limit = new BN(0.02 * LAMPORTS_PER_SOL);
+
+    const spend = (amount: BN) =>
+      program.methods
+        .spend(amount)
+        .accounts({
+          delegate: delegate.publicKey,
+          vaultState: ownerStatePDA,
+          vault: ownerVaultPDA,
+          allowance: allowancePDA,
+          destination,
+        })
+        .signers([delegate])
+        .rpc();
+
+    before(async () => {
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig, "confirmed");
+
+      const accounts = { user: owner.publicKey, vaultState: ownerStatePDA, vault: ownerVaultPDA };
+      await program.methods
+        .initialize(new BN(0), "payroll", null)
+        .accounts({ ...accounts, registry: registryFor(owner.publicKey) })
+        .signers([owner])
+        .rpc();
+      await program.methods
+        .deposit(new BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts(accounts)
+        .signers([owner])
+        .rpc();
+    });
+
+    it("Grants a delegate an allowance with a daily limit", async () => {
+      await program.methods
+        .grantAllowance(delegate.publicKey, new BN(0.05 * LAMPORTS_PER_SOL), { duration: new BN(86400), limit }, null)
+        .accounts({ user: owner.publicKey, vaultState: ownerStatePDA, allowance: allowancePDA })
+        .signers([owner])
+        .rpc();
+
+      const allowance = await program.account.allowance.fetch(allowancePDA);
+      assert.isTrue(allowance.delegate.equals(delegate.publicKey));
+      assert.equal(allowance.spent.toNumber(), 0);
+      const state = await program.account.vaultState.fetch(ownerStatePDA);
+      assert.equal(state.allowanceCount, 1);
+
+      // The default key can't be a delegate
+      const [defaultAllowancePDA] = PublicKey.findProgramAddressSync(
+        [Buffer.from("allowance"), ownerStatePDA.toBytes(), PublicKey.default.toBytes()],
+        program.programId
+      );
+      try {
+        await program.methods
+          .grantAllowance(PublicKey.default, new BN(1), null, null)
+          .accounts({ user: owner.publicKey, vaultState: ownerStatePDA, allowance: defaultAllowancePDA })
+          .signers([owner])
+          .rpc();
+        assert.fail("Grant should have failed");
+      } catch (err) {
+        assert.include(String(err), "InvalidAllowance");
+      }
+    });
+
+    it("Spends within the period limit only", async () => {
+      await spend(limit);
+      assert.equal(await provider.connection.getBalance(destination), limit.toNumber());
+
+      try {
+        await spend(new BN(1));
+        assert.fail("Spend should have failed");
+      } catch (err) {
+        assert.include(String(err), "AllowanceExceeded");
+      }
+    });
+
+    const closeOwnerVault = () =>
+      program.methods
+        .close()
+        .accounts({
+          user: owner.publicKey,
+          vaultState: ownerStatePDA,
+          vault: ownerVaultPDA,
+          registry: registryFor(owner.publicKey),
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+    it("Refuses to close while an allowance remains", async () => {
+      try {
+        await closeOwnerVault();
+        assert.fail("Close should have failed");
+      } catch (err) {
+        assert.include(String(err), "AllowancesOutstanding");
+      }
+    });
+
+    it("Revokes the allowance", async () => {
+      await program.methods
+        .revoke()
+        .accounts({ user: owner.publicKey, vaultState: ownerStatePDA, allowance: allowancePDA })
+        .signers([owner])
+        .rpc();
+      assert.isNull(await provider.connection.getAccountInfo(allowancePDA), "Allowance was not closed");
+      const state = await program.account.vaultState.fetch(ownerStatePDA);
+      assert.equal(state.allowanceCount, 0);
+    });
+
+    it("Closes the vault once its allowances are revoked", async () => {
+      await closeOwnerVault();
+      assert.isNull(await provider.connection.getAccountInfo(ownerStatePDA), "Vault state was not closed");
+    });
   });
 });
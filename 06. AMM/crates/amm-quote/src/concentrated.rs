@@ -0,0 +1,281 @@
+// Concentrated liquidity math.
+//
+// Liquidity is provided over a price range [tick_lower, tick_upper), where the price at tick i
+// is 1.0001^i (token Y per token X). Prices are handled as their square root in Q64.64 fixed
+// point, which keeps the liquidity formulas linear:
+//     x = L * (1/sqrt(P_a) - 1/sqrt(P_b))      y = L * (sqrt(P_b) - sqrt(P_a))
+// for liquidity L between sqrt prices sqrt(P_a) < sqrt(P_b).
+//
+// A swap moves the price through a sequence of steps, each staying inside a range where the
+// active liquidity is constant. Every rounding step favours the pool: amounts paid in are
+// rounded up, amounts paid out are rounded down.
+
+use crate::{ math::{ mul_div, mul_div_ceil }, QuoteError };
+
+/// Lowest tick with a representable sqrt price.
+pub const MIN_TICK: i32 = -443_636;
+/// Highest tick with a representable sqrt price.
+pub const MAX_TICK: i32 = 443_636;
+/// Sqrt price (Q64.64) at `MIN_TICK`.
+pub const MIN_SQRT_PRICE: u128 = 4_295_048_016;
+/// Sqrt price (Q64.64) at `MAX_TICK`.
+pub const MAX_SQRT_PRICE: u128 = 79_226_673_521_066_979_257_578_248_091;
+
+const Q64: u128 = 1 << 64;
+
+/// 2^64 / sqrt(1.0001)^(2^i), for each bit i of a tick.
+const TICK_FACTORS: [u128; 19] = [
+    18_445_821_805_675_392_311,
+    18_444_899_583_751_176_498,
+    18_443_055_278_223_354_162,
+    18_439_367_220_385_604_838,
+    18_431_993_317_065_449_817,
+    18_417_254_355_718_160_513,
+    18_387_811_781_193_591_352,
+    18_329_067_761_203_520_168,
+    18_212_142_134_806_087_854,
+    17_980_523_815_641_551_639,
+    17_526_086_738_831_147_013,
+    16_651_378_430_235_024_244,
+    15_030_750_278_693_429_944,
+    12_247_334_978_882_834_399,
+    8_131_365_268_884_726_200,
+    3_584_323_654_723_342_297,
+    696_457_651_847_595_233,
+    26_294_789_957_452_057,
+    37_481_735_321_082,
+];
+
+/// Returns the sqrt price (Q64.64) at `tick`, i.e. sqrt(1.0001^tick) * 2^64.
+pub fn sqrt_price_at_tick(tick: i32) -> Result<u128, QuoteError> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(QuoteError::InvalidTick);
+    }
+    // Build 1 / sqrt(1.0001)^|tick| from the bits of |tick|; every factor is below one,
+    // so the running product stays within 64 bits
+    let abs = tick.unsigned_abs();
+    let mut ratio = Q64;
+    for (bit, factor) in TICK_FACTORS.iter().enumerate() {
+        if abs & (1 << bit) != 0 {
+            ratio = (ratio * factor) >> 64;
+        }
+    }
+    if tick > 0 {
+        mul_div(Q64, Q64, ratio).ok_or(QuoteError::Overflow)
+    } else {
+        Ok(ratio)
+    }
+}
+
+/// Returns the highest tick whose sqrt price is at or below `sqrt_price`.
+pub fn tick_at_sqrt_price(sqrt_price: u128) -> Result<i32, QuoteError> {
+    if !(MIN_SQRT_PRICE..=MAX_SQRT_PRICE).contains(&sqrt_price) {
+        return Err(QuoteError::InvalidPrice);
+    }
+    let (mut lo, mut hi) = (MIN_TICK, MAX_TICK);
+    while lo < hi {
+        let mid = lo + (hi - lo + 1) / 2;
+        if sqrt_price_at_tick(mid)? <= sqrt_price {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    Ok(lo)
+}
+
+/// Token X held by `liquidity` between two sqrt prices: L * (b - a) / (a * b), in Q64.64.
+pub fn amount_x_delta(sqrt_price_a: u128, sqrt_price_b: u128, liquidity: u128, round_up: bool) -> Result<u128, QuoteError> {
+    let (lo, hi) = if sqrt_price_a < sqrt_price_b { (sqrt_price_a, sqrt_price_b) } else { (sqrt_price_b, sqrt_price_a) };
+    if lo == 0 {
+        return Err(QuoteError::InvalidPrice);
+    }
+    let div = if round_up { mul_div_ceil } else { mul_div };
+    let scaled = div(liquidity, hi - lo, hi).ok_or(QuoteError::Overflow)?;
+    div(scaled, Q64, lo).ok_or(QuoteError::Overflow)
+}
+
+/// Token Y held by `liquidity` between two sqrt prices: L * (b - a), in Q64.64.
+pub fn amount_y_delta(sqrt_price_a: u128, sqrt_price_b: u128, liquidity: u128, round_up: bool) -> Result<u128, QuoteError> {
+    let diff = sqrt_price_a.abs_diff(sqrt_price_b);
+    let div = if round_up { mul_div_ceil } else { mul_div };
+    div(liquidity, diff, Q64).ok_or(QuoteError::Overflow)
+}
+
+/// Returns the token amounts `(x, y)` backing `liquidity` over [sqrt_price_lower, sqrt_price_upper)
+/// when the pool is at `sqrt_price`. Below the range it is all X, above it all Y.
+/// Round up for amounts paid in and down for amounts paid out.
+pub fn liquidity_amounts(
+    sqrt_price: u128,
+    sqrt_price_lower: u128,
+    sqrt_price_upper: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<(u64, u64), QuoteError> {
+    if sqrt_price_lower >= sqrt_price_upper {
+        return Err(QuoteError::InvalidTick);
+    }
+    let (x, y) = if sqrt_price <= sqrt_price_lower {
+        (amount_x_delta(sqrt_price_lower, sqrt_price_upper, liquidity, round_up)?, 0)
+    } else if sqrt_price >= sqrt_price_upper {
+        (0, amount_y_delta(sqrt_price_lower, sqrt_price_upper, liquidity, round_up)?)
+    } else {
+        (
+            amount_x_delta(sqrt_price, sqrt_price_upper, liquidity, round_up)?,
+            amount_y_delta(sqrt_price_lower, sqrt_price, liquidity, round_up)?,
+        )
+    };
+    let to_u64 = |amount: u128| u64::try_from(amount).map_err(|_| QuoteError::Overflow);
+    Ok((to_u64(x)?, to_u64(y)?))
+}
+
+/// One step of a swap, within a range of constant liquidity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SwapStep {
+    /// Sqrt price after the step.
+    pub sqrt_price_next: u128,
+    /// Input consumed by the step, excluding the fee.
+    pub amount_in: u64,
+    pub amount_out: u64,
+    /// Fee charged on the input.
+    pub fee_amount: u64,
+}
+
+/// Swaps up to `amount_remaining` (fee included) of token X (`x_to_y`) or Y, moving the price
+/// from `sqrt_price` towards `sqrt_price_target` with constant `liquidity`. The step stops at the
+/// target if the input is large enough to get there, and otherwise spends the whole input.
+pub fn compute_swap_step(
+    sqrt_price: u128,
+    sqrt_price_target: u128,
+    liquidity: u128,
+    amount_remaining: u64,
+    fee: u16,
+    x_to_y: bool,
+) -> Result<SwapStep, QuoteError> {
+    if fee >= 10_000 {
+        return Err(QuoteError::InvalidFee);
+    }
+    if (x_to_y && sqrt_price_target > sqrt_price) || (!x_to_y && sqrt_price_target < sqrt_price) {
+        return Err(QuoteError::InvalidPrice);
+    }
+    // No liquidity in this range: the price moves through it for free
+    if liquidity == 0 {
+        return Ok(SwapStep { sqrt_price_next: sqrt_price_target, amount_in: 0, amount_out: 0, fee_amount: 0 });
+    }
+
+    let amount_less_fee = (amount_remaining as u128 * (10_000 - fee as u128) / 10_000) as u64;
+    let amount_to_target = if x_to_y {
+        amount_x_delta(sqrt_price_target, sqrt_price, liquidity, true)?
+    } else {
+        amount_y_delta(sqrt_price, sqrt_price_target, liquidity, true)?
+    };
+
+    let reaches_target = amount_less_fee as u128 >= amount_to_target;
+    let sqrt_price_next = if reaches_target {
+        sqrt_price_target
+    } else {
+        next_sqrt_price_from_input(sqrt_price, liquidity, amount_less_fee, x_to_y)?
+    };
+
+    let (amount_in, amount_out) = if x_to_y {
+        (
+            amount_x_delta(sqrt_price_next, sqrt_price, liquidity, true)?,
+            amount_y_delta(sqrt_price_next, sqrt_price, liquidity, false)?,
+        )
+    } else {
+        (
+            amount_y_delta(sqrt_price, sqrt_price_next, liquidity, true)?,
+            amount_x_delta(sqrt_price, sqrt_price_next, liquidity, false)?,
+        )
+    };
+    let amount_in = u64::try_from(amount_in).map_err(|_| QuoteError::Overflow)?;
+    let amount_out = u64::try_from(amount_out).map_err(|_| QuoteError::Overflow)?;
+
+    // A step that stops short keeps the whole remainder as fee, so no input is lost to rounding
+    let fee_amount = if reaches_target {
+        let fee_amount = (amount_in as u128 * fee as u128).div_ceil(10_000 - fee as u128);
+        u64::try_from(fee_amount).map_err(|_| QuoteError::Overflow)?
+    } else {
+        amount_remaining.checked_sub(amount_in).ok_or(QuoteError::Underflow)?
+    };
+
+    Ok(SwapStep { sqrt_price_next, amount_in, amount_out, fee_amount })
+}
+
+/// Returns the sqrt price after adding `amount_in` of token X (`x_to_y`, price falls) or Y (price rises),
+/// rounded so the price moves no further than the input pays for.
+fn next_sqrt_price_from_input(sqrt_price: u128, liquidity: u128, amount_in: u64, x_to_y: bool) -> Result<u128, QuoteError> {
+    if x_to_y {
+        // sqrt(P') = L * sqrt(P) / (L + x * sqrt(P))
+        let product = mul_div(amount_in as u128, sqrt_price, Q64).ok_or(QuoteError::Overflow)?;
+        let denominator = liquidity.checked_add(product).ok_or(QuoteError::Overflow)?;
+        mul_div_ceil(liquidity, sqrt_price, denominator).ok_or(QuoteError::Overflow)
+    } else {
+        // sqrt(P') = sqrt(P) + y / L
+        let delta = mul_div(amount_in as u128, Q64, liquidity).ok_or(QuoteError::Overflow)?;
+        sqrt_price.checked_add(delta).ok_or(QuoteError::Overflow)
+    }
+}
+
+/// Returns the fee growth per unit of liquidity (Q64.64) from `fee_amount` shared by `liquidity`.
+pub fn fee_growth_delta(fee_amount: u64, liquidity: u128) -> u128 {
+    if liquidity == 0 {
+        return 0;
+    }
+    mul_div(fee_amount as u128, Q64, liquidity).unwrap_or(0)
+}
+
+/// Returns the fees earned by `liquidity` over a fee growth of `growth` (Q64.64), rounded down.
+pub fn fees_earned(liquidity: u128, growth: u128) -> u64 {
+    mul_div(liquidity, growth, Q64).map_or(u64::MAX, |fees| u64::try_from(fees).unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sqrt_price_at_tick_bounds() {
+        assert_eq!(sqrt_price_at_tick(0), Ok(Q64));
+        assert_eq!(sqrt_price_at_tick(MIN_TICK), Ok(MIN_SQRT_PRICE));
+        assert_eq!(sqrt_price_at_tick(MAX_TICK), Ok(MAX_SQRT_PRICE));
+        assert_eq!(sqrt_price_at_tick(MAX_TICK + 1), Err(QuoteError::InvalidTick));
+        // 1.0001^(±1) within a part in 10^15
+        assert_eq!(sqrt_price_at_tick(1).unwrap() / 1_000, 18_447_666_387_855_959_850 / 1_000);
+        assert_eq!(sqrt_price_at_tick(-1).unwrap(), 18_445_821_805_675_392_311);
+    }
+
+    #[test]
+    fn test_tick_at_sqrt_price_inverts_sqrt_price_at_tick() {
+        for tick in [MIN_TICK, -100_000, -1, 0, 1, 60, 100_000, MAX_TICK] {
+            let sqrt_price = sqrt_price_at_tick(tick).unwrap();
+            assert_eq!(tick_at_sqrt_price(sqrt_price), Ok(tick));
+            if tick < MAX_TICK {
+                assert_eq!(tick_at_sqrt_price(sqrt_price + 1), Ok(tick));
+            }
+            if tick > MIN_TICK {
+                assert_eq!(tick_at_sqrt_price(sqrt_price - 1), Ok(tick - 1));
+            }
+        }
+    }
+
+    #[test]
+    fn test_swap_step_stops_at_target_or_spends_input() {
+        let liquidity = 1_000_000_000u128;
+        let (price, target) = (Q64, sqrt_price_at_tick(-100).unwrap());
+
+        // A small input doesn't reach the target and is spent in full
+        let step = compute_swap_step(price, target, liquidity, 1_000, 30, true).unwrap();
+        assert!(step.sqrt_price_next > target && step.sqrt_price_next < price);
+        assert_eq!(step.amount_in + step.fee_amount, 1_000);
+        assert!(step.amount_out < step.amount_in);
+
+        // A large input stops at the target
+        let step = compute_swap_step(price, target, liquidity, u32::MAX as u64, 30, true).unwrap();
+        assert_eq!(step.sqrt_price_next, target);
+        assert!(step.amount_in + step.fee_amount < u32::MAX as u64);
+
+        // Moving the wrong way is rejected
+        assert_eq!(compute_swap_step(price, target, liquidity, 1_000, 30, false), Err(QuoteError::InvalidPrice));
+    }
+}
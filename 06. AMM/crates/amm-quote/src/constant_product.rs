@@ -0,0 +1,59 @@
+// Constant product (x * y = k) swap pricing.
+
+use crate::QuoteError;
+
+/// Calculates the output for an exact input. The fee is taken from the input and rounding
+/// favours the pool. Returns `(amount_out, fee_amount)`.
+pub fn swap_exact_in(amount_in: u64, reserve_in: u64, reserve_out: u64, fee: u16) -> Result<(u64, u64), QuoteError> {
+    let (amount_in_with_fee, fee_amount) = take_fee(amount_in, fee)?;
+    if amount_in_with_fee == 0 {
+        return Ok((0, fee_amount));
+    }
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(QuoteError::InsufficientLiquidity);
+    }
+
+    // amount_out = (amount_in_with_fee * reserve_out) / (reserve_in + amount_in_with_fee)
+    let numerator = (amount_in_with_fee as u128)
+        .checked_mul(reserve_out as u128)
+        .ok_or(QuoteError::Overflow)?;
+    let denominator = reserve_in as u128 + amount_in_with_fee as u128;
+    Ok(((numerator / denominator) as u64, fee_amount))
+}
+
+/// Calculates the input required for an exact output. Both the curve input and the fee gross-up
+/// are rounded up, so the pool never loses to rounding. Returns `(amount_in, fee_amount)`.
+pub fn swap_exact_out(amount_out: u64, reserve_in: u64, reserve_out: u64, fee: u16) -> Result<(u64, u64), QuoteError> {
+    if amount_out >= reserve_out {
+        return Err(QuoteError::InsufficientLiquidity);
+    }
+
+    // amount_in_with_fee = ceil(reserve_in * amount_out / (reserve_out - amount_out))
+    let numerator = reserve_in as u128 * amount_out as u128;
+    let denominator = (reserve_out - amount_out) as u128;
+    add_fee(numerator.div_ceil(denominator), fee)
+}
+
+/// Splits the fee off an input. Returns `(amount_in_with_fee, fee_amount)`.
+pub(crate) fn take_fee(amount_in: u64, fee: u16) -> Result<(u64, u64), QuoteError> {
+    if fee > 10_000 {
+        return Err(QuoteError::InvalidFee);
+    }
+    let amount_in_with_fee = (amount_in as u128 * (10_000 - fee as u128) / 10_000) as u64;
+    Ok((amount_in_with_fee, amount_in - amount_in_with_fee))
+}
+
+/// Grosses an after-fee input back up, rounding up. Returns `(amount_in, fee_amount)`.
+pub(crate) fn add_fee(amount_in_with_fee: u128, fee: u16) -> Result<(u64, u64), QuoteError> {
+    if fee >= 10_000 {
+        return Err(QuoteError::InvalidFee);
+    }
+    // amount_in = ceil(amount_in_with_fee * 10_000 / (10_000 - fee))
+    let amount_in = amount_in_with_fee
+        .checked_mul(10_000)
+        .ok_or(QuoteError::Overflow)?
+        .div_ceil(10_000 - fee as u128);
+    let amount_in = u64::try_from(amount_in).map_err(|_| QuoteError::Overflow)?;
+    let (_, fee_amount) = take_fee(amount_in, fee)?;
+    Ok((amount_in, fee_amount))
+}
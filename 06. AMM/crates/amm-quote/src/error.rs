@@ -0,0 +1,38 @@
+use core::fmt;
+
+/// Why a quote could not be produced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuoteError {
+    /// The fee is above 100% (or exactly 100% where an input must be solved for).
+    InvalidFee,
+    /// The amount is zero or out of range.
+    InvalidAmount,
+    /// The reserves are empty or too small for the requested output.
+    InsufficientLiquidity,
+    /// An intermediate or final value doesn't fit its type.
+    Overflow,
+    /// A subtraction went below zero.
+    Underflow,
+    /// The StableSwap invariant failed to converge.
+    NoConvergence,
+    /// The tick is outside the supported range.
+    InvalidTick,
+    /// The sqrt price is outside the supported range, or on the wrong side of a swap's price limit.
+    InvalidPrice,
+}
+
+impl fmt::Display for QuoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            QuoteError::InvalidFee => "fee is greater than 100%",
+            QuoteError::InvalidAmount => "invalid amount",
+            QuoteError::InsufficientLiquidity => "insufficient liquidity",
+            QuoteError::Overflow => "overflow",
+            QuoteError::Underflow => "underflow",
+            QuoteError::NoConvergence => "curve invariant did not converge",
+            QuoteError::InvalidTick => "tick out of range",
+            QuoteError::InvalidPrice => "sqrt price out of range",
+        };
+        f.write_str(msg)
+    }
+}
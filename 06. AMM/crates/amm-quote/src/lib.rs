@@ -0,0 +1,132 @@
+// Pricing math for the AMM program, shared by the on-chain program and off-chain clients.
+//
+// Everything here is pure integer math with no dependencies, so it builds for the Solana
+// target, for native clients and for wasm alike. All arithmetic is checked and every rounding
+// step favours the pool:
+// - quote_swap_exact_in / quote_swap_exact_out: Swap pricing on the constant product or StableSwap curve.
+// - quote_deposit: Token amounts owed for minting LP against existing reserves (rounded up).
+// - quote_withdraw: Token amounts paid out for burning LP (rounded down).
+// - initial_liquidity: LP sized for the first deposit into an empty pool.
+// - quote_flash_loan_fee: Fee owed on top of a flash loan's principal (rounded up).
+// - concentrated: Tick math, liquidity amounts and swap steps for concentrated liquidity pools.
+//
+// Fees are in basis points of the input (30 = 0.3%) and are taken from the input before pricing.
+
+#![no_std]
+
+pub mod concentrated;
+pub mod constant_product;
+pub mod error;
+pub mod math;
+pub mod stable_swap;
+
+pub use error::QuoteError;
+pub use math::{ mul_div, mul_div_ceil, sqrt_u128 };
+
+/// The curve a pool prices swaps on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Curve {
+    /// x * y = k.
+    ConstantProduct,
+    /// The StableSwap invariant with amplification coefficient `amp`.
+    StableSwap { amp: u64 },
+}
+
+/// A priced swap. `fee_amount` is charged in the input token and included in `amount_in`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SwapQuote {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+}
+
+/// Token amounts exchanged for LP on a deposit or withdrawal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LiquidityQuote {
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+/// Quotes the output of swapping exactly `amount_in`.
+pub fn quote_swap_exact_in(
+    curve: Curve,
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    fee: u16,
+) -> Result<SwapQuote, QuoteError> {
+    let (amount_out, fee_amount) = match curve {
+        Curve::ConstantProduct => constant_product::swap_exact_in(amount_in, reserve_in, reserve_out, fee)?,
+        Curve::StableSwap { amp } => stable_swap::swap_exact_in(amount_in, reserve_in, reserve_out, fee, amp)?,
+    };
+    Ok(SwapQuote { amount_in, amount_out, fee_amount })
+}
+
+/// Quotes the input needed to receive exactly `amount_out`.
+pub fn quote_swap_exact_out(
+    curve: Curve,
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    fee: u16,
+) -> Result<SwapQuote, QuoteError> {
+    let (amount_in, fee_amount) = match curve {
+        Curve::ConstantProduct => constant_product::swap_exact_out(amount_out, reserve_in, reserve_out, fee)?,
+        Curve::StableSwap { amp } => stable_swap::swap_exact_out(amount_out, reserve_in, reserve_out, fee, amp)?,
+    };
+    Ok(SwapQuote { amount_in, amount_out, fee_amount })
+}
+
+/// Quotes the token amounts to deposit for `lp_amount` new LP tokens, proportional to the
+/// reserves and rounded up.
+pub fn quote_deposit(lp_amount: u64, reserve_x: u64, reserve_y: u64, lp_supply: u64) -> Result<LiquidityQuote, QuoteError> {
+    if lp_amount == 0 {
+        return Err(QuoteError::InvalidAmount);
+    }
+    if lp_supply == 0 {
+        return Err(QuoteError::InsufficientLiquidity);
+    }
+    Ok(LiquidityQuote {
+        amount_x: share_ceil(reserve_x, lp_amount, lp_supply)?,
+        amount_y: share_ceil(reserve_y, lp_amount, lp_supply)?,
+    })
+}
+
+/// Quotes the token amounts paid out for burning `lp_amount` LP tokens, proportional to the
+/// reserves and rounded down.
+pub fn quote_withdraw(lp_amount: u64, reserve_x: u64, reserve_y: u64, lp_supply: u64) -> Result<LiquidityQuote, QuoteError> {
+    if lp_amount == 0 || lp_amount > lp_supply {
+        return Err(QuoteError::InvalidAmount);
+    }
+    Ok(LiquidityQuote {
+        amount_x: (reserve_x as u128 * lp_amount as u128 / lp_supply as u128) as u64,
+        amount_y: (reserve_y as u128 * lp_amount as u128 / lp_supply as u128) as u64,
+    })
+}
+
+/// Returns the LP minted by the first deposit of `x` and `y` into an empty pool:
+/// sqrt(x * y) on the constant product curve and the invariant D on the StableSwap curve.
+pub fn initial_liquidity(curve: Curve, x: u64, y: u64) -> Result<u64, QuoteError> {
+    if x == 0 || y == 0 {
+        return Err(QuoteError::InvalidAmount);
+    }
+    let liquidity = match curve {
+        Curve::ConstantProduct => sqrt_u128(x as u128 * y as u128),
+        Curve::StableSwap { amp } => stable_swap::compute_d(amp, x, y)?,
+    };
+    u64::try_from(liquidity).map_err(|_| QuoteError::Overflow)
+}
+
+/// Quotes the fee owed for flash borrowing `amount`: the pool fee on the principal, rounded up.
+pub fn quote_flash_loan_fee(amount: u64, fee: u16) -> Result<u64, QuoteError> {
+    if fee > 10_000 {
+        return Err(QuoteError::InvalidFee);
+    }
+    share_ceil(amount, fee as u64, 10_000)
+}
+
+/// Returns ceil(amount * numerator / denominator).
+fn share_ceil(amount: u64, numerator: u64, denominator: u64) -> Result<u64, QuoteError> {
+    let share = (amount as u128 * numerator as u128).div_ceil(denominator as u128);
+    u64::try_from(share).map_err(|_| QuoteError::Overflow)
+}
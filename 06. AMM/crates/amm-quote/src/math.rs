@@ -0,0 +1,104 @@
+// Integer helpers for the curve math.
+
+/// Integer square root, rounded down, using Newton's method.
+pub fn sqrt_u128(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = n / 2 + n % 2;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+/// Computes `a * b / c` rounded down, with a 256-bit intermediate product.
+/// Returns `None` on division by zero or if the result doesn't fit in a u128.
+pub fn mul_div(a: u128, b: u128, c: u128) -> Option<u128> {
+    if c == 0 {
+        return None;
+    }
+    let (hi, lo) = widening_mul(a, b);
+    if hi == 0 {
+        return Some(lo / c);
+    }
+    if hi >= c {
+        return None;
+    }
+
+    // Long division of the 256-bit (hi, lo) by c, one bit at a time
+    let (mut remainder, mut quotient) = (hi, 0u128);
+    for i in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((lo >> i) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= c {
+            remainder = remainder.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+    Some(quotient)
+}
+
+/// Computes `a * b / c` rounded up, with a 256-bit intermediate product.
+/// Returns `None` on division by zero or if the result doesn't fit in a u128.
+pub fn mul_div_ceil(a: u128, b: u128, c: u128) -> Option<u128> {
+    let quotient = mul_div(a, b, c)?;
+    if widening_mul(quotient, c) == widening_mul(a, b) {
+        Some(quotient)
+    } else {
+        quotient.checked_add(1)
+    }
+}
+
+/// Returns the full 256-bit product of `a` and `b` as `(high, low)` halves.
+fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & MASK);
+    let (b_hi, b_lo) = (b >> 64, b & MASK);
+
+    let lo_lo = a_lo * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_lo = a_hi * b_lo;
+    let hi_hi = a_hi * b_hi;
+
+    let mid = (lo_lo >> 64) + (lo_hi & MASK) + (hi_lo & MASK);
+    let lo = (lo_lo & MASK) | (mid << 64);
+    let hi = hi_hi + (lo_hi >> 64) + (hi_lo >> 64) + (mid >> 64);
+    (hi, lo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sqrt_u128_rounds_down() {
+        assert_eq!(sqrt_u128(0), 0);
+        assert_eq!(sqrt_u128(1), 1);
+        assert_eq!(sqrt_u128(2), 1);
+        assert_eq!(sqrt_u128(4), 2);
+        assert_eq!(sqrt_u128(100_000 * 200_000), 141_421);
+        assert_eq!(sqrt_u128(u128::MAX), u64::MAX as u128);
+    }
+
+    #[test]
+    fn test_mul_div_uses_wide_intermediate() {
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX), Some(u128::MAX));
+        assert_eq!(mul_div(1 << 100, 1 << 100, 1 << 90), Some(1 << 110));
+        assert_eq!(mul_div(7, 3, 2), Some(10));
+        assert_eq!(mul_div(u128::MAX, 2, 1), None);
+        assert_eq!(mul_div(1, 1, 0), None);
+    }
+
+    #[test]
+    fn test_mul_div_ceil_rounds_up() {
+        assert_eq!(mul_div_ceil(7, 3, 2), Some(11));
+        assert_eq!(mul_div_ceil(8, 3, 2), Some(12));
+        assert_eq!(mul_div_ceil(1 << 100, (1 << 100) + 1, 1 << 90), Some((1 << 110) + (1 << 10)));
+        assert_eq!(mul_div_ceil(u128::MAX, 3, 3), Some(u128::MAX));
+        assert_eq!(mul_div_ceil(1, 1, 0), None);
+    }
+}
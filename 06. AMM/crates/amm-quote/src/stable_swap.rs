@@ -0,0 +1,150 @@
+// StableSwap invariant math for two-token pools.
+//
+// Pegged pairs trade on the StableSwap curve instead of x*y=k:
+//     A·n^n·(x + y) + D = A·D·n^n + D^(n+1) / (n^n·x·y), with n = 2
+// The amplification coefficient A flattens the curve around the peg: A -> 0 approaches
+// the constant product curve, A -> infinity approaches a constant sum. D is the pool's
+// invariant, equal to x + y when the pool is balanced.
+//
+// Fees follow the constant product path: they are taken from the input and every rounding
+// step favours the pool, so D never decreases across a swap.
+
+use crate::{ constant_product::{ add_fee, take_fee }, math::mul_div, QuoteError };
+
+/// Number of tokens in a pool.
+const N_COINS: u128 = 2;
+/// Newton's method iterations before giving up on convergence.
+const MAX_ITERATIONS: usize = 255;
+
+/// Computes the invariant D for reserves `x` and `y`.
+pub fn compute_d(amp: u64, x: u64, y: u64) -> Result<u128, QuoteError> {
+    try_compute_d(amp, x, y).ok_or(QuoteError::NoConvergence)
+}
+
+/// Computes the reserve of one token that keeps the invariant at `d` when the other reserve is `x`.
+pub fn compute_y(amp: u64, x: u128, d: u128) -> Result<u128, QuoteError> {
+    try_compute_y(amp, x, d).ok_or(QuoteError::NoConvergence)
+}
+
+fn try_compute_d(amp: u64, x: u64, y: u64) -> Option<u128> {
+    let (x, y) = (x as u128, y as u128);
+    if x == 0 || y == 0 {
+        return if x + y == 0 { Some(0) } else { None };
+    }
+    let sum = x + y;
+    // Dividing by the smaller reserve first keeps the intermediate large, so truncation
+    // can't make Newton's method oscillate on imbalanced pools
+    let (lo, hi) = if x < y { (x, y) } else { (y, x) };
+    let ann = (amp as u128).checked_mul(N_COINS)?;
+    if ann == 0 {
+        return None;
+    }
+
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        // d_p = D^3 / (n^n·x·y)
+        let d_p = mul_div(mul_div(d, d, lo * N_COINS)?, d, hi * N_COINS)?;
+        let d_prev = d;
+        // D = (Ann·S + n·d_p)·D / ((Ann - 1)·D + (n + 1)·d_p)
+        let numerator = ann.checked_mul(sum)?.checked_add(d_p.checked_mul(N_COINS)?)?;
+        let denominator = (ann - 1).checked_mul(d)?.checked_add(d_p.checked_mul(N_COINS + 1)?)?;
+        d = mul_div(numerator, d, denominator)?;
+        if d.abs_diff(d_prev) <= 1 {
+            return Some(d);
+        }
+    }
+    None
+}
+
+fn try_compute_y(amp: u64, x: u128, d: u128) -> Option<u128> {
+    let ann = (amp as u128).checked_mul(N_COINS)?;
+    if ann == 0 || x == 0 {
+        return None;
+    }
+
+    // c = D^3 / (n^n·x·Ann), b = x + D / Ann
+    let (lo, hi) = if x < ann { (x, ann) } else { (ann, x) };
+    let c = mul_div(mul_div(d, d, lo.checked_mul(N_COINS)?)?, d, hi.checked_mul(N_COINS)?)?;
+    let b = x.checked_add(d / ann)?;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        // y = (y^2 + c) / (2y + b - D)
+        let numerator = y.checked_mul(y)?.checked_add(c)?;
+        let denominator = y.checked_mul(2)?.checked_add(b)?.checked_sub(d)?;
+        y = numerator.checked_div(denominator)?;
+        if y.abs_diff(y_prev) <= 1 {
+            return Some(y);
+        }
+    }
+    None
+}
+
+/// Calculates the output for an exact input on the StableSwap curve.
+/// The fee is taken from the input and rounding favours the pool. Returns `(amount_out, fee_amount)`.
+pub fn swap_exact_in(amount_in: u64, reserve_in: u64, reserve_out: u64, fee: u16, amp: u64) -> Result<(u64, u64), QuoteError> {
+    let (amount_in_with_fee, fee_amount) = take_fee(amount_in, fee)?;
+    if amount_in_with_fee == 0 {
+        return Ok((0, fee_amount));
+    }
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(QuoteError::InsufficientLiquidity);
+    }
+
+    let d = compute_d(amp, reserve_in, reserve_out)?;
+    let new_reserve_in = reserve_in as u128 + amount_in_with_fee as u128;
+    let new_reserve_out = compute_y(amp, new_reserve_in, d)?;
+
+    // One unit is held back to absorb Newton's method rounding
+    let amount_out = (reserve_out as u128).saturating_sub(new_reserve_out + 1) as u64;
+
+    Ok((amount_out, fee_amount))
+}
+
+/// Calculates the input required for an exact output on the StableSwap curve.
+/// Both the curve input and the fee gross-up are rounded up, so the pool never loses to rounding.
+/// Returns `(amount_in, fee_amount)`.
+pub fn swap_exact_out(amount_out: u64, reserve_in: u64, reserve_out: u64, fee: u16, amp: u64) -> Result<(u64, u64), QuoteError> {
+    if amount_out >= reserve_out || reserve_in == 0 {
+        return Err(QuoteError::InsufficientLiquidity);
+    }
+
+    let d = compute_d(amp, reserve_in, reserve_out)?;
+    let new_reserve_out = (reserve_out - amount_out) as u128;
+    let new_reserve_in = compute_y(amp, new_reserve_out, d)?;
+    let amount_in_with_fee = new_reserve_in
+        .checked_sub(reserve_in as u128)
+        .ok_or(QuoteError::Underflow)?
+        + 1;
+
+    add_fee(amount_in_with_fee, fee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constant_product;
+
+    #[test]
+    fn test_stable_swap_keeps_invariant_with_lower_slippage() {
+        let (reserve_in, reserve_out, fee, amp) = (1_000_000_000u64, 1_000_000_000u64, 4, 100);
+        let d_before = compute_d(amp, reserve_in, reserve_out).unwrap();
+        assert!(d_before.abs_diff(2_000_000_000) <= 1);
+
+        for amount_in in [1, 1_000, 10_000_000, 500_000_000] {
+            let (amount_out, _) = swap_exact_in(amount_in, reserve_in, reserve_out, fee, amp).unwrap();
+            let d_after = compute_d(amp, reserve_in + amount_in, reserve_out - amount_out).unwrap();
+            assert!(d_after >= d_before);
+
+            // Near the peg StableSwap gives more than x*y=k
+            let (cp_out, _) = constant_product::swap_exact_in(amount_in, reserve_in, reserve_out, fee).unwrap();
+            assert!(amount_out >= cp_out);
+        }
+
+        // Exact output quotes buy at least the requested amount
+        let (amount_in, _) = swap_exact_out(10_000_000, reserve_in, reserve_out, fee, amp).unwrap();
+        let (received, _) = swap_exact_in(amount_in, reserve_in, reserve_out, fee, amp).unwrap();
+        assert!(received >= 10_000_000);
+    }
+}
@@ -0,0 +1,134 @@
+// Property-based tests for the quote functions.
+// The core property is that no quote can make the pool worse off: swaps never decrease the
+// curve invariant (k for constant product, D for StableSwap), and deposits and withdrawals
+// never decrease the reserves backing each LP token.
+
+use amm_quote::{
+    quote_deposit, quote_flash_loan_fee, quote_swap_exact_in, quote_swap_exact_out, quote_withdraw, stable_swap, Curve, QuoteError,
+};
+use proptest::prelude::*;
+
+const MAX_RESERVE: u64 = 1_000_000_000_000_000;
+
+proptest! {
+    #[test]
+    fn constant_product_exact_in_never_decreases_k(
+        reserve_in in 1..MAX_RESERVE,
+        reserve_out in 1..MAX_RESERVE,
+        amount_in in 0..MAX_RESERVE,
+        fee in 0u16..=10_000,
+    ) {
+        let quote = quote_swap_exact_in(Curve::ConstantProduct, amount_in, reserve_in, reserve_out, fee).unwrap();
+        prop_assert!(quote.amount_out < reserve_out);
+        prop_assert!(quote.fee_amount <= amount_in);
+
+        let k_before = reserve_in as u128 * reserve_out as u128;
+        let k_after = (reserve_in + amount_in) as u128 * (reserve_out - quote.amount_out) as u128;
+        prop_assert!(k_after >= k_before);
+    }
+
+    #[test]
+    fn constant_product_exact_out_never_decreases_k(
+        reserve_in in 1..MAX_RESERVE,
+        reserve_out in 2..MAX_RESERVE,
+        out_ratio in 0.0f64..1.0,
+        fee in 0u16..10_000,
+    ) {
+        let amount_out = ((reserve_out - 1) as f64 * out_ratio) as u64;
+        let quote = match quote_swap_exact_out(Curve::ConstantProduct, amount_out, reserve_in, reserve_out, fee) {
+            Ok(quote) => quote,
+            Err(QuoteError::Overflow) => return Ok(()),
+            Err(e) => return Err(TestCaseError::fail(format!("{e}"))),
+        };
+
+        let k_before = reserve_in as u128 * reserve_out as u128;
+        let k_after = (reserve_in as u128 + quote.amount_in as u128) * (reserve_out - amount_out) as u128;
+        prop_assert!(k_after >= k_before);
+
+        // The quoted input always buys at least the requested output
+        let exact_in = quote_swap_exact_in(Curve::ConstantProduct, quote.amount_in, reserve_in, reserve_out, fee).unwrap();
+        prop_assert!(exact_in.amount_out >= amount_out);
+    }
+
+    #[test]
+    fn stable_swap_exact_in_never_decreases_d(
+        reserve_in in 1_000..MAX_RESERVE,
+        imbalance in 0.1f64..10.0,
+        amount_in in 0..MAX_RESERVE,
+        fee in 0u16..=10_000,
+        amp in 1u64..=10_000,
+    ) {
+        let reserve_out = ((reserve_in as f64 * imbalance) as u64).clamp(1_000, MAX_RESERVE);
+        let curve = Curve::StableSwap { amp };
+        let quote = quote_swap_exact_in(curve, amount_in, reserve_in, reserve_out, fee).unwrap();
+        prop_assert!(quote.amount_out < reserve_out);
+
+        let d_before = stable_swap::compute_d(amp, reserve_in, reserve_out).unwrap();
+        let d_after = stable_swap::compute_d(amp, reserve_in + amount_in, reserve_out - quote.amount_out).unwrap();
+        prop_assert!(d_after >= d_before);
+    }
+
+    #[test]
+    fn stable_swap_exact_out_never_decreases_d(
+        reserve_in in 1_000..MAX_RESERVE,
+        imbalance in 0.1f64..10.0,
+        out_ratio in 0.0f64..0.99,
+        fee in 0u16..10_000,
+        amp in 1u64..=10_000,
+    ) {
+        let reserve_out = ((reserve_in as f64 * imbalance) as u64).clamp(1_000, MAX_RESERVE);
+        let amount_out = (reserve_out as f64 * out_ratio) as u64;
+        let curve = Curve::StableSwap { amp };
+        let quote = match quote_swap_exact_out(curve, amount_out, reserve_in, reserve_out, fee) {
+            Ok(quote) => quote,
+            Err(QuoteError::Overflow) => return Ok(()),
+            Err(e) => return Err(TestCaseError::fail(format!("{e}"))),
+        };
+
+        let d_before = stable_swap::compute_d(amp, reserve_in, reserve_out).unwrap();
+        let d_after = stable_swap::compute_d(amp, reserve_in + quote.amount_in, reserve_out - amount_out).unwrap();
+        prop_assert!(d_after >= d_before);
+    }
+
+    #[test]
+    fn deposit_never_dilutes_lp(
+        reserve_x in 0..MAX_RESERVE,
+        reserve_y in 0..MAX_RESERVE,
+        lp_supply in 1..MAX_RESERVE,
+        lp_amount in 1..MAX_RESERVE,
+    ) {
+        let quote = quote_deposit(lp_amount, reserve_x, reserve_y, lp_supply).unwrap();
+        let new_supply = (lp_supply + lp_amount) as u128;
+
+        // Reserves per LP token never fall: (x + dx) / (L + l) >= x / L
+        prop_assert!((reserve_x as u128 + quote.amount_x as u128) * lp_supply as u128 >= reserve_x as u128 * new_supply);
+        prop_assert!((reserve_y as u128 + quote.amount_y as u128) * lp_supply as u128 >= reserve_y as u128 * new_supply);
+    }
+
+    #[test]
+    fn withdraw_never_dilutes_lp(
+        reserve_x in 0..MAX_RESERVE,
+        reserve_y in 0..MAX_RESERVE,
+        lp_supply in 1..MAX_RESERVE,
+        lp_ratio in 0.0f64..=1.0,
+    ) {
+        let lp_amount = ((lp_supply as f64 * lp_ratio) as u64).clamp(1, lp_supply);
+        let quote = quote_withdraw(lp_amount, reserve_x, reserve_y, lp_supply).unwrap();
+        prop_assert!(quote.amount_x <= reserve_x && quote.amount_y <= reserve_y);
+        let new_supply = (lp_supply - lp_amount) as u128;
+
+        // Reserves per LP token never fall: (x - dx) / (L - l) >= x / L
+        prop_assert!((reserve_x - quote.amount_x) as u128 * lp_supply as u128 >= reserve_x as u128 * new_supply);
+        prop_assert!((reserve_y - quote.amount_y) as u128 * lp_supply as u128 >= reserve_y as u128 * new_supply);
+    }
+
+    #[test]
+    fn flash_loan_fee_never_undercharges(
+        amount in 0..u64::MAX,
+        fee in 0u16..=10_000,
+    ) {
+        let fee_amount = quote_flash_loan_fee(amount, fee).unwrap();
+        prop_assert!(fee_amount as u128 * 10_000 >= amount as u128 * fee as u128);
+        prop_assert!(fee_amount <= amount);
+    }
+}
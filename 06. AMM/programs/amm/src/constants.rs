@@ -2,3 +2,37 @@ use anchor_lang::prelude::*;
 
 #[constant]
 pub const SEED: &str = "anchor";
+
+/// LP tokens minted on the first deposit and locked forever in the pool's `lp_lock` account.
+/// Keeps the LP supply from ever returning to zero, which blocks first-depositor inflation attacks.
+#[constant]
+pub const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+/// Maximum number of pools a single `swap_route` may hop through.
+#[constant]
+pub const MAX_ROUTE_HOPS: u8 = 4;
+
+/// Bounds on the StableSwap amplification coefficient.
+#[constant]
+pub const MIN_AMP: u64 = 1;
+#[constant]
+pub const MAX_AMP: u64 = 1_000_000;
+
+/// Shortest allowed amplification ramp, in seconds.
+#[constant]
+pub const MIN_RAMP_DURATION: i64 = 86_400;
+
+/// Largest factor the amplification coefficient may move by in a single ramp.
+#[constant]
+pub const MAX_AMP_CHANGE: u64 = 10;
+
+/// Maximum number of fee tiers the factory can enable.
+#[constant]
+pub const MAX_FEE_TIERS: u8 = 8;
+
+/// Maximum number of pools returned by a single `list_pools` call, to stay within the return data limit.
+#[constant]
+pub const MAX_POOLS_PER_PAGE: u8 = 8;
+
+/// Number of ticks stored in each concentrated liquidity tick array.
+pub const TICK_ARRAY_SIZE: usize = 16;
@@ -1,5 +1,5 @@
+use amm_quote::QuoteError;
 use anchor_lang::error_code;
-use constant_product_curve::CurveError;
 
 #[error_code]
 pub enum AmmError {
@@ -42,19 +42,52 @@ pub enum AmmError {
     #[msg("Insufficient Liquidity.")]
     InsufficientLiquidity,
     #[msg("Insufficient Funds.")]
-    InsufficientFunds    
+    InsufficientFunds,
+    #[msg("Invalid swap route.")]
+    InvalidRoute,
+    #[msg("Not supported by this pool's curve.")]
+    InvalidCurve,
+    #[msg("Invalid amplification coefficient.")]
+    InvalidAmp,
+    #[msg("Invalid amplification ramp.")]
+    InvalidRamp,
+    #[msg("Fee tier is not enabled.")]
+    InvalidFeeTier,
+    #[msg("Pool seed must be the factory's next pool index.")]
+    InvalidPoolSeed,
+    #[msg("A flash loan is outstanding on this pool.")]
+    FlashLoanActive,
+    #[msg("No flash loan is outstanding on this pool.")]
+    NoFlashLoan,
+    #[msg("Flash loan must be repaid by a flash_repay later in the same transaction.")]
+    FlashLoanNotRepaid,
+    #[msg("Pool still has liquidity providers or uncollected protocol fees.")]
+    PoolNotEmpty,
+    #[msg("Tick is out of range or not on the pool's tick spacing.")]
+    InvalidTick,
+    #[msg("Sqrt price is out of range or on the wrong side of the current price.")]
+    InvalidPrice,
+    #[msg("Tick array is missing or does not cover the required ticks.")]
+    InvalidTickArray,
+    #[msg("Transaction expired.")]
+    Expired,
+    #[msg("Invalid dynamic fee parameters.")]
+    InvalidDynamicFee,
+    #[msg("Token account is not owned by the pool's treasury.")]
+    InvalidTreasury,
 }
 
-impl From<CurveError> for AmmError {
-    fn from(error: CurveError) -> AmmError {
+impl From<QuoteError> for AmmError {
+    fn from(error: QuoteError) -> AmmError {
         match error {
-            CurveError::InvalidPrecision => AmmError::InvalidPrecision,
-            CurveError::Overflow => AmmError::Overflow,
-            CurveError::Underflow => AmmError::Underflow,
-            CurveError::InvalidFeeAmount => AmmError::InvalidFee,
-            CurveError::InsufficientBalance => AmmError::InsufficientBalance,
-            CurveError::ZeroBalance => AmmError::ZeroBalance,
-            CurveError::SlippageLimitExceeded => AmmError::SlippageExceeded,
+            QuoteError::InvalidFee => AmmError::InvalidFee,
+            QuoteError::InvalidAmount => AmmError::InvalidAmount,
+            QuoteError::InsufficientLiquidity => AmmError::InsufficientLiquidity,
+            QuoteError::Overflow => AmmError::Overflow,
+            QuoteError::Underflow => AmmError::Underflow,
+            QuoteError::NoConvergence => AmmError::CurveError,
+            QuoteError::InvalidTick => AmmError::InvalidTick,
+            QuoteError::InvalidPrice => AmmError::InvalidPrice,
         }
     }
 }
\ No newline at end of file
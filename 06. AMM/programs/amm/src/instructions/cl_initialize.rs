@@ -0,0 +1,221 @@
+// This file defines the 'ClInitialize' and 'ClInitTickArray' instructions for the AMM program.
+// They set up a concentrated liquidity pool and the tick arrays its positions and swaps use.
+//
+// Key roles:
+// - 'initializer' / 'payer': Pays for the new accounts.
+// - 'factory': The pool registry; the pool's seed must be its next pool index and its fee one of its tiers.
+// - 'canonical_pool': The PDA `["cl_canonical", lower_mint, higher_mint, fee_tier]` for the pool's sorted
+//   mint pair and fee tier. Creating it fails if the pair already has a concentrated pool in that tier.
+// - 'pool': The concentrated pool's PDA, `["cl_pool", seed]`.
+// - 'vault_x' and 'vault_y': The pool's token vaults (ATAs owned by the pool PDA).
+// - 'tick_array': A PDA `["tick_array", pool, start_tick]` holding `TICK_ARRAY_SIZE` ticks.
+//
+// The cl_initialize flow:
+// - Creates the pool and its vaults, with the price set to `sqrt_price` (Q64.64 sqrt of Y per X).
+// - Registers the pool in the factory, sharing its seed sequence with `initialize`, and under its
+//   canonical PDA, so `list_pools` enumerates it and clients can find it from the mints alone.
+// - `authority` may lock the pool, like any other pool; pools created without one can never be locked.
+// - Positions can only use ticks on multiples of `tick_spacing`. Wider spacing means fewer ticks to
+//   cross, and cheaper swaps, at the cost of coarser ranges.
+// - Concentrated pools have no LP mint: liquidity is tracked per position instead.
+// - A `ClPoolInitialized` event announces the new pool to indexers.
+//
+// The cl_init_tick_array flow:
+// - Anyone may create a tick array for a pool. A position needs the arrays holding its two ticks, and
+//   a swap needs every array the price moves through, so arrays are created on demand.
+
+use amm_quote::concentrated::{ tick_at_sqrt_price, MAX_TICK, MIN_TICK };
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{ Mint, TokenAccount, TokenInterface },
+};
+
+use crate::{
+    state::{ CanonicalPool, ClPool, Factory, Tick, TickArray },
+    error::AmmError,
+    constants::TICK_ARRAY_SIZE,
+};
+
+#[derive(Accounts)]
+#[instruction(seed: u64, fee: u16)]
+pub struct ClInitialize<'info> {
+    /// The user creating the pool.
+    #[account(mut)]
+    pub initializer: Signer<'info>,
+    /// The mint for token X.
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    /// The mint for token Y.
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    /// The factory the pool is registered in.
+    #[account(
+        mut,
+        seeds = [b"factory"],
+        bump = factory.bump
+    )]
+    pub factory: Account<'info, Factory>,
+    /// The canonical concentrated pool PDA for the mint pair and fee tier.
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [
+            b"cl_canonical",
+            CanonicalPool::sorted_mints(mint_x.key(), mint_y.key()).0.as_ref(),
+            CanonicalPool::sorted_mints(mint_x.key(), mint_y.key()).1.as_ref(),
+            fee.to_le_bytes().as_ref(),
+        ],
+        bump,
+        space = 8 + CanonicalPool::INIT_SPACE,
+    )]
+    pub canonical_pool: Account<'info, CanonicalPool>,
+    /// The concentrated pool PDA.
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"cl_pool", seed.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + ClPool::INIT_SPACE,
+    )]
+    pub pool: Account<'info, ClPool>,
+    /// The pool's vault for token X.
+    #[account(
+        init,
+        payer = initializer,
+        associated_token::mint = mint_x,
+        associated_token::authority = pool,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    /// The pool's vault for token Y.
+    #[account(
+        init,
+        payer = initializer,
+        associated_token::mint = mint_y,
+        associated_token::authority = pool,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClInitialize<'info> {
+    /// Initializes the pool at `sqrt_price` with the given fee, authority and tick spacing.
+    pub fn cl_initialize(
+        &mut self,
+        seed: u64,
+        fee: u16,
+        authority: Option<Pubkey>,
+        tick_spacing: u16,
+        sqrt_price: u128,
+        bumps: ClInitializeBumps,
+    ) -> Result<()> {
+        // A 100% fee would leave nothing to swap
+        require!(fee < 10_000, AmmError::InvalidFee);
+        require!(self.factory.fee_tiers.contains(&fee), AmmError::InvalidFeeTier);
+        require!(seed == self.factory.pool_count, AmmError::InvalidPoolSeed);
+        require!(tick_spacing > 0, AmmError::InvalidTick);
+        require_keys_neq!(self.mint_x.key(), self.mint_y.key(), AmmError::InvalidToken);
+        let tick_current = tick_at_sqrt_price(sqrt_price).map_err(AmmError::from)?;
+
+        self.pool.set_inner(ClPool {
+            seed,
+            authority,
+            locked: false,
+            mint_x: self.mint_x.key(),
+            mint_y: self.mint_y.key(),
+            fee,
+            tick_spacing,
+            sqrt_price,
+            tick_current,
+            liquidity: 0,
+            fee_growth_global_x: 0,
+            fee_growth_global_y: 0,
+            bump: bumps.pool,
+        });
+        self.canonical_pool.set_inner(CanonicalPool {
+            config: self.pool.key(),
+            seed,
+            mint_x: self.mint_x.key(),
+            mint_y: self.mint_y.key(),
+            fee_tier: fee,
+            bump: bumps.canonical_pool,
+        });
+        self.factory.pool_count = self.factory.pool_count.checked_add(1).ok_or(AmmError::Overflow)?;
+
+        emit!(ClPoolInitialized {
+            pool: self.pool.key(),
+            seed,
+            authority,
+            canonical_pool: self.canonical_pool.key(),
+            mint_x: self.mint_x.key(),
+            mint_y: self.mint_y.key(),
+            fee,
+            tick_spacing,
+            sqrt_price,
+            tick: tick_current,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(start_tick: i32)]
+pub struct ClInitTickArray<'info> {
+    /// Pays for the tick array.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// The concentrated pool PDA.
+    #[account(
+        seeds = [b"cl_pool", pool.seed.to_le_bytes().as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, ClPool>,
+    /// The tick array PDA.
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"tick_array", pool.key().as_ref(), start_tick.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + TickArray::INIT_SPACE,
+    )]
+    pub tick_array: Box<Account<'info, TickArray>>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClInitTickArray<'info> {
+    /// Initializes the empty tick array starting at `start_tick`.
+    pub fn cl_init_tick_array(&mut self, start_tick: i32) -> Result<()> {
+        // Must be the start of an array overlapping [MIN_TICK, MAX_TICK]
+        require!(self.pool.tick_array_start(start_tick) == start_tick, AmmError::InvalidTickArray);
+        require!(
+            start_tick >= self.pool.tick_array_start(MIN_TICK) && start_tick <= MAX_TICK,
+            AmmError::InvalidTickArray
+        );
+
+        self.tick_array.set_inner(TickArray {
+            pool: self.pool.key(),
+            start_tick,
+            ticks: [Tick::default(); TICK_ARRAY_SIZE],
+        });
+        Ok(())
+    }
+}
+
+#[event]
+pub struct ClPoolInitialized {
+    pub pool: Pubkey,
+    pub seed: u64,
+    pub authority: Option<Pubkey>,
+    pub canonical_pool: Pubkey,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub fee: u16,
+    pub tick_spacing: u16,
+    pub sqrt_price: u128,
+    pub tick: i32,
+}
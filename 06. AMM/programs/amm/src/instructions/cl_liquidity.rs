@@ -0,0 +1,410 @@
+// This file defines the 'ClDeposit' and 'ClWithdraw' instructions for the AMM program.
+// They add and remove liquidity over a price range in a concentrated pool, and collect its fees.
+//
+// Key roles:
+// - 'user': The liquidity provider.
+// - 'pool': The concentrated pool's PDA.
+// - 'vault_x' and 'vault_y': The pool's token vaults.
+// - 'position': The user's position over [tick_lower, tick_upper), created on its first deposit.
+// - 'tick_array_lower' and 'tick_array_upper': The tick arrays holding the position's ticks.
+//   `tick_array_upper` is omitted when both ticks are in `tick_array_lower`.
+//
+// The cl_deposit flow:
+// - Adds `liquidity` to the position and to its two ticks. If the range contains the current price,
+//   the pool's active liquidity grows too.
+// - The user pays the token amounts backing that liquidity at the current price, rounded up: only
+//   token X below the range, only token Y above it, both inside it. `max_x` / `max_y` bound them.
+// - A `ClLiquidityAdded` event records the range, liquidity and amounts.
+//
+// The cl_withdraw flow:
+// - Removes `liquidity` from the position and pays out the tokens backing it, rounded down,
+//   checked against `min_x` / `min_y`.
+// - A `ClLiquidityRemoved` event records the range, liquidity and amounts.
+//
+// The cl_collect flow:
+// - Every deposit and withdrawal first accrues the fees the position earned while the price was in
+//   its range. cl_collect accrues them too, then pays everything owed to the user.
+// - A `ClFeesCollected` event records the amounts.
+//
+// As with `deposit`, Token-2022 transfer fees are grossed up on the way in so the vaults receive
+// the full amounts; amounts paid out arrive net of the transfer fee.
+
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{ transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked },
+};
+
+use crate::{
+    state::{ ClPool, ClPosition, TickArray },
+    error::AmmError,
+    utils::get_amount_with_transfer_fee,
+};
+
+#[derive(Accounts)]
+#[instruction(tick_lower: i32, tick_upper: i32)]
+pub struct ClDeposit<'info> {
+    /// The user providing liquidity.
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// The mint for token X.
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    /// The mint for token Y.
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    /// The concentrated pool PDA.
+    #[account(
+        mut,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"cl_pool", pool.seed.to_le_bytes().as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, ClPool>,
+    /// The pool's vault for token X.
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = pool,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    /// The pool's vault for token Y.
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = pool,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    /// The user's token X account.
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_x: InterfaceAccount<'info, TokenAccount>,
+    /// The user's token Y account.
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_y: InterfaceAccount<'info, TokenAccount>,
+    /// The tick array holding `tick_lower`.
+    #[account(mut, has_one = pool)]
+    pub tick_array_lower: Box<Account<'info, TickArray>>,
+    /// The tick array holding `tick_upper`, if it isn't `tick_array_lower`.
+    #[account(mut, has_one = pool)]
+    pub tick_array_upper: Option<Box<Account<'info, TickArray>>>,
+    /// The user's position over the range, created on its first deposit.
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [
+            b"cl_position",
+            pool.key().as_ref(),
+            user.key().as_ref(),
+            tick_lower.to_le_bytes().as_ref(),
+            tick_upper.to_le_bytes().as_ref(),
+        ],
+        bump,
+        space = 8 + ClPosition::INIT_SPACE,
+    )]
+    pub position: Account<'info, ClPosition>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClDeposit<'info> {
+    /// Sets up the position if this is its first deposit, checking the range against the pool's ticks.
+    pub fn open_position(&mut self, tick_lower: i32, tick_upper: i32, bump: u8) -> Result<()> {
+        if self.position.owner == Pubkey::default() {
+            self.pool.check_tick(tick_lower)?;
+            self.pool.check_tick(tick_upper)?;
+            require!(tick_lower < tick_upper, AmmError::InvalidTick);
+            self.position.set_inner(ClPosition {
+                owner: self.user.key(),
+                pool: self.pool.key(),
+                tick_lower,
+                tick_upper,
+                liquidity: 0,
+                fee_growth_inside_last_x: 0,
+                fee_growth_inside_last_y: 0,
+                tokens_owed_x: 0,
+                tokens_owed_y: 0,
+                bump,
+            });
+        }
+        Ok(())
+    }
+
+    /// Adds `liquidity` to the position, paying at most `max_x` and `max_y`.
+    pub fn cl_deposit(&mut self, liquidity: u128, max_x: u64, max_y: u64) -> Result<()> {
+        require!(!self.pool.locked, AmmError::PoolLocked);
+        require!(liquidity > 0, AmmError::InvalidAmount);
+        let delta = i128::try_from(liquidity).map_err(|_| AmmError::Overflow)?;
+
+        let (x, y) = self.pool.modify_position(
+            &mut self.position,
+            &mut self.tick_array_lower,
+            self.tick_array_upper.as_deref_mut().map(|array| &mut **array),
+            delta,
+        )?;
+
+        // Gross up for transfer fees so the vaults receive the full amounts
+        let x_in = get_amount_with_transfer_fee(&self.mint_x, x)?;
+        let y_in = get_amount_with_transfer_fee(&self.mint_y, y)?;
+        require!(x_in <= max_x && y_in <= max_y, AmmError::SlippageExceeded);
+
+        let received_x = self.deposit_tokens(true, x_in)?;
+        let received_y = self.deposit_tokens(false, y_in)?;
+        require!(received_x >= x && received_y >= y, AmmError::InsufficientFunds);
+
+        emit!(ClLiquidityAdded {
+            pool: self.pool.key(),
+            user: self.user.key(),
+            position: self.position.key(),
+            tick_lower: self.position.tick_lower,
+            tick_upper: self.position.tick_upper,
+            liquidity,
+            amount_x: received_x,
+            amount_y: received_y,
+        });
+
+        Ok(())
+    }
+
+    /// Transfers tokens from the user to the pool vault.
+    /// Returns the amount actually received by the vault, net of any transfer fee.
+    fn deposit_tokens(&mut self, is_x: bool, amount: u64) -> Result<u64> {
+        if amount == 0 {
+            return Ok(0);
+        }
+        let (from, vault, mint) = if is_x {
+            (&self.user_x, &mut self.vault_x, &self.mint_x)
+        } else {
+            (&self.user_y, &mut self.vault_y, &self.mint_y)
+        };
+        require!(from.amount >= amount, AmmError::InsufficientFunds);
+
+        let cpi_accounts = TransferChecked {
+            from: from.to_account_info(),
+            mint: mint.to_account_info(),
+            to: vault.to_account_info(),
+            authority: self.user.to_account_info(),
+        };
+        let ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        transfer_checked(ctx, amount, mint.decimals)?;
+
+        let before = vault.amount;
+        vault.reload()?;
+        vault.amount.checked_sub(before).ok_or(error!(AmmError::Underflow))
+    }
+}
+
+#[derive(Accounts)]
+pub struct ClWithdraw<'info> {
+    /// The position's owner.
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// The mint for token X.
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    /// The mint for token Y.
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    /// The concentrated pool PDA.
+    #[account(
+        mut,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"cl_pool", pool.seed.to_le_bytes().as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, ClPool>,
+    /// The pool's vault for token X.
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = pool,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    /// The pool's vault for token Y.
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = pool,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    /// The user's token X account.
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_x: InterfaceAccount<'info, TokenAccount>,
+    /// The user's token Y account.
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_y: InterfaceAccount<'info, TokenAccount>,
+    /// The tick array holding the position's lower tick.
+    #[account(mut, has_one = pool)]
+    pub tick_array_lower: Box<Account<'info, TickArray>>,
+    /// The tick array holding the position's upper tick, if it isn't `tick_array_lower`.
+    #[account(mut, has_one = pool)]
+    pub tick_array_upper: Option<Box<Account<'info, TickArray>>>,
+    /// The user's position.
+    #[account(
+        mut,
+        has_one = pool,
+        seeds = [
+            b"cl_position",
+            pool.key().as_ref(),
+            user.key().as_ref(),
+            position.tick_lower.to_le_bytes().as_ref(),
+            position.tick_upper.to_le_bytes().as_ref(),
+        ],
+        bump = position.bump,
+    )]
+    pub position: Account<'info, ClPosition>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> ClWithdraw<'info> {
+    /// Removes `liquidity` from the position, receiving at least `min_x` and `min_y`.
+    /// The fees accrued stay in the position until collected.
+    pub fn cl_withdraw(&mut self, liquidity: u128, min_x: u64, min_y: u64) -> Result<()> {
+        require!(!self.pool.locked, AmmError::PoolLocked);
+        require!(liquidity > 0, AmmError::InvalidAmount);
+        require!(liquidity <= self.position.liquidity, AmmError::InsufficientLiquidity);
+        let delta = i128::try_from(liquidity).map_err(|_| AmmError::Overflow)?;
+
+        let (x, y) = self.modify_position(-delta)?;
+        let delivered_x = self.withdraw_tokens(true, x)?;
+        let delivered_y = self.withdraw_tokens(false, y)?;
+        require!(delivered_x >= min_x && delivered_y >= min_y, AmmError::SlippageExceeded);
+
+        emit!(ClLiquidityRemoved {
+            pool: self.pool.key(),
+            user: self.user.key(),
+            position: self.position.key(),
+            tick_lower: self.position.tick_lower,
+            tick_upper: self.position.tick_upper,
+            liquidity,
+            amount_x: x,
+            amount_y: y,
+        });
+
+        Ok(())
+    }
+
+    /// Pays out all the fees the position has earned.
+    pub fn cl_collect(&mut self) -> Result<()> {
+        require!(!self.pool.locked, AmmError::PoolLocked);
+        self.modify_position(0)?;
+        let (amount_x, amount_y) = (self.position.tokens_owed_x, self.position.tokens_owed_y);
+        self.position.tokens_owed_x = 0;
+        self.position.tokens_owed_y = 0;
+
+        self.withdraw_tokens(true, amount_x)?;
+        self.withdraw_tokens(false, amount_y)?;
+
+        emit!(ClFeesCollected {
+            pool: self.pool.key(),
+            user: self.user.key(),
+            position: self.position.key(),
+            amount_x,
+            amount_y,
+        });
+
+        Ok(())
+    }
+
+    fn modify_position(&mut self, liquidity_delta: i128) -> Result<(u64, u64)> {
+        self.pool.modify_position(
+            &mut self.position,
+            &mut self.tick_array_lower,
+            self.tick_array_upper.as_deref_mut().map(|array| &mut **array),
+            liquidity_delta,
+        )
+    }
+
+    /// Transfers tokens from the pool vault to the user, using the pool PDA as authority.
+    /// Returns the amount the user received, net of any transfer fee.
+    fn withdraw_tokens(&mut self, is_x: bool, amount: u64) -> Result<u64> {
+        if amount == 0 {
+            return Ok(0);
+        }
+        let (vault, to, mint) = if is_x {
+            (&self.vault_x, &mut self.user_x, &self.mint_x)
+        } else {
+            (&self.vault_y, &mut self.user_y, &self.mint_y)
+        };
+
+        let seeds = &[&b"cl_pool"[..], &self.pool.seed.to_le_bytes(), &[self.pool.bump]];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = TransferChecked {
+            from: vault.to_account_info(),
+            mint: mint.to_account_info(),
+            to: to.to_account_info(),
+            authority: self.pool.to_account_info(),
+        };
+        let ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer_checked(ctx, amount, mint.decimals)?;
+
+        let before = to.amount;
+        to.reload()?;
+        to.amount.checked_sub(before).ok_or(error!(AmmError::Underflow))
+    }
+}
+
+#[event]
+pub struct ClLiquidityAdded {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    /// Token X received by the vault.
+    pub amount_x: u64,
+    /// Token Y received by the vault.
+    pub amount_y: u64,
+}
+
+#[event]
+pub struct ClLiquidityRemoved {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    /// Token X sent from the vault.
+    pub amount_x: u64,
+    /// Token Y sent from the vault.
+    pub amount_y: u64,
+}
+
+#[event]
+pub struct ClFeesCollected {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub position: Pubkey,
+    /// Token X fees sent from the vault.
+    pub amount_x: u64,
+    /// Token Y fees sent from the vault.
+    pub amount_y: u64,
+}
@@ -0,0 +1,202 @@
+// This file defines the 'ClSwap' instruction for the AMM program.
+// It swaps through a concentrated liquidity pool, crossing position ranges as the price moves.
+//
+// Key roles:
+// - 'user': The swapper.
+// - 'pool': The concentrated pool's PDA.
+// - 'vault_x' and 'vault_y': The pool's token vaults.
+// - remaining accounts: The writable tick arrays the price may move through, in any order.
+//
+// The cl_swap flow:
+// - The price moves from tick to tick. Between two initialized ticks the active liquidity is
+//   constant and the swap is priced like a constant product pool with that much liquidity.
+// - Each time the price crosses an initialized tick, the liquidity of the positions starting or
+//   ending there is added or removed, and the tick's fee growth is flipped to the other side.
+// - The swap stops when the input is spent or the price reaches `sqrt_price_limit`; only the
+//   input actually used is taken from the user. It fails if it needs a tick array that wasn't passed.
+// - The fee is taken from the input of every step and shared by the liquidity active during it.
+//   Concentrated pools have no protocol fee.
+// - Slippage is checked on the output the user receives. For Token-2022 mints with a transfer fee,
+//   the input transfer is grossed up so the vault receives the full amount used.
+// - A `SwapEvent` is emitted, with the vault balances as the reserves.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{ transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked };
+
+use crate::{
+    state::{ ClPool, TickArray },
+    error::AmmError,
+    instructions::SwapEvent,
+    utils::get_amount_with_transfer_fee,
+};
+
+#[derive(Accounts)]
+pub struct ClSwap<'info> {
+    /// The user performing the swap.
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// The mint for token X.
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    /// The mint for token Y.
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    /// The concentrated pool PDA.
+    #[account(
+        mut,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"cl_pool", pool.seed.to_le_bytes().as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, ClPool>,
+    /// The pool's vault for token X.
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = pool,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    /// The pool's vault for token Y.
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = pool,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    /// The user's token X account.
+    #[account(
+        mut,
+        token::mint = mint_x,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub user_x: InterfaceAccount<'info, TokenAccount>,
+    /// The user's token Y account.
+    #[account(
+        mut,
+        token::mint = mint_y,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub user_y: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> ClSwap<'info> {
+    /// Swaps up to `amount_in` of token X (`x_to_y`) or Y, stopping at `sqrt_price_limit`,
+    /// for at least `min_amount_out`. `tick_arrays` are the pool's tick arrays to swap through.
+    pub fn cl_swap(
+        &mut self,
+        tick_arrays: &'info [AccountInfo<'info>],
+        amount_in: u64,
+        min_amount_out: u64,
+        x_to_y: bool,
+        sqrt_price_limit: u128,
+    ) -> Result<()> {
+        require!(!self.pool.locked, AmmError::PoolLocked);
+        require!(amount_in > 0, AmmError::InvalidAmount);
+
+        let mut arrays = self.load_tick_arrays(tick_arrays)?;
+        let mut refs: Vec<&mut TickArray> = arrays.iter_mut().map(|array| &mut **array).collect();
+        let result = self.pool.swap(&mut refs, amount_in, x_to_y, sqrt_price_limit)?;
+        require!(result.amount_out > 0, AmmError::InvalidAmount);
+        for array in &arrays {
+            array.exit(&crate::ID)?;
+        }
+
+        let mint_in = if x_to_y { &self.mint_x } else { &self.mint_y };
+        let gross_in = get_amount_with_transfer_fee(mint_in, result.amount_in)?;
+        let received = self.transfer_in(x_to_y, gross_in)?;
+        require!(received >= result.amount_in, AmmError::InsufficientFunds);
+
+        // Slippage protection, on what the user actually receives
+        let delivered = self.transfer_out(x_to_y, result.amount_out)?;
+        require!(delivered >= min_amount_out, AmmError::SlippageExceeded);
+
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        emit!(SwapEvent {
+            pool: self.pool.key(),
+            user: self.user.key(),
+            amount_in: received,
+            amount_out: result.amount_out,
+            fee_amount: result.fee_amount,
+            fee: self.pool.fee,
+            x_to_y,
+            reserve_x: self.vault_x.amount,
+            reserve_y: self.vault_y.amount,
+        });
+
+        Ok(())
+    }
+
+    /// Deserializes the pool's tick arrays from the remaining accounts.
+    /// Each must be writable, belong to the pool, and appear only once.
+    fn load_tick_arrays(&self, infos: &'info [AccountInfo<'info>]) -> Result<Vec<Account<'info, TickArray>>> {
+        let mut arrays: Vec<Account<'info, TickArray>> = Vec::with_capacity(infos.len());
+        for info in infos {
+            require!(info.is_writable, AmmError::InvalidTickArray);
+            let array = Account::<TickArray>::try_from(info)?;
+            require_keys_eq!(array.pool, self.pool.key(), AmmError::InvalidTickArray);
+            // A duplicate would overwrite the other copy's changes when written back
+            require!(
+                arrays.iter().all(|other| other.start_tick != array.start_tick),
+                AmmError::InvalidTickArray
+            );
+            arrays.push(array);
+        }
+        Ok(arrays)
+    }
+
+    /// Transfers the input tokens from the user to the vault.
+    /// Returns the amount the vault received, net of any transfer fee.
+    fn transfer_in(&mut self, x_to_y: bool, amount: u64) -> Result<u64> {
+        let (user_src, vault, mint) = if x_to_y {
+            (&self.user_x, &mut self.vault_x, &self.mint_x)
+        } else {
+            (&self.user_y, &mut self.vault_y, &self.mint_y)
+        };
+        require!(user_src.amount >= amount, AmmError::InsufficientFunds);
+
+        let cpi_accounts = TransferChecked {
+            from: user_src.to_account_info(),
+            mint: mint.to_account_info(),
+            to: vault.to_account_info(),
+            authority: self.user.to_account_info(),
+        };
+        let ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        transfer_checked(ctx, amount, mint.decimals)?;
+
+        let before = vault.amount;
+        vault.reload()?;
+        vault.amount.checked_sub(before).ok_or(error!(AmmError::Underflow))
+    }
+
+    /// Transfers the output tokens from the vault to the user, using the pool PDA as authority.
+    /// Returns the amount the user received, net of any transfer fee.
+    fn transfer_out(&mut self, x_to_y: bool, amount: u64) -> Result<u64> {
+        let (vault, user_dst, mint) = if x_to_y {
+            (&self.vault_y, &mut self.user_y, &self.mint_y)
+        } else {
+            (&self.vault_x, &mut self.user_x, &self.mint_x)
+        };
+
+        let seeds = &[&b"cl_pool"[..], &self.pool.seed.to_le_bytes(), &[self.pool.bump]];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = TransferChecked {
+            from: vault.to_account_info(),
+            mint: mint.to_account_info(),
+            to: user_dst.to_account_info(),
+            authority: self.pool.to_account_info(),
+        };
+        let ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer_checked(ctx, amount, mint.decimals)?;
+
+        let before = user_dst.amount;
+        user_dst.reload()?;
+        user_dst.amount.checked_sub(before).ok_or(error!(AmmError::Underflow))
+    }
+}
@@ -0,0 +1,72 @@
+// This file defines the 'ClUpdate' instruction context for the AMM program.
+// It lets a concentrated pool's authority administer the pool, like `Update` does for other pools.
+//
+// Key roles:
+// - 'authority': Must match `pool.authority`.
+// - 'pool': The concentrated pool's PDA being updated.
+//
+// The update flow:
+// - cl_lock / cl_unlock: Pauses or resumes deposits, swaps, withdrawals and fee collection on the pool.
+// - cl_transfer_authority: Hands the pool to a new authority, or renounces it with `None`.
+// Pools whose authority is `None` are permanently immutable.
+// Each change emits the same event as its `Update` counterpart, with the pool key and seed.
+
+use anchor_lang::prelude::*;
+
+use crate::{
+    state::ClPool,
+    instructions::{ AuthorityTransferred, PoolLockUpdated },
+};
+
+#[derive(Accounts)]
+pub struct ClUpdate<'info> {
+    /// The pool's update authority.
+    pub authority: Signer<'info>,
+    /// The concentrated pool PDA.
+    #[account(
+        mut,
+        seeds = [b"cl_pool", pool.seed.to_le_bytes().as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, ClPool>,
+}
+
+impl<'info> ClUpdate<'info> {
+    /// Locks the pool, rejecting deposits, swaps, withdrawals and fee collection until unlocked.
+    pub fn cl_lock(&mut self) -> Result<()> {
+        self.pool.check_authority(&self.authority.key())?;
+        self.pool.locked = true;
+        self.emit_lock_updated();
+        Ok(())
+    }
+
+    /// Unlocks a previously locked pool.
+    pub fn cl_unlock(&mut self) -> Result<()> {
+        self.pool.check_authority(&self.authority.key())?;
+        self.pool.locked = false;
+        self.emit_lock_updated();
+        Ok(())
+    }
+
+    /// Transfers the update authority. Passing `None` renounces it for good.
+    pub fn cl_transfer_authority(&mut self, new_authority: Option<Pubkey>) -> Result<()> {
+        self.pool.check_authority(&self.authority.key())?;
+
+        emit!(AuthorityTransferred {
+            pool: self.pool.key(),
+            seed: self.pool.seed,
+            old_authority: self.pool.authority,
+            new_authority,
+        });
+        self.pool.authority = new_authority;
+        Ok(())
+    }
+
+    fn emit_lock_updated(&self) {
+        emit!(PoolLockUpdated {
+            pool: self.pool.key(),
+            seed: self.pool.seed,
+            locked: self.pool.locked,
+        });
+    }
+}
@@ -0,0 +1,205 @@
+// This file defines the 'ClosePool' instruction for the AMM program.
+// It lets the pool authority close a drained pool and return its rent to the initializer.
+//
+// Key roles:
+// - 'authority': Must match `config.authority`.
+// - 'initializer': The account that created the pool, receiving the rent back.
+// - 'config', 'canonical_pool', 'vault_x', 'vault_y' and 'lp_lock': The accounts closed.
+// - 'initializer_x' and 'initializer_y': Receive any tokens left in the vaults.
+//
+// The close flow:
+// - The pool must be drained: the only LP left is the permanently locked `MINIMUM_LIQUIDITY`
+//   (or none, if the pool was never funded), protocol fees have been collected and no flash loan
+//   is outstanding.
+// - The locked LP is burned, and the dust backing it is sent from the vaults to the initializer.
+// - The vaults and the LP lock account are closed, then the config and the canonical pool PDA,
+//   all refunding rent to the initializer. The pair and fee tier can then be used for a new pool.
+// - The LP mint stays: SPL Token mints can't be closed. It has no supply left, and its authority is
+//   the closed config PDA, which can't be recreated because pool seeds are never reused.
+// - Token-2022 vaults holding withheld transfer fees can't be closed until those are harvested.
+// - A `PoolClosed` event records the pool key and seed.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    burn, close_account, transfer_checked, Burn, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::{
+    state::{ CanonicalPool, Config },
+    error::AmmError,
+};
+
+#[derive(Accounts)]
+pub struct ClosePool<'info> {
+    /// The pool's update authority.
+    pub authority: Signer<'info>,
+    /// The account that created the pool.
+    #[account(mut)]
+    pub initializer: SystemAccount<'info>,
+    /// The mint for token X.
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    /// The mint for token Y.
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    /// The config PDA for the pool.
+    #[account(
+        mut,
+        close = initializer,
+        has_one = mint_x,
+        has_one = mint_y,
+        has_one = initializer,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+    /// The canonical pool PDA for the mint pair and fee tier.
+    #[account(
+        mut,
+        close = initializer,
+        has_one = config,
+        seeds = [
+            b"pool",
+            CanonicalPool::sorted_mints(mint_x.key(), mint_y.key()).0.as_ref(),
+            CanonicalPool::sorted_mints(mint_x.key(), mint_y.key()).1.as_ref(),
+            canonical_pool.fee_tier.to_le_bytes().as_ref(),
+        ],
+        bump = canonical_pool.bump
+    )]
+    pub canonical_pool: Account<'info, CanonicalPool>,
+    /// The LP token mint (PDA, authority = config).
+    #[account(
+        mut,
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump,
+        mint::token_program = token_program,
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+    /// Token account holding the permanently locked minimum liquidity.
+    #[account(
+        mut,
+        seeds = [b"lock", config.key().as_ref()],
+        bump,
+    )]
+    pub lp_lock: InterfaceAccount<'info, TokenAccount>,
+    /// The pool's vault for token X.
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    /// The pool's vault for token Y.
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    /// The initializer's token X account, receiving any dust left in `vault_x`.
+    #[account(
+        mut,
+        token::mint = mint_x,
+        token::authority = initializer,
+        token::token_program = token_program,
+    )]
+    pub initializer_x: InterfaceAccount<'info, TokenAccount>,
+    /// The initializer's token Y account, receiving any dust left in `vault_y`.
+    #[account(
+        mut,
+        token::mint = mint_y,
+        token::authority = initializer,
+        token::token_program = token_program,
+    )]
+    pub initializer_y: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> ClosePool<'info> {
+    /// Burns the locked liquidity, empties and closes the pool's token accounts, and emits `PoolClosed`.
+    /// The config and canonical pool accounts are closed by Anchor when the instruction returns.
+    pub fn close_pool(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(self.config.flash_loan.is_none(), AmmError::FlashLoanActive);
+        require!(
+            self.config.protocol_fees_x == 0 && self.config.protocol_fees_y == 0,
+            AmmError::PoolNotEmpty
+        );
+        // Only the permanently locked liquidity may remain
+        require!(self.mint_lp.supply == self.lp_lock.amount, AmmError::PoolNotEmpty);
+
+        self.burn_locked_liquidity()?;
+        self.sweep(true)?;
+        self.sweep(false)?;
+
+        self.close_token_account(self.vault_x.to_account_info())?;
+        self.close_token_account(self.vault_y.to_account_info())?;
+        self.close_token_account(self.lp_lock.to_account_info())?;
+
+        emit!(PoolClosed {
+            pool: self.config.key(),
+            seed: self.config.seed,
+        });
+
+        Ok(())
+    }
+
+    /// Burns the `MINIMUM_LIQUIDITY` held by the LP lock account, signed by the config PDA.
+    fn burn_locked_liquidity(&self) -> Result<()> {
+        if self.lp_lock.amount == 0 {
+            return Ok(());
+        }
+        let seeds = &[&b"config"[..], &self.config.seed.to_le_bytes(), &[self.config.config_bump]];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = Burn {
+            mint: self.mint_lp.to_account_info(),
+            from: self.lp_lock.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+        let ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        burn(ctx, self.lp_lock.amount)
+    }
+
+    /// Sends whatever is left in the vault for token X (`is_x`) or Y to the initializer.
+    fn sweep(&self, is_x: bool) -> Result<()> {
+        let (vault, to, mint) = if is_x {
+            (&self.vault_x, &self.initializer_x, &self.mint_x)
+        } else {
+            (&self.vault_y, &self.initializer_y, &self.mint_y)
+        };
+        if vault.amount == 0 {
+            return Ok(());
+        }
+        let seeds = &[&b"config"[..], &self.config.seed.to_le_bytes(), &[self.config.config_bump]];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = TransferChecked {
+            from: vault.to_account_info(),
+            mint: mint.to_account_info(),
+            to: to.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+        let ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer_checked(ctx, vault.amount, mint.decimals)
+    }
+
+    /// Closes a token account owned by the config PDA, refunding its rent to the initializer.
+    fn close_token_account(&self, account: AccountInfo<'info>) -> Result<()> {
+        let seeds = &[&b"config"[..], &self.config.seed.to_le_bytes(), &[self.config.config_bump]];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = CloseAccount {
+            account,
+            destination: self.initializer.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+        let ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        close_account(ctx)
+    }
+}
+
+#[event]
+pub struct PoolClosed {
+    pub pool: Pubkey,
+    pub seed: u64,
+}
@@ -0,0 +1,130 @@
+// This file defines the 'CollectProtocolFees' instruction for the AMM program.
+// It lets the pool authority withdraw the protocol's share of swap fees to the pool's treasury.
+//
+// Key roles:
+// - 'authority': Must match `config.authority`.
+// - 'vault_x' and 'vault_y': The pool's token vaults holding the accrued fees.
+// - 'treasury_x' and 'treasury_y': Token accounts receiving the fees, owned by `config.treasury`.
+//
+// The collect flow:
+// - The program transfers `config.protocol_fees_x` / `config.protocol_fees_y` from the vaults
+//   to the treasury accounts, using the config PDA as authority.
+// - The claimable balances are reset to zero. LP reserves are unaffected.
+// - A `ProtocolFeesCollected` event records the amounts sent.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{ state::Config, error::AmmError };
+
+#[derive(Accounts)]
+pub struct CollectProtocolFees<'info> {
+    /// The pool's update authority.
+    pub authority: Signer<'info>,
+    /// The mint for token X.
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    /// The mint for token Y.
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    /// The config PDA for the pool.
+    #[account(
+        mut,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+    /// The pool's vault for token X.
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    /// The pool's vault for token Y.
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    /// The treasury token account receiving token X fees.
+    #[account(
+        mut,
+        token::mint = mint_x,
+        token::token_program = token_program,
+        constraint = treasury_x.owner == config.treasury @ AmmError::InvalidTreasury,
+    )]
+    pub treasury_x: InterfaceAccount<'info, TokenAccount>,
+    /// The treasury token account receiving token Y fees.
+    #[account(
+        mut,
+        token::mint = mint_y,
+        token::token_program = token_program,
+        constraint = treasury_y.owner == config.treasury @ AmmError::InvalidTreasury,
+    )]
+    pub treasury_y: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> CollectProtocolFees<'info> {
+    /// Transfers `amount` of token X or Y from the vault to the treasury, signed by the config PDA.
+    pub fn withdraw_fees(&mut self, is_x: bool, amount: u64) -> Result<()> {
+        let (from, to, mint, decimals) = match is_x {
+            true => (self.vault_x.to_account_info(), self.treasury_x.to_account_info(), self.mint_x.to_account_info(), self.mint_x.decimals),
+            false => (self.vault_y.to_account_info(), self.treasury_y.to_account_info(), self.mint_y.to_account_info(), self.mint_y.decimals),
+        };
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint,
+            to,
+            authority: self.config.to_account_info(),
+        };
+
+        let seeds = &[&b"config"[..], &self.config.seed.to_le_bytes(), &[self.config.config_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        transfer_checked(ctx, amount, decimals)
+    }
+
+    /// Sends all accrued protocol fees to the treasury and resets the claimable balances.
+    pub fn collect_protocol_fees(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(self.config.flash_loan.is_none(), AmmError::FlashLoanActive);
+
+        let (fees_x, fees_y) = (self.config.protocol_fees_x, self.config.protocol_fees_y);
+        self.config.protocol_fees_x = 0;
+        self.config.protocol_fees_y = 0;
+
+        if fees_x > 0 {
+            self.withdraw_fees(true, fees_x)?;
+        }
+        if fees_y > 0 {
+            self.withdraw_fees(false, fees_y)?;
+        }
+
+        emit!(ProtocolFeesCollected {
+            pool: self.config.key(),
+            seed: self.config.seed,
+            amount_x: fees_x,
+            amount_y: fees_y,
+        });
+
+        Ok(())
+    }
+}
+
+#[event]
+pub struct ProtocolFeesCollected {
+    pub pool: Pubkey,
+    pub seed: u64,
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
@@ -6,20 +6,42 @@
 // - 'vault_x' and 'vault_y': The pool's token vaults.
 // - 'mint_lp': The LP token mint.
 // - 'user_lp': The user's LP token account.
+// - 'lp_lock': Holds the minimum liquidity locked on the first deposit.
+// - 'position': The user's position in the pool, created on their first deposit.
 //
 // The deposit flow:
 // - User transfers tokens X and Y to the pool vaults.
 // - The program mints LP tokens to the user, representing their share of the pool.
+// - The first deposit mints sqrt(x * y) LP (the invariant D on StableSwap pools) and locks
+//   `MINIMUM_LIQUIDITY` of it forever.
 // - Proportional math ensures fair share for all liquidity providers.
+// - A `LiquidityAdded` event records the amounts, LP minted and the pool state afterwards.
+// - The amounts paid in and the LP minted are added to the user's position, along with the pool's
+//   liquidity per LP at entry, so `position_info` can later report fees and impermanent loss.
+//
+// The single-sided deposit flow (zap in):
+// - User transfers only token X or only token Y to the pool vault.
+// - Part of it is swapped on the pool's curve (paying the pool fee) so the remainder matches the pool ratio.
+// - LP tokens are minted for the balanced result.
+//
+// Token-2022 mints with a transfer fee are supported: LP is always computed from the amounts
+// actually received by the vaults, and proportional deposits gross up the transfer so the
+// vaults receive what the curve requires.
 
+use amm_quote::quote_deposit;
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{ Transfer, transfer, Mint, Token, TokenAccount, MintTo, mint_to },
+    token_interface::{ transfer_checked, mint_to, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked },
 };
-use constant_product_curve::ConstantProduct;
 
-use crate::{ state::Config, error::AmmError };
+use crate::{
+    state::{ Config, Position },
+    error::AmmError,
+    instructions::get_amount_out,
+    constants::MINIMUM_LIQUIDITY,
+    utils::get_amount_with_transfer_fee,
+};
 
 #[derive(Accounts)]
 pub struct Deposit<'info> {
@@ -27,12 +49,15 @@ pub struct Deposit<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
     /// The mint for token X.
-    pub mint_x: Account<'info, Mint>,
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
     /// The mint for token Y.
-    pub mint_y: Account<'info, Mint>,
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
 
     /// The config PDA for the pool.
     #[account(
+        mut,
         has_one = mint_x,
         has_one = mint_y,
         seeds = [b"config", config.seed.to_le_bytes().as_ref()],
@@ -44,17 +69,19 @@ pub struct Deposit<'info> {
     #[account(
         mut,
         associated_token::mint = mint_x,
-        associated_token::authority = config
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
     )]
-    pub vault_x: Account<'info, TokenAccount>,
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
 
     /// The pool's vault for token Y.
     #[account(
         mut,
         associated_token::mint = mint_y,
         associated_token::authority = config,
+        associated_token::token_program = token_program,
     )]
-    pub vault_y: Account<'info, TokenAccount>,
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
 
     /// The LP token mint (PDA, authority = config).
     #[account(
@@ -63,65 +90,119 @@ pub struct Deposit<'info> {
         bump,
         mint::decimals = 6,
         mint::authority = config,
+        mint::token_program = token_program,
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+
+    /// Token account holding the permanently locked minimum liquidity.
+    #[account(
+        mut,
+        seeds = [b"lock", config.key().as_ref()],
+        bump,
     )]
-    pub mint_lp: Account<'info, Mint>,
+    pub lp_lock: InterfaceAccount<'info, TokenAccount>,
 
     /// The user's token X account.
     #[account(
         mut,
         associated_token::mint = mint_x,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub user_x: Account<'info, TokenAccount>,
+    pub user_x: InterfaceAccount<'info, TokenAccount>,
 
     /// The user's token Y account.
     #[account(
         mut,
         associated_token::mint = mint_y,
         associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub user_y: Account<'info, TokenAccount>,
+    pub user_y: InterfaceAccount<'info, TokenAccount>,
 
     /// The user's LP token account.
     #[account(
         init_if_needed,
         payer = user,
         associated_token::mint = mint_lp,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_lp: InterfaceAccount<'info, TokenAccount>,
+
+    /// The user's position in the pool, created on their first deposit.
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"position", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + Position::INIT_SPACE,
     )]
-    pub user_lp: Account<'info, TokenAccount>,
+    pub position: Account<'info, Position>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Deposit<'info> {
+    /// Sets up the user's position if this is their first deposit into the pool.
+    pub fn open_position(&mut self, bump: u8) -> Result<()> {
+        if self.position.owner == Pubkey::default() {
+            self.position.set_inner(Position {
+                owner: self.user.key(),
+                pool: self.config.key(),
+                lp_amount: 0,
+                cost_x: 0,
+                cost_y: 0,
+                entry_liquidity_per_lp: 0,
+                bump,
+            });
+        }
+        Ok(())
+    }
+
     /// Transfers tokens from the user to the pool vaults.
-    pub fn deposit_tokens(&mut self, is_x: bool, amount: u64) -> Result<()> {
-        let (from, to) = match is_x {
-            true => (self.user_x.to_account_info(), self.vault_x.to_account_info()),
-            false => (self.user_y.to_account_info(), self.vault_y.to_account_info()),
+    /// Returns the amount actually received by the vault, net of any transfer fee.
+    pub fn deposit_tokens(&mut self, is_x: bool, amount: u64) -> Result<u64> {
+        let (from, to, mint, decimals) = match is_x {
+            true => (self.user_x.to_account_info(), self.vault_x.to_account_info(), self.mint_x.to_account_info(), self.mint_x.decimals),
+            false => (self.user_y.to_account_info(), self.vault_y.to_account_info(), self.mint_y.to_account_info(), self.mint_y.decimals),
         };
         let cpi_program = self.token_program.to_account_info();
 
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint,
             to,
             authority: self.user.to_account_info(),
         };
 
         let ctx = CpiContext::new(cpi_program, cpi_accounts);
-        transfer(ctx, amount)
+        transfer_checked(ctx, amount, decimals)?;
+
+        let vault = if is_x { &mut self.vault_x } else { &mut self.vault_y };
+        let before = vault.amount;
+        vault.reload()?;
+        vault.amount.checked_sub(before).ok_or(error!(AmmError::Underflow))
     }
 
     /// Mints LP tokens to the user, using the config PDA as authority.
     pub fn mint_lp_tokens(&mut self, amount: u64) -> Result<()> {
+        self.mint_lp_to(self.user_lp.to_account_info(), amount)
+    }
+
+    /// Mints `MINIMUM_LIQUIDITY` LP tokens into the pool's lock account.
+    pub fn lock_minimum_liquidity(&mut self) -> Result<()> {
+        self.mint_lp_to(self.lp_lock.to_account_info(), MINIMUM_LIQUIDITY)
+    }
+
+    fn mint_lp_to(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
         let cpi_program = self.token_program.to_account_info();
 
         let cpi_accounts = MintTo {
             mint: self.mint_lp.to_account_info(),
-            to: self.user_lp.to_account_info(),
+            to,
             authority: self.config.to_account_info(),
         };
 
@@ -137,36 +218,206 @@ impl<'info> Deposit<'info> {
     pub fn deposit(&mut self, amount: u64, max_x: u64, max_y: u64) -> Result<()> {
         // Check if pool is locked
         require!(!self.config.locked, AmmError::PoolLocked);
+        require!(self.config.flash_loan.is_none(), AmmError::FlashLoanActive);
         require!(amount != 0, AmmError::InvalidAmount);
 
-        let (x, y) = if self.mint_lp.supply == 0 &&
-            self.vault_x.amount == 0 &&
-            self.vault_y.amount == 0
-        {
-            // First deposit - use max amounts
-            (max_x, max_y)
-        } else {
-            // Subsequent deposits - calculate proportional amounts
-            let amounts = ConstantProduct::xy_deposit_amounts_from_l(
-                self.vault_x.amount,
-                self.vault_y.amount,
-                self.mint_lp.supply,
-                amount,
-                6
-            ).map_err(|_| AmmError::InvalidAmount)?;
-            (amounts.x, amounts.y)
-        };
+        // First deposit - seed the pool with the max amounts
+        if self.mint_lp.supply == 0 {
+            return self.first_deposit(amount, max_x, max_y);
+        }
+
+        // Reserves owned by LPs, excluding unclaimed protocol fees
+        let (reserve_x, reserve_y) = self.config.lp_reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        // Subsequent deposits - calculate proportional amounts, rounded up
+        let amounts = quote_deposit(amount, reserve_x, reserve_y, self.mint_lp.supply).map_err(AmmError::from)?;
+        let (x, y) = (amounts.amount_x, amounts.amount_y);
+
+        // Gross up for transfer fees so the vaults receive the full amounts
+        let x_in = get_amount_with_transfer_fee(&self.mint_x, x)?;
+        let y_in = get_amount_with_transfer_fee(&self.mint_y, y)?;
 
         // Check slippage
-        require!(x <= max_x && y <= max_y, AmmError::SlippageExceeded);
+        require!(x_in <= max_x && y_in <= max_y, AmmError::SlippageExceeded);
+
+        // Accumulate the TWAP oracle with the reserves from before the deposit
+        self.config.update_oracle(reserve_x, reserve_y, Clock::get()?.unix_timestamp);
 
         // Perform the deposits
-        self.deposit_tokens(true, x)?;
-        self.deposit_tokens(false, y)?;
+        let received_x = self.deposit_tokens(true, x_in)?;
+        let received_y = self.deposit_tokens(false, y_in)?;
+        require!(received_x >= x && received_y >= y, AmmError::InsufficientFunds);
 
         // Mint LP tokens
         self.mint_lp_tokens(amount)?;
 
+        self.emit_liquidity_added(received_x, received_y, amount)?;
+        self.credit_position(received_x, received_y, amount)
+    }
+
+    /// Seeds an empty pool with exactly `x` and `y`. LP is minted from the liquidity of the amounts
+    /// received (sqrt(x * y), or D on StableSwap pools) and `MINIMUM_LIQUIDITY` of it is locked
+    /// forever, so the LP price can't be inflated by the first depositor. `min_lp` bounds the LP
+    /// the caller receives.
+    fn first_deposit(&mut self, min_lp: u64, x: u64, y: u64) -> Result<()> {
+        require!(x > 0 && y > 0, AmmError::InvalidAmount);
+
+        // Start the TWAP oracle clock from the first deposit
+        let now = Clock::get()?.unix_timestamp;
+        self.config.update_oracle(0, 0, now);
+
+        let received_x = self.deposit_tokens(true, x)?;
+        let received_y = self.deposit_tokens(false, y)?;
+
+        let liquidity = self.config.liquidity(received_x, received_y, now)?;
+        require!(liquidity > MINIMUM_LIQUIDITY, AmmError::LiquidityLessThanMinimum);
+        let lp_amount = liquidity - MINIMUM_LIQUIDITY;
+        require!(lp_amount >= min_lp, AmmError::SlippageExceeded);
+
+        self.lock_minimum_liquidity()?;
+        self.mint_lp_tokens(lp_amount)?;
+
+        self.emit_liquidity_added(received_x, received_y, lp_amount)?;
+        self.credit_position(received_x, received_y, lp_amount)
+    }
+
+    /// Deposits only token X (`is_x`) or only token Y. The right fraction of `amount_in` is swapped
+    /// against the pool, charging the pool fee, and LP tokens are minted for the balanced remainder.
+    pub fn deposit_single(&mut self, is_x: bool, amount_in: u64, min_lp_out: u64) -> Result<()> {
+        // Check if pool is locked
+        require!(!self.config.locked, AmmError::PoolLocked);
+        require!(self.config.flash_loan.is_none(), AmmError::FlashLoanActive);
+        require!(amount_in != 0, AmmError::InvalidAmount);
+        require!(self.mint_lp.supply > 0, AmmError::NoLiquidityInPool);
+
+        // Reserves owned by LPs, excluding unclaimed protocol fees
+        let (reserve_x, reserve_y) = self.config.lp_reserves(self.vault_x.amount, self.vault_y.amount)?;
+        let (reserve_in, reserve_out) = if is_x { (reserve_x, reserve_y) } else { (reserve_y, reserve_x) };
+        require!(reserve_in > 0 && reserve_out > 0, AmmError::InsufficientLiquidity);
+
+        // Accumulate the TWAP oracle with the reserves from before the deposit
+        let now = Clock::get()?.unix_timestamp;
+        self.config.update_oracle(reserve_x, reserve_y, now);
+
+        // Only the input is transferred: the swapped output never leaves the vault
+        let amount_in = self.deposit_tokens(is_x, amount_in)?;
+        require!(amount_in != 0, AmmError::InvalidAmount);
+
+        // Swap part of the input so the remainder matches the post-swap pool ratio
+        let config = &self.config;
+        let (swap_in, swap_out, fee_amount) = get_single_sided_swap_with(amount_in, reserve_in, reserve_out, |swap_in| {
+            config.get_amount_out(swap_in, reserve_in, reserve_out, now)
+        })?;
+        let protocol_fee = self.config.accrue_protocol_fee(is_x, fee_amount)?;
+        let pool_in = reserve_in
+            .checked_add(swap_in)
+            .and_then(|v| v.checked_sub(protocol_fee))
+            .ok_or(AmmError::Overflow)?;
+        let pool_out = reserve_out - swap_out;
+
+        // The internal swap moves the price like any other, feeding the dynamic fee
+        let after = if is_x { (pool_in, pool_out) } else { (pool_out, pool_in) };
+        self.config.record_volatility((reserve_x, reserve_y), after, now);
+
+        // Mint LP for the balanced (amount_in - swap_in, swap_out) deposit against the post-swap reserves
+        let supply = self.mint_lp.supply as u128;
+        let lp_from_in = (amount_in - swap_in) as u128 * supply / pool_in as u128;
+        let lp_from_out = swap_out as u128 * supply / pool_out as u128;
+        let lp_amount = u64::try_from(lp_from_in.min(lp_from_out)).map_err(|_| AmmError::Overflow)?;
+
+        // Check slippage
+        require!(lp_amount > 0, AmmError::InvalidAmount);
+        require!(lp_amount >= min_lp_out, AmmError::SlippageExceeded);
+
+        self.mint_lp_tokens(lp_amount)?;
+
+        let (amount_x, amount_y) = if is_x { (amount_in, 0) } else { (0, amount_in) };
+        self.emit_liquidity_added(amount_x, amount_y, lp_amount)?;
+        self.credit_position(amount_x, amount_y, lp_amount)
+    }
+
+    /// Adds the deposit to the user's position, at the pool's liquidity per LP after the deposit.
+    /// Must be called after `emit_liquidity_added`, which reloads the vaults and LP mint.
+    fn credit_position(&mut self, amount_x: u64, amount_y: u64, lp_amount: u64) -> Result<()> {
+        let (reserve_x, reserve_y) = self.config.lp_reserves(self.vault_x.amount, self.vault_y.amount)?;
+        let now = Clock::get()?.unix_timestamp;
+        let liquidity_per_lp = self.config.liquidity_per_lp(reserve_x, reserve_y, self.mint_lp.supply, now)?;
+        self.position.credit(amount_x, amount_y, lp_amount, liquidity_per_lp)
+    }
+
+    /// Emits a `LiquidityAdded` event with the pool's LP reserves and LP supply after the deposit.
+    fn emit_liquidity_added(&mut self, amount_x: u64, amount_y: u64, lp_amount: u64) -> Result<()> {
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        self.mint_lp.reload()?;
+        let (reserve_x, reserve_y) = self.config.lp_reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        emit!(LiquidityAdded {
+            pool: self.config.key(),
+            seed: self.config.seed,
+            user: self.user.key(),
+            amount_x,
+            amount_y,
+            lp_amount,
+            reserve_x,
+            reserve_y,
+            lp_supply: self.mint_lp.supply,
+        });
+
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[event]
+pub struct LiquidityAdded {
+    pub pool: Pubkey,
+    pub seed: u64,
+    pub user: Pubkey,
+    /// Token X received by the vault.
+    pub amount_x: u64,
+    /// Token Y received by the vault.
+    pub amount_y: u64,
+    /// LP minted to the user.
+    pub lp_amount: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    pub lp_supply: u64,
+}
+
+/// Finds how much of a single-sided `amount_in` to swap so that the remaining input and the swap
+/// output are in the same ratio as the pool after the swap, on the constant product curve.
+/// Returns `(swap_in, swap_out, fee_amount)`.
+pub fn get_single_sided_swap(amount_in: u64, reserve_in: u64, reserve_out: u64, fee: u16) -> Result<(u64, u64, u64)> {
+    get_single_sided_swap_with(amount_in, reserve_in, reserve_out, |swap_in| {
+        get_amount_out(swap_in, reserve_in, reserve_out, fee)
+    })
+}
+
+/// Curve-agnostic form of `get_single_sided_swap`, pricing each candidate swap with `quote`,
+/// which returns `(amount_out, fee_amount)`. Binary searches for the largest swap that still
+/// leaves enough input, which keeps rounding in favour of the pool.
+pub fn get_single_sided_swap_with(
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    quote: impl Fn(u64) -> Result<(u64, u64)>,
+) -> Result<(u64, u64, u64)> {
+    let (mut lo, mut hi) = (0u64, amount_in);
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        let (out, _) = quote(mid)?;
+        // (amount_in - mid) / (reserve_in + mid) >= out / (reserve_out - out)
+        let lhs = (amount_in - mid) as u128 * (reserve_out - out) as u128;
+        let balanced = (out as u128)
+            .checked_mul(reserve_in as u128 + mid as u128)
+            .is_some_and(|rhs| lhs >= rhs);
+        if balanced {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+
+    let (swap_out, fee_amount) = quote(lo)?;
+    Ok((lo, swap_out, fee_amount))
+}
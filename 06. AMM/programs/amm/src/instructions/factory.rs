@@ -0,0 +1,109 @@
+// This file defines the factory instructions for the AMM program.
+// The factory is the single registry every pool is created through.
+//
+// Key roles:
+// - 'authority': Creates the factory and manages its fee tiers. Must be the program's upgrade authority,
+//   so nobody can front-run the deployment and take the factory over.
+// - 'factory': The registry PDA, holding the pool count and the enabled fee tiers.
+// - 'program' and 'program_data': The AMM program and its program data account, holding the upgrade authority.
+//
+// The factory flow:
+// - initialize_factory: Creates the factory once, with the upgrade authority as its authority, and initial fee tiers.
+// - update_fee_tiers: Replaces the enabled fee tiers. Existing pools are not affected.
+// - Pools are then created with `initialize`, which takes the next pool index from the factory
+//   and registers the pool's canonical PDA for its mint pair and fee tier.
+
+use anchor_lang::prelude::*;
+
+use crate::{
+    program::Amm,
+    state::Factory,
+    error::AmmError,
+    constants::MAX_FEE_TIERS,
+};
+
+#[derive(Accounts)]
+pub struct InitializeFactory<'info> {
+    /// The factory authority, paying for the factory account. Must be the program's upgrade authority.
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// The factory PDA.
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"factory"],
+        bump,
+        space = 8 + Factory::INIT_SPACE,
+    )]
+    pub factory: Account<'info, Factory>,
+    /// This program, to find its program data account.
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ AmmError::InvalidAuthority)]
+    pub program: Program<'info, Amm>,
+    /// The program's program data account, holding its upgrade authority.
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ AmmError::InvalidAuthority)]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeFactory<'info> {
+    /// Creates the factory with `fee_tiers` enabled.
+    pub fn initialize_factory(&mut self, fee_tiers: Vec<u16>, bumps: InitializeFactoryBumps) -> Result<()> {
+        check_fee_tiers(&fee_tiers)?;
+        self.factory.set_inner(Factory {
+            authority: self.authority.key(),
+            pool_count: 0,
+            fee_tiers: fee_tiers.clone(),
+            bump: bumps.factory,
+        });
+
+        emit!(FeeTiersUpdated {
+            factory: self.factory.key(),
+            fee_tiers,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct UpdateFactory<'info> {
+    /// The factory authority.
+    pub authority: Signer<'info>,
+    /// The factory PDA.
+    #[account(
+        mut,
+        seeds = [b"factory"],
+        bump = factory.bump
+    )]
+    pub factory: Account<'info, Factory>,
+}
+
+impl<'info> UpdateFactory<'info> {
+    /// Replaces the fee tiers new pools may be created with.
+    pub fn update_fee_tiers(&mut self, fee_tiers: Vec<u16>) -> Result<()> {
+        self.factory.check_authority(&self.authority.key())?;
+        check_fee_tiers(&fee_tiers)?;
+        self.factory.fee_tiers = fee_tiers.clone();
+
+        emit!(FeeTiersUpdated {
+            factory: self.factory.key(),
+            fee_tiers,
+        });
+        Ok(())
+    }
+}
+
+/// Fee tiers must be valid fees, without duplicates, and fit in the factory account.
+fn check_fee_tiers(fee_tiers: &[u16]) -> Result<()> {
+    require!(fee_tiers.len() <= MAX_FEE_TIERS as usize, AmmError::InvalidFeeTier);
+    for (i, fee) in fee_tiers.iter().enumerate() {
+        require!(*fee <= 10_000, AmmError::InvalidFee);
+        require!(!fee_tiers[..i].contains(fee), AmmError::InvalidFeeTier);
+    }
+    Ok(())
+}
+
+#[event]
+pub struct FeeTiersUpdated {
+    pub factory: Pubkey,
+    pub fee_tiers: Vec<u16>,
+}
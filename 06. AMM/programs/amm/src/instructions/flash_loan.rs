@@ -0,0 +1,236 @@
+// This file defines the 'FlashLoan' instruction context for the AMM program.
+// It lends tokens out of the pool's vaults for the duration of a single transaction.
+//
+// Key roles:
+// - 'user': The borrower, who receives the loan and repays it.
+// - 'vault_x' and 'vault_y': The pool's token vaults the loan is drawn from.
+// - 'config': The pool's configuration PDA, recording the outstanding loan.
+// - 'instructions': The instructions sysvar, used to inspect the rest of the transaction.
+//
+// The flash loan flow:
+// - flash_loan: Checks, via instruction introspection, that a `flash_repay` for the same pool follows
+//   later in the transaction, records the vault balances and the fee owed, and sends the loan to the user.
+//   It must be called directly by the transaction, not through CPI.
+// - Between the two, the user is free to use the tokens, except on this pool: while the loan is
+//   outstanding the pool rejects deposits, swaps, withdrawals and another flash loan.
+// - flash_repay: Pulls the principal plus the pool fee back from the user and checks that both vaults
+//   are back to at least their balance before the loan plus the fee. If it fails, the whole transaction
+//   (including the loan) is reverted.
+// - The fee is the pool's swap fee on the principal, rounded up: the dynamic fee at the time of the loan
+//   when it is enabled, as for a swap. It stays in the vaults for LPs, minus the protocol's share,
+//   exactly like a swap fee.
+
+use amm_quote::quote_flash_loan_fee;
+use anchor_lang::{
+    prelude::*,
+    solana_program::sysvar::instructions::{ load_current_index_checked, load_instruction_at_checked, ID as INSTRUCTIONS_ID },
+    Discriminator,
+};
+use anchor_spl::token_interface::{ transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked };
+
+use crate::{
+    state::{ Config, OutstandingLoan },
+    error::AmmError,
+    utils::get_amount_with_transfer_fee,
+};
+
+/// Position of `config` in the `FlashLoan` accounts, used to match the repayment to the pool.
+const CONFIG_ACCOUNT_INDEX: usize = 3;
+
+#[derive(Accounts)]
+pub struct FlashLoan<'info> {
+    /// The borrower.
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// The mint for token X.
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    /// The mint for token Y.
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    /// The config PDA for the pool.
+    #[account(
+        mut,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+    /// The pool's vault for token X.
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    /// The pool's vault for token Y.
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    /// The user's token X account.
+    #[account(
+        mut,
+        token::mint = mint_x,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub user_x: InterfaceAccount<'info, TokenAccount>,
+    /// The user's token Y account.
+    #[account(
+        mut,
+        token::mint = mint_y,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub user_y: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: The instructions sysvar, checked by address.
+    #[account(address = INSTRUCTIONS_ID)]
+    pub instructions: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> FlashLoan<'info> {
+    /// Lends `amount_x` of token X and `amount_y` of token Y to the user,
+    /// to be repaid with the fee by a `flash_repay` later in the same transaction.
+    pub fn flash_loan(&mut self, amount_x: u64, amount_y: u64) -> Result<()> {
+        require!(!self.config.locked, AmmError::PoolLocked);
+        require!(self.config.flash_loan.is_none(), AmmError::FlashLoanActive);
+        require!(amount_x > 0 || amount_y > 0, AmmError::InvalidAmount);
+        self.check_repayment_follows()?;
+
+        let fee = self.config.effective_fee(Clock::get()?.unix_timestamp);
+        let fee_x = quote_flash_loan_fee(amount_x, fee).map_err(AmmError::from)?;
+        let fee_y = quote_flash_loan_fee(amount_y, fee).map_err(AmmError::from)?;
+        self.config.flash_loan = Some(OutstandingLoan {
+            balance_x: self.vault_x.amount,
+            balance_y: self.vault_y.amount,
+            amount_x,
+            amount_y,
+            fee_x,
+            fee_y,
+        });
+
+        self.lend(true, amount_x)?;
+        self.lend(false, amount_y)
+    }
+
+    /// Repays the outstanding loan plus its fee and releases the pool.
+    pub fn flash_repay(&mut self) -> Result<()> {
+        let loan = self.config.flash_loan.ok_or(AmmError::NoFlashLoan)?;
+
+        self.repay(true, loan.balance_x, loan.fee_x)?;
+        self.repay(false, loan.balance_y, loan.fee_y)?;
+
+        // The fee grew the reserves; accumulate the oracle with the reserves from before the loan
+        let (reserve_x, reserve_y) = self.config.lp_reserves(loan.balance_x, loan.balance_y)?;
+        self.config.update_oracle(reserve_x, reserve_y, Clock::get()?.unix_timestamp);
+        self.config.accrue_protocol_fee(true, loan.fee_x)?;
+        self.config.accrue_protocol_fee(false, loan.fee_y)?;
+        self.config.flash_loan = None;
+
+        emit!(FlashLoanRepaid {
+            pool: self.config.key(),
+            user: self.user.key(),
+            amount_x: loan.amount_x,
+            amount_y: loan.amount_y,
+            fee_x: loan.fee_x,
+            fee_y: loan.fee_y,
+        });
+
+        Ok(())
+    }
+
+    /// Ensures this instruction is a top-level instruction and that a `flash_repay`
+    /// for the same pool comes after it in the transaction.
+    fn check_repayment_follows(&self) -> Result<()> {
+        let instructions = self.instructions.to_account_info();
+        let current = load_current_index_checked(&instructions)? as usize;
+
+        // Under CPI the current top-level instruction belongs to the calling program
+        let current_ix = load_instruction_at_checked(current, &instructions)?;
+        require_keys_eq!(current_ix.program_id, crate::ID, AmmError::FlashLoanNotRepaid);
+
+        let mut index = current + 1;
+        while let Ok(ix) = load_instruction_at_checked(index, &instructions) {
+            if ix.program_id == crate::ID
+                && ix.data.starts_with(crate::instruction::FlashRepay::DISCRIMINATOR)
+                && ix.accounts.get(CONFIG_ACCOUNT_INDEX).map(|account| account.pubkey) == Some(self.config.key())
+            {
+                return Ok(());
+            }
+            index += 1;
+        }
+        err!(AmmError::FlashLoanNotRepaid)
+    }
+
+    /// Transfers `amount` of token X (`is_x`) or Y from the vault to the user, using the config PDA as authority.
+    fn lend(&self, is_x: bool, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        let (vault, user_dst, mint) = if is_x {
+            (&self.vault_x, &self.user_x, &self.mint_x)
+        } else {
+            (&self.vault_y, &self.user_y, &self.mint_y)
+        };
+
+        let seeds = &[&b"config"[..], &self.config.seed.to_le_bytes(), &[self.config.config_bump]];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = TransferChecked {
+            from: vault.to_account_info(),
+            mint: mint.to_account_info(),
+            to: user_dst.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+        let ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+
+    /// Transfers whatever the vault for token X (`is_x`) or Y is short of `balance + fee` from the user,
+    /// grossed up for any transfer fee, and checks that it arrived.
+    fn repay(&mut self, is_x: bool, balance: u64, fee: u64) -> Result<()> {
+        let owed = balance.checked_add(fee).ok_or(AmmError::Overflow)?;
+        let (vault, user_src, mint) = if is_x {
+            (&mut self.vault_x, &self.user_x, &self.mint_x)
+        } else {
+            (&mut self.vault_y, &self.user_y, &self.mint_y)
+        };
+
+        let shortfall = owed.saturating_sub(vault.amount);
+        if shortfall > 0 {
+            let amount = get_amount_with_transfer_fee(mint, shortfall)?;
+            require!(user_src.amount >= amount, AmmError::InsufficientFunds);
+
+            let cpi_accounts = TransferChecked {
+                from: user_src.to_account_info(),
+                mint: mint.to_account_info(),
+                to: vault.to_account_info(),
+                authority: self.user.to_account_info(),
+            };
+            let ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+            transfer_checked(ctx, amount, mint.decimals)?;
+            vault.reload()?;
+        }
+
+        require!(vault.amount >= owed, AmmError::InsufficientFunds);
+        Ok(())
+    }
+}
+
+#[event]
+pub struct FlashLoanRepaid {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    /// Fee paid in token X, including the protocol's share.
+    pub fee_x: u64,
+    /// Fee paid in token Y, including the protocol's share.
+    pub fee_y: u64,
+}
@@ -3,29 +3,67 @@
 //
 // Key roles:
 // - 'initializer': The user creating the pool.
+// - 'factory': The pool registry; the pool's seed must be its next pool index.
+// - 'canonical_pool': The PDA for the pool's sorted mint pair and fee tier. Creating it fails if the
+//   pair already has a pool in that tier, whichever way round its mints were given.
 // - 'config': The pool's configuration PDA.
 // - 'vault_x' and 'vault_y': The pool's token vaults.
 // - 'mint_lp': The LP token mint (PDA, authority = config).
+// - 'lp_lock': Holds the minimum liquidity locked on the first deposit.
 //
 // The initialize flow:
-// - Creates the config, vaults, and LP mint with deterministic seeds.
-// - Sets up pool parameters (fee, authority, etc).
+// - Creates the config, vaults, LP mint and LP lock account with deterministic seeds.
+// - Registers the pool in the factory and under its canonical PDA. `fee` must be an enabled fee tier.
+// - Sets up pool parameters (fee, authority, curve type, etc).
+// - StableSwap pools start with a fixed amplification coefficient; the authority can ramp it later.
+// - A `PoolInitialized` event announces the new pool to indexers.
+// - Both mints may be SPL Token or Token-2022 mints, as long as they share the same token program.
 
 use anchor_lang::prelude::*;
-use anchor_spl::{associated_token::AssociatedToken, token::{Mint, Token, TokenAccount}};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{ Mint, TokenAccount, TokenInterface },
+};
 
-use crate::state::Config;
+use crate::{
+    state::{ CanonicalPool, Config, CurveType, Factory },
+    error::AmmError,
+    constants::{ MIN_AMP, MAX_AMP },
+};
 
 #[derive(Accounts)]
-#[instruction(seed: u64)]
+#[instruction(seed: u64, fee: u16)]
 pub struct Initialize<'info> {
     /// The user creating the pool.
     #[account(mut)]
     pub initializer: Signer<'info>,
     /// The mint for token X.
-    pub mint_x: Account<'info, Mint>,
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
     /// The mint for token Y.
-    pub mint_y: Account<'info, Mint>,
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    /// The factory the pool is registered in.
+    #[account(
+        mut,
+        seeds = [b"factory"],
+        bump = factory.bump
+    )]
+    pub factory: Account<'info, Factory>,
+    /// The canonical pool PDA for the mint pair and fee tier.
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [
+            b"pool",
+            CanonicalPool::sorted_mints(mint_x.key(), mint_y.key()).0.as_ref(),
+            CanonicalPool::sorted_mints(mint_x.key(), mint_y.key()).1.as_ref(),
+            fee.to_le_bytes().as_ref(),
+        ],
+        bump,
+        space = 8 + CanonicalPool::INIT_SPACE,
+    )]
+    pub canonical_pool: Account<'info, CanonicalPool>,
     /// The LP token mint (PDA, authority = config).
     #[account(
         init,
@@ -34,8 +72,21 @@ pub struct Initialize<'info> {
         bump,
         mint::decimals = 6,
         mint::authority = config,
+        mint::token_program = token_program,
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+    /// Token account holding the permanently locked `MINIMUM_LIQUIDITY` LP tokens (PDA, authority = config).
+    /// No instruction ever transfers out of it.
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"lock", config.key().as_ref()],
+        bump,
+        token::mint = mint_lp,
+        token::authority = config,
+        token::token_program = token_program,
     )]
-    pub mint_lp: Account<'info, Mint>,
+    pub lp_lock: InterfaceAccount<'info, TokenAccount>,
     /// The config PDA for the pool.
     #[account(
         init,
@@ -50,37 +101,109 @@ pub struct Initialize<'info> {
         init,
         payer = initializer,
         associated_token::mint = mint_x,
-        associated_token::authority = config
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
     )]
-    pub vault_x: Account<'info, TokenAccount>,
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
     /// The pool's vault for token Y.
     #[account(
         init,
         payer = initializer,
         associated_token::mint = mint_y,
-        associated_token::authority = config
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
     )]
-    pub vault_y: Account<'info, TokenAccount>,
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
     /// Standard program accounts required for CPI and ATA creation.
-    pub token_program: Program<'info, Token>,
+    /// `token_program` may be SPL Token or Token-2022 and must own both mints.
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Initialize<'info> {
     /// Initializes the config state with pool parameters and bumps.
-    pub fn init(&mut self, seed: u64, fee: u16, authority: Option<Pubkey>, bumps: InitializeBumps) -> Result<()> {
+    /// `amp` is the StableSwap amplification coefficient and must be zero for constant product pools.
+    pub fn init(
+        &mut self,
+        seed: u64,
+        fee: u16,
+        authority: Option<Pubkey>,
+        curve: CurveType,
+        amp: u64,
+        bumps: InitializeBumps,
+    ) -> Result<()> {
+        require!(fee <= 10_000, AmmError::InvalidFee);
+        require!(self.factory.fee_tiers.contains(&fee), AmmError::InvalidFeeTier);
+        require!(seed == self.factory.pool_count, AmmError::InvalidPoolSeed);
+        require_keys_neq!(self.mint_x.key(), self.mint_y.key(), AmmError::InvalidToken);
+        match curve {
+            CurveType::ConstantProduct => require!(amp == 0, AmmError::InvalidAmp),
+            CurveType::StableSwap => require!((MIN_AMP..=MAX_AMP).contains(&amp), AmmError::InvalidAmp),
+        }
+        let now = Clock::get()?.unix_timestamp;
         self.config.set_inner(
             Config { 
                 seed, 
                 authority, 
+                initializer: self.initializer.key(),
                 mint_x:self.mint_x.key(), 
                 mint_y: self.mint_y.key(), 
                 fee, 
+                protocol_fee: 0,
+                protocol_fees_x: 0,
+                protocol_fees_y: 0,
+                treasury: self.initializer.key(),
                 locked: false, 
                 config_bump: bumps.config, 
                 lp_bump: bumps.mint_lp, 
+                price_x_cumulative: 0,
+                price_y_cumulative: 0,
+                last_update_ts: now,
+                curve,
+                amp_initial: amp,
+                amp_target: amp,
+                amp_ramp_start_ts: now,
+                amp_ramp_stop_ts: now,
+                flash_loan: None,
+                dynamic_fee: None,
             });
+        self.canonical_pool.set_inner(CanonicalPool {
+            config: self.config.key(),
+            seed,
+            mint_x: self.mint_x.key(),
+            mint_y: self.mint_y.key(),
+            fee_tier: fee,
+            bump: bumps.canonical_pool,
+        });
+        self.factory.pool_count = self.factory.pool_count.checked_add(1).ok_or(AmmError::Overflow)?;
+
+        emit!(PoolInitialized {
+            pool: self.config.key(),
+            seed,
+            authority,
+            mint_x: self.mint_x.key(),
+            mint_y: self.mint_y.key(),
+            mint_lp: self.mint_lp.key(),
+            canonical_pool: self.canonical_pool.key(),
+            fee,
+            curve,
+            amp,
+        });
+
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[event]
+pub struct PoolInitialized {
+    pub pool: Pubkey,
+    pub seed: u64,
+    pub authority: Option<Pubkey>,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub mint_lp: Pubkey,
+    pub canonical_pool: Pubkey,
+    pub fee: u16,
+    pub curve: CurveType,
+    pub amp: u64,
+}
@@ -0,0 +1,111 @@
+// This file defines the 'ListPools' instruction for the AMM program.
+// It is a read-only view that enumerates the pools registered in the factory.
+//
+// Key roles:
+// - 'factory': The pool registry, giving the number of pools created so far.
+// - remaining accounts: The pool accounts to list, in seed order.
+//
+// The list_pools flow:
+// - Pool seeds are assigned sequentially, so pool `i` is the config PDA `["config", i]`, or the
+//   `ClPool` PDA `["cl_pool", i]` if it is a concentrated pool. Callers pass one of the two for each of
+//   seeds `start`, `start + 1`, ... as remaining accounts; the other doesn't exist.
+// - Each account is checked against its expected addresses and summarised in a `PoolSummary`.
+//   Pools that have been closed, or were never of the kind passed, are skipped.
+// - The page is returned via return data, at most `MAX_POOLS_PER_PAGE` pools per call.
+// - To find the pool for a given pair instead, derive its canonical PDA
+//   `["pool", lower_mint, higher_mint, fee_tier]` (or `["cl_canonical", ...]` for a concentrated pool)
+//   and read `config` from it.
+
+use anchor_lang::prelude::*;
+
+use crate::{
+    state::{ ClPool, Config, CurveType, Factory },
+    error::AmmError,
+    constants::MAX_POOLS_PER_PAGE,
+};
+
+#[derive(Accounts)]
+pub struct ListPools<'info> {
+    /// The factory PDA.
+    #[account(
+        seeds = [b"factory"],
+        bump = factory.bump
+    )]
+    pub factory: Account<'info, Factory>,
+}
+
+/// A summary of one registered pool.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PoolSummary {
+    /// The pool's config PDA, or its `ClPool` PDA for a concentrated pool.
+    pub pool: Pubkey,
+    pub seed: u64,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub fee: u16,
+    /// Reported as `ConstantProduct` for concentrated pools, which price like one between ticks.
+    pub curve: CurveType,
+    /// True for concentrated liquidity pools.
+    pub concentrated: bool,
+    pub locked: bool,
+}
+
+/// A page of registered pools.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PoolPage {
+    /// Total number of pools in the factory.
+    pub pool_count: u64,
+    /// The open pools among the requested seeds, in seed order.
+    pub pools: Vec<PoolSummary>,
+}
+
+impl<'info> ListPools<'info> {
+    /// Returns the pools starting at seed `start`, read from `accounts`.
+    pub fn list_pools(&self, accounts: &'info [AccountInfo<'info>], start: u64) -> Result<PoolPage> {
+        require!(accounts.len() <= MAX_POOLS_PER_PAGE as usize, AmmError::InvalidAmount);
+        let end = start.checked_add(accounts.len() as u64).ok_or(AmmError::Overflow)?;
+        require!(end <= self.factory.pool_count, AmmError::InvalidPoolSeed);
+
+        let mut pools = Vec::with_capacity(accounts.len());
+        for (seed, info) in (start..end).zip(accounts) {
+            let seed_bytes = seed.to_le_bytes();
+            let (config, _) = Pubkey::find_program_address(&[b"config", seed_bytes.as_ref()], &crate::ID);
+            let (cl_pool, _) = Pubkey::find_program_address(&[b"cl_pool", seed_bytes.as_ref()], &crate::ID);
+            require!(info.key() == config || info.key() == cl_pool, AmmError::InvalidPoolSeed);
+            if info.data_is_empty() {
+                continue;
+            }
+
+            if info.key() == config {
+                let config = Account::<Config>::try_from(info)?;
+                pools.push(PoolSummary {
+                    pool: config.key(),
+                    seed,
+                    mint_x: config.mint_x,
+                    mint_y: config.mint_y,
+                    fee: config.fee,
+                    curve: config.curve,
+                    concentrated: false,
+                    locked: config.locked,
+                });
+            } else {
+                let pool = Account::<ClPool>::try_from(info)?;
+                pools.push(PoolSummary {
+                    pool: pool.key(),
+                    seed,
+                    mint_x: pool.mint_x,
+                    mint_y: pool.mint_y,
+                    fee: pool.fee,
+                    curve: CurveType::ConstantProduct,
+                    concentrated: true,
+                    locked: pool.locked,
+                });
+            }
+        }
+
+        Ok(PoolPage {
+            pool_count: self.factory.pool_count,
+            pools,
+        })
+    }
+}
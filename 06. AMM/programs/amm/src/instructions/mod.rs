@@ -2,8 +2,34 @@ pub mod initialize;
 pub mod deposit;
 pub mod swap;
 pub mod withdraw;
+pub mod update;
+pub mod collect_protocol_fees;
+pub mod observe;
+pub mod swap_route;
+pub mod factory;
+pub mod list_pools;
+pub mod flash_loan;
+pub mod position_info;
+pub mod close_pool;
+pub mod cl_initialize;
+pub mod cl_liquidity;
+pub mod cl_swap;
+pub mod cl_update;
 
 pub use initialize::*;
 pub use deposit::*;
 pub use swap::*;
 pub use withdraw::*;
+pub use update::*;
+pub use collect_protocol_fees::*;
+pub use observe::*;
+pub use swap_route::*;
+pub use factory::*;
+pub use list_pools::*;
+pub use flash_loan::*;
+pub use position_info::*;
+pub use close_pool::*;
+pub use cl_initialize::*;
+pub use cl_liquidity::*;
+pub use cl_swap::*;
+pub use cl_update::*;
@@ -0,0 +1,85 @@
+// This file defines the 'Observe' instruction for the AMM program.
+// It is a read-only view over the pool's TWAP price oracle.
+//
+// Key roles:
+// - 'config': The pool's configuration PDA holding the cumulative prices.
+// - 'vault_x' and 'vault_y': The pool's token vaults, used for the current spot price.
+//
+// The observe flow:
+// - The stored cumulative prices are extended to the current `Clock` timestamp using the current reserves.
+// - The result is returned as an `Observation` (via return data) without modifying any account.
+// - Fails during a flash loan, while the vaults don't hold the pool's real reserves.
+// - Callers take two observations and compute TWAP = (cumulative_2 - cumulative_1) / (timestamp_2 - timestamp_1),
+//   using wrapping subtraction. Prices are Q64.64 fixed point: divide by 2^64 for the decimal price.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{ state::Config, error::AmmError };
+
+#[derive(Accounts)]
+pub struct Observe<'info> {
+    /// The mint for token X.
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    /// The mint for token Y.
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    /// The config PDA for the pool.
+    #[account(
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+    /// The pool's vault for token X.
+    #[account(
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    /// The pool's vault for token Y.
+    #[account(
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// A snapshot of the pool's price oracle.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct Observation {
+    /// Unix timestamp the cumulative prices are valid at.
+    pub timestamp: i64,
+    /// Cumulative price of X in Y (Q64.64) times seconds.
+    pub price_x_cumulative: u128,
+    /// Cumulative price of Y in X (Q64.64) times seconds.
+    pub price_y_cumulative: u128,
+    /// Current LP reserve of token X.
+    pub reserve_x: u64,
+    /// Current LP reserve of token Y.
+    pub reserve_y: u64,
+}
+
+impl<'info> Observe<'info> {
+    /// Returns the oracle's cumulative prices as of the current timestamp.
+    pub fn observe(&self) -> Result<Observation> {
+        // Mid-loan the vaults are drained, and the spot price would skew the TWAP since the last update
+        require!(self.config.flash_loan.is_none(), AmmError::FlashLoanActive);
+        let timestamp = Clock::get()?.unix_timestamp;
+        let (reserve_x, reserve_y) = self.config.lp_reserves(self.vault_x.amount, self.vault_y.amount)?;
+        let (price_x_cumulative, price_y_cumulative) = self.config.cumulative_prices(reserve_x, reserve_y, timestamp);
+
+        Ok(Observation {
+            timestamp,
+            price_x_cumulative,
+            price_y_cumulative,
+            reserve_x,
+            reserve_y,
+        })
+    }
+}
@@ -0,0 +1,151 @@
+// This file defines the 'PositionInfo' instruction for the AMM program.
+// It is a read-only view reporting a liquidity provider's fee income and impermanent loss.
+//
+// Key roles:
+// - 'position': The liquidity provider's position, created on their first deposit.
+// - 'config': The pool's configuration PDA.
+// - 'vault_x', 'vault_y' and 'mint_lp': The pool's current reserves and LP supply.
+//
+// The position_info flow:
+// - The position's share of the LP reserves is its LP over the LP supply.
+// - Fees are the part of that share earned since entry: swap and flash loan fees grow the pool's
+//   liquidity per LP token, so the fee share is (liquidity_per_lp_now - entry) / liquidity_per_lp_now.
+// - Impermanent loss compares the share without fees against simply holding the tokens paid in,
+//   both valued in token Y at the pool's current price. It is negative when the position is ahead.
+// - The result is returned as a `PositionReport` (via return data) without modifying any account.
+// - Fails during a flash loan, while the vaults don't hold the pool's real reserves.
+
+use amm_quote::mul_div;
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{ Mint, TokenAccount, TokenInterface };
+
+use crate::{ state::{ Config, Position }, error::AmmError };
+
+#[derive(Accounts)]
+pub struct PositionInfo<'info> {
+    /// The mint for token X.
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    /// The mint for token Y.
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    /// The config PDA for the pool.
+    #[account(
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+    /// The pool's vault for token X.
+    #[account(
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    /// The pool's vault for token Y.
+    #[account(
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    /// The LP token mint.
+    #[account(
+        seeds = [b"lp", config.key().as_ref()],
+        bump,
+        mint::token_program = token_program,
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+    /// The position to report on.
+    #[account(
+        seeds = [b"position", config.key().as_ref(), position.owner.as_ref()],
+        bump = position.bump,
+    )]
+    pub position: Account<'info, Position>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// A liquidity provider's position, valued at the pool's current reserves.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PositionReport {
+    /// LP tokens held by the position.
+    pub lp_amount: u64,
+    /// The position's share of the LP reserve of token X, fees included.
+    pub amount_x: u64,
+    /// The position's share of the LP reserve of token Y, fees included.
+    pub amount_y: u64,
+    /// Token X earned from fees since entry.
+    pub fees_x: u64,
+    /// Token Y earned from fees since entry.
+    pub fees_y: u64,
+    /// Token X paid in for the position.
+    pub cost_x: u64,
+    /// Token Y paid in for the position.
+    pub cost_y: u64,
+    /// Value in token Y of holding `cost_x` and `cost_y` instead.
+    pub hold_value: u64,
+    /// Value in token Y of the position's share, excluding fees.
+    pub position_value: u64,
+    /// `hold_value - position_value`: what providing liquidity lost against holding, before fees.
+    pub impermanent_loss: i64,
+}
+
+impl<'info> PositionInfo<'info> {
+    /// Returns the position's accrued fees and impermanent loss at the current reserves.
+    pub fn position_info(&self) -> Result<PositionReport> {
+        // Mid-loan the vaults are drained, and the position would be valued at the wrong reserves
+        require!(self.config.flash_loan.is_none(), AmmError::FlashLoanActive);
+        let (reserve_x, reserve_y) = self.config.lp_reserves(self.vault_x.amount, self.vault_y.amount)?;
+        let now = Clock::get()?.unix_timestamp;
+        let liquidity_per_lp = self.config.liquidity_per_lp(reserve_x, reserve_y, self.mint_lp.supply, now)?;
+        position_report(&self.position, reserve_x, reserve_y, self.mint_lp.supply, liquidity_per_lp)
+    }
+}
+
+/// Values `position` against LP reserves `reserve_x` / `reserve_y`, LP supply `lp_supply` and the
+/// pool's current liquidity per LP token.
+pub fn position_report(
+    position: &Position,
+    reserve_x: u64,
+    reserve_y: u64,
+    lp_supply: u64,
+    liquidity_per_lp: u128,
+) -> Result<PositionReport> {
+    require!(position.lp_amount <= lp_supply, AmmError::InvalidAmount);
+    require!(reserve_x > 0 && reserve_y > 0, AmmError::NoLiquidityInPool);
+
+    let lp = position.lp_amount as u128;
+    let amount_x = reserve_x as u128 * lp / lp_supply as u128;
+    let amount_y = reserve_y as u128 * lp / lp_supply as u128;
+
+    // Fees are the share of the position's liquidity gained since entry
+    let growth = liquidity_per_lp.saturating_sub(position.entry_liquidity_per_lp);
+    let (fees_x, fees_y) = if growth > 0 {
+        let fees = |amount: u128| mul_div(amount, growth, liquidity_per_lp).ok_or(AmmError::Overflow);
+        (fees(amount_x)?, fees(amount_y)?)
+    } else {
+        (0, 0)
+    };
+
+    // Value everything in token Y at the pool price
+    let value_in_y = |x: u128, y: u128| x * reserve_y as u128 / reserve_x as u128 + y;
+    let hold_value = value_in_y(position.cost_x as u128, position.cost_y as u128);
+    let position_value = value_in_y(amount_x - fees_x, amount_y - fees_y);
+    let impermanent_loss = i64::try_from(hold_value as i128 - position_value as i128).map_err(|_| AmmError::Overflow)?;
+
+    let to_u64 = |value: u128| u64::try_from(value).map_err(|_| AmmError::Overflow);
+    Ok(PositionReport {
+        lp_amount: position.lp_amount,
+        amount_x: to_u64(amount_x)?,
+        amount_y: to_u64(amount_y)?,
+        fees_x: to_u64(fees_x)?,
+        fees_y: to_u64(fees_y)?,
+        cost_x: position.cost_x,
+        cost_y: position.cost_y,
+        hold_value: to_u64(hold_value)?,
+        position_value: to_u64(position_value)?,
+        impermanent_loss,
+    })
+}
@@ -1,5 +1,6 @@
 // This file defines the 'Swap' instruction for the AMM program.
-// It allows users to swap between the two pool tokens using the constant product formula (x*y=k).
+// It allows users to swap between the two pool tokens on the pool's curve: the constant product
+// formula (x*y=k) or, for pegged pairs, the StableSwap invariant.
 //
 // Key roles:
 // - 'user': The swapper.
@@ -9,16 +10,24 @@
 // The swap flow:
 // - User sends input tokens to the pool vault.
 // - The pool sends output tokens to the user, using the config PDA as authority.
-// - The output amount is calculated using the constant product formula and fee.
+// - The output amount is calculated using the pool's curve and fee, via the `amm_quote` library.
+// - swap_exact_out runs the same math in reverse, rounding the required input up.
+// - The TWAP oracle accumulators in the config are updated before the reserves change.
+// - The protocol's share of the fee is recorded in the config and excluded from LP reserves.
+// - Pools with a dynamic fee are charged the rate implied by recent volatility, and each swap's
+//   price move is then added to the volatility accumulator; `SwapEvent.fee` reports the rate charged.
+// - For Token-2022 mints with a transfer fee, exact-input swaps are priced on the amount the vault
+//   receives and slippage is checked on the amount the user receives; exact-output swaps gross up
+//   both legs so the user receives exactly `amount_out`.
 
+use amm_quote::{ quote_swap_exact_in, quote_swap_exact_out, Curve };
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{Transfer, transfer, Mint, Token, TokenAccount},
+    token_interface::{ transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked },
 };
-use constant_product_curve::ConstantProduct;
 
-use crate::{ state::Config, error::AmmError };
+use crate::{ state::Config, error::AmmError, utils::get_amount_with_transfer_fee };
 
 #[derive(Accounts)]
 pub struct Swap<'info> {
@@ -26,11 +35,14 @@ pub struct Swap<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
     /// The mint for token X.
-    pub mint_x: Account<'info, Mint>,
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
     /// The mint for token Y.
-    pub mint_y: Account<'info, Mint>,
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
     /// The config PDA for the pool.
     #[account(
+        mut,
         has_one = mint_x,
         has_one = mint_y,
         seeds = [b"config", config.seed.to_le_bytes().as_ref()],
@@ -41,112 +53,227 @@ pub struct Swap<'info> {
     #[account(
         mut,
         associated_token::mint = mint_x,
-        associated_token::authority = config
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
     )]
-    pub vault_x: Account<'info, TokenAccount>,
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
     /// The pool's vault for token Y.
     #[account(
         mut,
         associated_token::mint = mint_y,
         associated_token::authority = config,
+        associated_token::token_program = token_program,
     )]
-    pub vault_y: Account<'info, TokenAccount>,
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
     /// The user's token X account.
     #[account(
         mut,
         associated_token::mint = mint_x,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub user_x: Account<'info, TokenAccount>,
+    pub user_x: InterfaceAccount<'info, TokenAccount>,
     /// The user's token Y account.
     #[account(
         mut,
         associated_token::mint = mint_y,
         associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub user_y: Account<'info, TokenAccount>,
+    pub user_y: InterfaceAccount<'info, TokenAccount>,
     /// Standard program accounts required for CPI and ATA creation.
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Swap<'info> {
-    /// Swaps tokens using the constant product formula (x*y=k) and applies the pool fee.
+    /// Swaps tokens on the pool's curve and applies the pool fee.
     /// Transfers input tokens from user to vault, and output tokens from vault to user.
     pub fn swap(&mut self, amount_in: u64, min_amount_out: u64, x_to_y: bool) -> Result<()> {
         require!(!self.config.locked, AmmError::PoolLocked);
+        require!(self.config.flash_loan.is_none(), AmmError::FlashLoanActive);
         require!(amount_in > 0, AmmError::InvalidAmount);
 
-        // Select source/destination tokens
-        let (user_src, user_dst, vault_src, vault_dst) = if x_to_y {
-            (&self.user_x, &self.user_y, &self.vault_x, &self.vault_y)
+        let (reserve_in, reserve_out) = self.reserves(x_to_y)?;
+        let now = Clock::get()?.unix_timestamp;
+
+        // Price only what actually reached the vault
+        let received = self.transfer_in(x_to_y, amount_in)?;
+        let (amount_out, fee_amount) = self.config.get_amount_out(received, reserve_in, reserve_out, now)?;
+        require!(amount_out > 0, AmmError::InvalidAmount);
+
+        // Slippage protection, on what the user actually receives
+        let delivered = self.transfer_out(x_to_y, amount_out)?;
+        require!(delivered >= min_amount_out, AmmError::SlippageExceeded);
+
+        self.settle(x_to_y, received, amount_out, fee_amount, reserve_in, reserve_out)
+    }
+
+    /// Swaps tokens for an exact output amount, charging the pool fee on the input side.
+    /// Reverts if the required input exceeds `max_amount_in`.
+    pub fn swap_exact_out(&mut self, amount_out: u64, max_amount_in: u64, x_to_y: bool) -> Result<()> {
+        require!(!self.config.locked, AmmError::PoolLocked);
+        require!(self.config.flash_loan.is_none(), AmmError::FlashLoanActive);
+        require!(amount_out > 0, AmmError::InvalidAmount);
+
+        let (mint_in, mint_out) = if x_to_y { (&self.mint_x, &self.mint_y) } else { (&self.mint_y, &self.mint_x) };
+        // The vault must send enough for `amount_out` to arrive after any transfer fee
+        let gross_out = get_amount_with_transfer_fee(mint_out, amount_out)?;
+
+        let (reserve_in, reserve_out) = self.reserves(x_to_y)?;
+        let now = Clock::get()?.unix_timestamp;
+        let (curve_in, fee_amount) = self.config.get_amount_in(gross_out, reserve_in, reserve_out, now)?;
+        let amount_in = get_amount_with_transfer_fee(mint_in, curve_in)?;
+
+        // Slippage protection
+        require!(amount_in <= max_amount_in, AmmError::SlippageExceeded);
+
+        let received = self.transfer_in(x_to_y, amount_in)?;
+        require!(received >= curve_in, AmmError::InsufficientFunds);
+        self.transfer_out(x_to_y, gross_out)?;
+
+        self.settle(x_to_y, received, gross_out, fee_amount, reserve_in, reserve_out)
+    }
+
+    /// Returns the (input, output) reserves owned by LPs, excluding unclaimed protocol fees.
+    fn reserves(&self, x_to_y: bool) -> Result<(u64, u64)> {
+        let (reserve_x, reserve_y) = self.config.lp_reserves(self.vault_x.amount, self.vault_y.amount)?;
+        let (reserve_in, reserve_out) = if x_to_y { (reserve_x, reserve_y) } else { (reserve_y, reserve_x) };
+        // Ensure vault has enough liquidity
+        require!(reserve_in > 0 && reserve_out > 0, AmmError::InsufficientLiquidity);
+        Ok((reserve_in, reserve_out))
+    }
+
+    /// Transfers the input tokens from the user to the vault.
+    /// Returns the amount the vault received, net of any transfer fee.
+    fn transfer_in(&mut self, x_to_y: bool, amount: u64) -> Result<u64> {
+        let (user_src, vault_src, mint) = if x_to_y {
+            (&self.user_x, &self.vault_x, &self.mint_x)
         } else {
-            (&self.user_y, &self.user_x, &self.vault_y, &self.vault_x)
+            (&self.user_y, &self.vault_y, &self.mint_y)
         };
 
         // Ensure user has enough tokens
-        require!(user_src.amount >= amount_in, AmmError::InsufficientFunds);
-        // Ensure vault has enough liquidity
-        require!(vault_src.amount > 0 && vault_dst.amount > 0, AmmError::InsufficientLiquidity);
-
-        // Calculate output amount using the constant product curve
-        let (reserve_in, reserve_out) = (vault_src.amount, vault_dst.amount);
-        // Apply fee (assuming fee is in basis points, e.g., 30 = 0.3%)
-        let fee = self.config.fee as u128;
-        let amount_in_with_fee = (amount_in as u128 * (10_000 - fee)) / 10_000;
-        // Calculate output amount using constant product formula: x * y = k
-        // amount_out = (amount_in_with_fee * reserve_out) / (reserve_in + amount_in_with_fee)
-        let numerator = amount_in_with_fee * reserve_out as u128;
-        let denominator = reserve_in as u128 + amount_in_with_fee;
-        let amount_out = (numerator / denominator) as u64;
-
-        // Slippage protection
-        require!(amount_out >= min_amount_out, AmmError::SlippageExceeded);
-        require!(amount_out > 0, AmmError::InvalidAmount);
-        // Ensure vault has enough tokens to fulfill the swap
-        require!(vault_dst.amount >= amount_out, AmmError::InsufficientLiquidity);
+        require!(user_src.amount >= amount, AmmError::InsufficientFunds);
 
-        // Transfer input tokens from user to vault
-        let cpi_program = self.token_program.to_account_info();
-        let transfer_in_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from: user_src.to_account_info(),
+            mint: mint.to_account_info(),
             to: vault_src.to_account_info(),
             authority: self.user.to_account_info(),
         };
-        let cpi_ctx_in = CpiContext::new(cpi_program.clone(), transfer_in_accounts);
-        transfer(cpi_ctx_in, amount_in)?;
+        let ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        transfer_checked(ctx, amount, mint.decimals)?;
+
+        let vault = if x_to_y { &mut self.vault_x } else { &mut self.vault_y };
+        let before = vault.amount;
+        vault.reload()?;
+        vault.amount.checked_sub(before).ok_or(error!(AmmError::Underflow))
+    }
+
+    /// Transfers the output tokens from the vault to the user, using the config PDA as authority.
+    /// Returns the amount the user received, net of any transfer fee.
+    fn transfer_out(&mut self, x_to_y: bool, amount: u64) -> Result<u64> {
+        let (user_dst, vault_dst, mint) = if x_to_y {
+            (&self.user_y, &self.vault_y, &self.mint_y)
+        } else {
+            (&self.user_x, &self.vault_x, &self.mint_x)
+        };
 
-        // Transfer output tokens from vault to user using PDA authority
         let seeds = &[&b"config"[..], &self.config.seed.to_le_bytes(), &[self.config.config_bump]];
         let signer_seeds = &[&seeds[..]];
-        let transfer_out_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from: vault_dst.to_account_info(),
+            mint: mint.to_account_info(),
             to: user_dst.to_account_info(),
             authority: self.config.to_account_info(),
         };
-        let cpi_ctx_out = CpiContext::new_with_signer(cpi_program, transfer_out_accounts, signer_seeds);
-        transfer(cpi_ctx_out, amount_out)?;
+        let ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer_checked(ctx, amount, mint.decimals)?;
+
+        let user = if x_to_y { &mut self.user_y } else { &mut self.user_x };
+        let before = user.amount;
+        user.reload()?;
+        user.amount.checked_sub(before).ok_or(error!(AmmError::Underflow))
+    }
+
+    /// Records a completed swap: updates the oracle, sets aside the protocol fee and emits a `SwapEvent`.
+    fn settle(
+        &mut self,
+        x_to_y: bool,
+        amount_in: u64,
+        amount_out: u64,
+        fee_amount: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+    ) -> Result<()> {
+        // Accumulate the TWAP oracle with the reserves from before the swap
+        let now = Clock::get()?.unix_timestamp;
+        let (reserve_x, reserve_y) = if x_to_y { (reserve_in, reserve_out) } else { (reserve_out, reserve_in) };
+        self.config.update_oracle(reserve_x, reserve_y, now);
+
+        // Set aside the protocol's share of the fee, charged in the input token
+        let protocol_fee = self.config.accrue_protocol_fee(x_to_y, fee_amount)?;
+        let reserve_in_after = reserve_in
+            .checked_add(amount_in)
+            .and_then(|v| v.checked_sub(protocol_fee))
+            .ok_or(AmmError::Overflow)?;
+        let (reserve_x_after, reserve_y_after) = if x_to_y {
+            (reserve_in_after, reserve_out - amount_out)
+        } else {
+            (reserve_out - amount_out, reserve_in_after)
+        };
+
+        // The rate this swap was priced at, before its own price move feeds the dynamic fee
+        let fee = self.config.effective_fee(now);
+        self.config.record_volatility((reserve_x, reserve_y), (reserve_x_after, reserve_y_after), now);
 
         // Emit swap event for tracking
         emit!(SwapEvent {
+            pool: self.config.key(),
             user: self.user.key(),
             amount_in,
             amount_out,
+            fee_amount,
+            fee,
             x_to_y,
-            reserve_x: if x_to_y { vault_src.amount + amount_in } else { vault_dst.amount - amount_out },
-            reserve_y: if x_to_y { vault_dst.amount - amount_out } else { vault_src.amount + amount_in },
+            reserve_x: reserve_x_after,
+            reserve_y: reserve_y_after,
         });
 
         Ok(())
     }
 }
 
+/// Calculates the output for an exact input using the constant product formula: x * y = k.
+/// The fee (in basis points, e.g., 30 = 0.3%) is taken from the input and rounding favours the pool.
+/// Returns `(amount_out, fee_amount)`.
+pub fn get_amount_out(amount_in: u64, reserve_in: u64, reserve_out: u64, fee: u16) -> Result<(u64, u64)> {
+    let quote = quote_swap_exact_in(Curve::ConstantProduct, amount_in, reserve_in, reserve_out, fee)
+        .map_err(AmmError::from)?;
+    Ok((quote.amount_out, quote.fee_amount))
+}
+
+/// Calculates the input required for an exact output using the constant product formula: x * y = k.
+/// Both the curve input and the fee gross-up are rounded up, so the pool never loses to rounding.
+/// Returns `(amount_in, fee_amount)`.
+pub fn get_amount_in(amount_out: u64, reserve_in: u64, reserve_out: u64, fee: u16) -> Result<(u64, u64)> {
+    let quote = quote_swap_exact_out(Curve::ConstantProduct, amount_out, reserve_in, reserve_out, fee)
+        .map_err(AmmError::from)?;
+    Ok((quote.amount_in, quote.fee_amount))
+}
+
 #[event]
 pub struct SwapEvent {
+    pub pool: Pubkey,
     pub user: Pubkey,
     pub amount_in: u64,
     pub amount_out: u64,
+    /// Fee charged on the input, including the protocol's share.
+    pub fee_amount: u64,
+    /// Fee rate the swap was charged, in basis points. Varies per swap for dynamic fee pools.
+    pub fee: u16,
     pub x_to_y: bool,
     pub reserve_x: u64,
     pub reserve_y: u64,
@@ -0,0 +1,250 @@
+// This file defines the 'SwapRoute' instruction for the AMM program.
+// It chains exact-input swaps across several pools in a single instruction, for pairs with no direct pool.
+//
+// Key roles:
+// - 'user': The swapper.
+// - 'mint_in': The mint of the route's input token.
+// - 'user_src': The user's token account for the first input token.
+// - 'user_dst': The user's token account for the final output token.
+// - remaining accounts: The ordered route, four accounts per hop:
+//   [config (mut), vault_in (mut), vault_out (mut), mint_out], where vault_in/vault_out are the
+//   pool's vaults for the hop's input and output tokens and mint_out is the hop's output mint.
+//
+// The swap route flow:
+// - User sends the input tokens to the first pool's vault.
+// - Each hop is priced with its pool's curve and fee on the amount its vault
+//   actually received, exactly like 'swap'.
+// - A hop's output is moved straight into the next pool's input vault, signed by the previous config PDA.
+// - The final output goes to the user, subject to a single overall `min_amount_out` checked
+//   against what the user actually receives.
+// - One `SwapEvent` is emitted per hop, plus an aggregate `SwapRouteEvent`.
+
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id,
+    token_interface::{ transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked },
+};
+
+use crate::{
+    state::Config,
+    error::AmmError,
+    constants::MAX_ROUTE_HOPS,
+    instructions::SwapEvent,
+};
+
+/// Number of remaining accounts describing each hop: config, vault_in, vault_out, mint_out.
+pub const ACCOUNTS_PER_HOP: usize = 4;
+
+#[derive(Accounts)]
+pub struct SwapRoute<'info> {
+    /// The user performing the swap.
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// The mint of the route's input token.
+    #[account(mint::token_program = token_program)]
+    pub mint_in: InterfaceAccount<'info, Mint>,
+    /// The user's token account for the route's input token.
+    #[account(
+        mut,
+        token::mint = mint_in,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub user_src: InterfaceAccount<'info, TokenAccount>,
+    /// The user's token account for the route's output token.
+    #[account(
+        mut,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub user_dst: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> SwapRoute<'info> {
+    /// Swaps `amount_in` along the route given in `remaining` and checks the final output
+    /// against `min_amount_out`.
+    pub fn swap_route(
+        &mut self,
+        remaining: &'info [AccountInfo<'info>],
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<()> {
+        require!(amount_in > 0, AmmError::InvalidAmount);
+        require!(
+            !remaining.is_empty() && remaining.chunks_exact(ACCOUNTS_PER_HOP).remainder().is_empty(),
+            AmmError::InvalidRoute
+        );
+        let hops = remaining.len() / ACCOUNTS_PER_HOP;
+        require!(hops <= MAX_ROUTE_HOPS as usize, AmmError::InvalidRoute);
+
+        let now = Clock::get()?.unix_timestamp;
+        let mut amount = amount_in;
+        let mut mint_in = self.mint_in.key();
+        let token_program = self.token_program.key();
+        // The previous hop's output, still held in that pool's vault: (config, vault_out, mint_out)
+        let mut pending: Option<(Account<'info, Config>, &'info AccountInfo<'info>, InterfaceAccount<'info, Mint>)> = None;
+
+        for hop in remaining.chunks(ACCOUNTS_PER_HOP) {
+            let (config_info, vault_in_info, vault_out_info, mint_out_info) = (&hop[0], &hop[1], &hop[2], &hop[3]);
+
+            let mut config = Account::<Config>::try_from(config_info)?;
+            require!(config_info.is_writable, AmmError::InvalidRoute);
+            require!(!config.locked, AmmError::PoolLocked);
+            require!(config.flash_loan.is_none(), AmmError::FlashLoanActive);
+            if let Some((previous, _, _)) = &pending {
+                require_keys_neq!(previous.key(), config.key(), AmmError::InvalidRoute);
+            }
+
+            // The hop's direction follows from which of the pool's mints it receives
+            let x_to_y = if mint_in == config.mint_x {
+                true
+            } else if mint_in == config.mint_y {
+                false
+            } else {
+                return err!(AmmError::InvalidToken);
+            };
+            let mint_out = if x_to_y { config.mint_y } else { config.mint_x };
+
+            // Only the pool's own vaults may be used
+            require_keys_eq!(mint_out_info.key(), mint_out, AmmError::InvalidToken);
+            require_keys_eq!(
+                vault_in_info.key(),
+                get_associated_token_address_with_program_id(&config.key(), &mint_in, &token_program),
+                AmmError::InvalidToken
+            );
+            require_keys_eq!(
+                vault_out_info.key(),
+                get_associated_token_address_with_program_id(&config.key(), &mint_out, &token_program),
+                AmmError::InvalidToken
+            );
+            let vault_in = InterfaceAccount::<TokenAccount>::try_from(vault_in_info)?;
+            let vault_out = InterfaceAccount::<TokenAccount>::try_from(vault_out_info)?;
+            let hop_mint_out = InterfaceAccount::<Mint>::try_from(mint_out_info)?;
+
+            // Price the hop against the LP reserves, excluding unclaimed protocol fees
+            let (vault_x, vault_y) = if x_to_y {
+                (vault_in.amount, vault_out.amount)
+            } else {
+                (vault_out.amount, vault_in.amount)
+            };
+            let (reserve_x, reserve_y) = config.lp_reserves(vault_x, vault_y)?;
+            let (reserve_in, reserve_out) = if x_to_y { (reserve_x, reserve_y) } else { (reserve_y, reserve_x) };
+            require!(reserve_in > 0 && reserve_out > 0, AmmError::InsufficientLiquidity);
+
+            // Move this hop's input into its vault, then price only what arrived
+            match pending.take() {
+                None => self.transfer_from_user(vault_in_info, amount)?,
+                Some((previous, previous_vault_out, previous_mint_out)) => {
+                    self.transfer_from_vault(&previous, previous_vault_out, &previous_mint_out, vault_in_info, amount)?
+                }
+            }
+            let received = InterfaceAccount::<TokenAccount>::try_from(vault_in_info)?
+                .amount
+                .checked_sub(vault_in.amount)
+                .ok_or(AmmError::Underflow)?;
+
+            let (amount_out, fee_amount) = config.get_amount_out(received, reserve_in, reserve_out, now)?;
+            require!(amount_out > 0, AmmError::InvalidAmount);
+
+            config.update_oracle(reserve_x, reserve_y, now);
+            let protocol_fee = config.accrue_protocol_fee(x_to_y, fee_amount)?;
+            let reserve_in_after = reserve_in
+                .checked_add(received)
+                .and_then(|v| v.checked_sub(protocol_fee))
+                .ok_or(AmmError::Overflow)?;
+            let (reserve_x_after, reserve_y_after) = if x_to_y {
+                (reserve_in_after, reserve_out - amount_out)
+            } else {
+                (reserve_out - amount_out, reserve_in_after)
+            };
+            let fee = config.effective_fee(now);
+            config.record_volatility((reserve_x, reserve_y), (reserve_x_after, reserve_y_after), now);
+            config.exit(&crate::ID)?;
+
+            emit!(SwapEvent {
+                pool: config.key(),
+                user: self.user.key(),
+                amount_in: received,
+                amount_out,
+                fee_amount,
+                fee,
+                x_to_y,
+                reserve_x: reserve_x_after,
+                reserve_y: reserve_y_after,
+            });
+
+            pending = Some((config, vault_out_info, hop_mint_out));
+            amount = amount_out;
+            mint_in = mint_out;
+        }
+
+        require_keys_eq!(self.user_dst.mint, mint_in, AmmError::InvalidToken);
+
+        let (last, last_vault_out, last_mint_out) = pending.ok_or(AmmError::InvalidRoute)?;
+        let user_dst = self.user_dst.to_account_info();
+        let before = self.user_dst.amount;
+        self.transfer_from_vault(&last, last_vault_out, &last_mint_out, &user_dst, amount)?;
+        self.user_dst.reload()?;
+        let amount_out = self.user_dst.amount.checked_sub(before).ok_or(AmmError::Underflow)?;
+
+        // Slippage protection on the route as a whole
+        require!(amount_out >= min_amount_out, AmmError::SlippageExceeded);
+
+        emit!(SwapRouteEvent {
+            user: self.user.key(),
+            mint_in: self.mint_in.key(),
+            mint_out: mint_in,
+            amount_in,
+            amount_out,
+            hops: hops as u8,
+        });
+
+        Ok(())
+    }
+
+    /// Transfers the route's input from the user to the first pool's vault.
+    fn transfer_from_user(&self, to: &AccountInfo<'info>, amount: u64) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: self.user_src.to_account_info(),
+            mint: self.mint_in.to_account_info(),
+            to: to.clone(),
+            authority: self.user.to_account_info(),
+        };
+        let ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        transfer_checked(ctx, amount, self.mint_in.decimals)
+    }
+
+    /// Transfers a hop's output out of its vault, signed by that pool's config PDA.
+    fn transfer_from_vault(
+        &self,
+        config: &Account<'info, Config>,
+        from: &AccountInfo<'info>,
+        mint: &InterfaceAccount<'info, Mint>,
+        to: &AccountInfo<'info>,
+        amount: u64,
+    ) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: from.clone(),
+            mint: mint.to_account_info(),
+            to: to.clone(),
+            authority: config.to_account_info(),
+        };
+
+        let seeds = &[&b"config"[..], &config.seed.to_le_bytes(), &[config.config_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+}
+
+#[event]
+pub struct SwapRouteEvent {
+    pub user: Pubkey,
+    pub mint_in: Pubkey,
+    pub mint_out: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub hops: u8,
+}
@@ -0,0 +1,268 @@
+// This file defines the 'Update' instruction context for the AMM program.
+// It lets the pool authority administer a pool after it has been initialized.
+//
+// Key roles:
+// - 'authority': Must match `config.authority`.
+// - 'config': The pool's configuration PDA being updated.
+//
+// The update flow:
+// - lock / unlock: Pauses or resumes deposit, swap and withdraw on the pool.
+// - update_fee: Changes the swap fee (in basis points).
+// - update_protocol_fee: Changes the protocol's share of the swap fee (in basis points of the fee).
+// - set_treasury: Changes the wallet whose token accounts receive the protocol fees.
+// - set_dynamic_fee / disable_dynamic_fee: Switches the pool to a fee that follows recent volatility
+//   between two bounds, or back to the static `fee`.
+// - ramp_amp / stop_ramp_amp: Moves a StableSwap pool's amplification coefficient linearly
+//   to a new value over time, or freezes it at its current value.
+// - transfer_authority: Hands the pool to a new authority, or renounces it with `None`.
+// Pools whose authority is `None` are permanently immutable.
+// Every change emits an event carrying the pool key and seed, for indexers.
+
+use anchor_lang::prelude::*;
+
+use crate::{
+    state::{ Config, CurveType, DynamicFee },
+    error::AmmError,
+    constants::{ MIN_AMP, MAX_AMP, MIN_RAMP_DURATION, MAX_AMP_CHANGE },
+};
+
+#[derive(Accounts)]
+pub struct Update<'info> {
+    /// The pool's update authority.
+    pub authority: Signer<'info>,
+    /// The config PDA for the pool.
+    #[account(
+        mut,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> Update<'info> {
+    /// Locks the pool, rejecting deposits, swaps and withdrawals until unlocked.
+    pub fn lock(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        self.config.locked = true;
+        self.emit_lock_updated();
+        Ok(())
+    }
+
+    /// Unlocks a previously locked pool.
+    pub fn unlock(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        self.config.locked = false;
+        self.emit_lock_updated();
+        Ok(())
+    }
+
+    /// Updates the swap fee, expressed in basis points (30 = 0.3%).
+    pub fn update_fee(&mut self, fee: u16) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(fee <= 10_000, AmmError::InvalidFee);
+
+        emit!(FeeUpdated {
+            pool: self.config.key(),
+            seed: self.config.seed,
+            old_fee: self.config.fee,
+            new_fee: fee,
+        });
+        self.config.fee = fee;
+        Ok(())
+    }
+
+    /// Updates the protocol's share of each swap fee, in basis points of the fee (2_000 = 20%).
+    pub fn update_protocol_fee(&mut self, protocol_fee: u16) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(protocol_fee <= 10_000, AmmError::InvalidFee);
+
+        emit!(ProtocolFeeUpdated {
+            pool: self.config.key(),
+            seed: self.config.seed,
+            old_protocol_fee: self.config.protocol_fee,
+            new_protocol_fee: protocol_fee,
+        });
+        self.config.protocol_fee = protocol_fee;
+        Ok(())
+    }
+
+    /// Sets the wallet whose token accounts `collect_protocol_fees` pays out to.
+    pub fn set_treasury(&mut self, treasury: Pubkey) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+
+        emit!(TreasuryUpdated {
+            pool: self.config.key(),
+            seed: self.config.seed,
+            old_treasury: self.config.treasury,
+            new_treasury: treasury,
+        });
+        self.config.treasury = treasury;
+        Ok(())
+    }
+
+    /// Enables the dynamic fee, or replaces its parameters, resetting the volatility accumulator.
+    /// Swaps are then charged between `min_fee` and `max_fee` basis points, reaching `max_fee` once
+    /// recent price moves add up to `max_volatility` basis points; they decay over `decay_period` seconds.
+    pub fn set_dynamic_fee(&mut self, min_fee: u16, max_fee: u16, max_volatility: u64, decay_period: i64) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(min_fee <= max_fee && max_fee <= 10_000, AmmError::InvalidFee);
+        require!(max_volatility > 0 && decay_period > 0, AmmError::InvalidDynamicFee);
+
+        self.config.dynamic_fee = Some(DynamicFee {
+            min_fee,
+            max_fee,
+            max_volatility,
+            decay_period,
+            volatility: 0,
+            last_update_ts: Clock::get()?.unix_timestamp,
+        });
+        self.emit_dynamic_fee_updated();
+        Ok(())
+    }
+
+    /// Disables the dynamic fee; swaps are charged the static `fee` again.
+    pub fn disable_dynamic_fee(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        self.config.dynamic_fee = None;
+        self.emit_dynamic_fee_updated();
+        Ok(())
+    }
+
+    /// Starts ramping a StableSwap pool's amplification coefficient from its current value
+    /// to `target_amp`, reached at `stop_ts`. The ramp must last at least `MIN_RAMP_DURATION`
+    /// and may not move the coefficient by more than `MAX_AMP_CHANGE` times.
+    pub fn ramp_amp(&mut self, target_amp: u64, stop_ts: i64) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(self.config.curve == CurveType::StableSwap, AmmError::InvalidCurve);
+        require!((MIN_AMP..=MAX_AMP).contains(&target_amp), AmmError::InvalidAmp);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(stop_ts >= now.saturating_add(MIN_RAMP_DURATION), AmmError::InvalidRamp);
+
+        let current_amp = self.config.amp(now);
+        require!(
+            target_amp <= current_amp * MAX_AMP_CHANGE && target_amp * MAX_AMP_CHANGE >= current_amp,
+            AmmError::InvalidRamp
+        );
+
+        self.config.amp_initial = current_amp;
+        self.config.amp_target = target_amp;
+        self.config.amp_ramp_start_ts = now;
+        self.config.amp_ramp_stop_ts = stop_ts;
+        self.emit_amp_ramp_updated();
+        Ok(())
+    }
+
+    /// Stops an in-progress amplification ramp, freezing the coefficient at its current value.
+    pub fn stop_ramp_amp(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(self.config.curve == CurveType::StableSwap, AmmError::InvalidCurve);
+
+        let now = Clock::get()?.unix_timestamp;
+        let current_amp = self.config.amp(now);
+        self.config.amp_initial = current_amp;
+        self.config.amp_target = current_amp;
+        self.config.amp_ramp_start_ts = now;
+        self.config.amp_ramp_stop_ts = now;
+        self.emit_amp_ramp_updated();
+        Ok(())
+    }
+
+    /// Transfers the update authority. Passing `None` renounces it for good.
+    pub fn transfer_authority(&mut self, new_authority: Option<Pubkey>) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+
+        emit!(AuthorityTransferred {
+            pool: self.config.key(),
+            seed: self.config.seed,
+            old_authority: self.config.authority,
+            new_authority,
+        });
+        self.config.authority = new_authority;
+        Ok(())
+    }
+
+    fn emit_lock_updated(&self) {
+        emit!(PoolLockUpdated {
+            pool: self.config.key(),
+            seed: self.config.seed,
+            locked: self.config.locked,
+        });
+    }
+
+    fn emit_dynamic_fee_updated(&self) {
+        emit!(DynamicFeeUpdated {
+            pool: self.config.key(),
+            seed: self.config.seed,
+            dynamic_fee: self.config.dynamic_fee,
+        });
+    }
+
+    fn emit_amp_ramp_updated(&self) {
+        emit!(AmpRampUpdated {
+            pool: self.config.key(),
+            seed: self.config.seed,
+            amp_initial: self.config.amp_initial,
+            amp_target: self.config.amp_target,
+            ramp_start_ts: self.config.amp_ramp_start_ts,
+            ramp_stop_ts: self.config.amp_ramp_stop_ts,
+        });
+    }
+}
+
+#[event]
+pub struct PoolLockUpdated {
+    pub pool: Pubkey,
+    pub seed: u64,
+    pub locked: bool,
+}
+
+#[event]
+pub struct FeeUpdated {
+    pub pool: Pubkey,
+    pub seed: u64,
+    pub old_fee: u16,
+    pub new_fee: u16,
+}
+
+#[event]
+pub struct ProtocolFeeUpdated {
+    pub pool: Pubkey,
+    pub seed: u64,
+    pub old_protocol_fee: u16,
+    pub new_protocol_fee: u16,
+}
+
+#[event]
+pub struct TreasuryUpdated {
+    pub pool: Pubkey,
+    pub seed: u64,
+    pub old_treasury: Pubkey,
+    pub new_treasury: Pubkey,
+}
+
+#[event]
+pub struct DynamicFeeUpdated {
+    pub pool: Pubkey,
+    pub seed: u64,
+    /// The new dynamic fee parameters, or `None` if it was disabled.
+    pub dynamic_fee: Option<DynamicFee>,
+}
+
+#[event]
+pub struct AmpRampUpdated {
+    pub pool: Pubkey,
+    pub seed: u64,
+    pub amp_initial: u64,
+    pub amp_target: u64,
+    pub ramp_start_ts: i64,
+    pub ramp_stop_ts: i64,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub pool: Pubkey,
+    pub seed: u64,
+    pub old_authority: Option<Pubkey>,
+    pub new_authority: Option<Pubkey>,
+}
@@ -6,19 +6,31 @@
 // - 'vault_x' and 'vault_y': The pool's token vaults.
 // - 'mint_lp': The LP token mint.
 // - 'user_lp': The user's LP token account.
+// - 'position': The user's position in the pool, if they have one.
 //
 // The withdraw flow:
 // - User burns LP tokens.
 // - The program transfers the user's proportional share of both tokens from the vaults to the user.
 // - Proportional math ensures fair share for all liquidity providers.
+// - A `LiquidityRemoved` event records the amounts, LP burned and the pool state afterwards.
+// - If the user's position is passed, the burned LP and its share of the cost basis are taken off it.
+//
+// The single-sided withdraw flow (zap out):
+// - User burns LP tokens for a proportional share of both tokens.
+// - The unwanted side is swapped back through the curve (paying the pool fee) inside the program.
+// - The user receives only the requested token, subject to `min_out`.
+//
+// Slippage bounds are checked against what the user actually receives, so Token-2022 transfer
+// fees are accounted for.
 
+use amm_quote::quote_withdraw;
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{Burn, burn, Transfer, transfer, Mint, Token, TokenAccount},
+    token_interface::{ burn, transfer_checked, Burn, Mint, TokenAccount, TokenInterface, TransferChecked },
 };
 
-use crate::{ state::Config, error::AmmError };
+use crate::{ state::{ Config, Position }, error::AmmError };
 
 #[derive(Accounts)]
 pub struct Withdraw<'info> {
@@ -26,11 +38,14 @@ pub struct Withdraw<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
     /// The mint for token X.
-    pub mint_x: Account<'info, Mint>,
+    #[account(mint::token_program = token_program)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
     /// The mint for token Y.
-    pub mint_y: Account<'info, Mint>,
+    #[account(mint::token_program = token_program)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
     /// The config PDA for the pool.
     #[account(
+        mut,
         has_one = mint_x,
         has_one = mint_y,
         seeds = [b"config", config.seed.to_le_bytes().as_ref()],
@@ -41,16 +56,18 @@ pub struct Withdraw<'info> {
     #[account(
         mut,
         associated_token::mint = mint_x,
-        associated_token::authority = config
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
     )]
-    pub vault_x: Account<'info, TokenAccount>,
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
     /// The pool's vault for token Y.
     #[account(
         mut,
         associated_token::mint = mint_y,
         associated_token::authority = config,
+        associated_token::token_program = token_program,
     )]
-    pub vault_y: Account<'info, TokenAccount>,
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
     /// The LP token mint (PDA, authority = config).
     #[account(
         mut,
@@ -58,30 +75,41 @@ pub struct Withdraw<'info> {
         bump,
         mint::decimals = 6,
         mint::authority = config,
+        mint::token_program = token_program,
     )]
-    pub mint_lp: Account<'info, Mint>,
+    pub mint_lp: InterfaceAccount<'info, Mint>,
     /// The user's token X account.
     #[account(
         mut,
         associated_token::mint = mint_x,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub user_x: Account<'info, TokenAccount>,
+    pub user_x: InterfaceAccount<'info, TokenAccount>,
     /// The user's token Y account.
     #[account(
         mut,
         associated_token::mint = mint_y,
         associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub user_y: Account<'info, TokenAccount>,
+    pub user_y: InterfaceAccount<'info, TokenAccount>,
     /// The user's LP token account.
     #[account(
         mut,
         associated_token::mint = mint_lp,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub user_lp: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+    pub user_lp: InterfaceAccount<'info, TokenAccount>,
+    /// The user's position in the pool. Optional, so LP received by transfer can still be withdrawn.
+    #[account(
+        mut,
+        seeds = [b"position", config.key().as_ref(), user.key().as_ref()],
+        bump = position.bump,
+    )]
+    pub position: Option<Account<'info, Position>>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
@@ -90,61 +118,178 @@ impl<'info> Withdraw<'info> {
     /// Burns the user's LP tokens and transfers their proportional share of vault_x and vault_y to them.
     /// Checks for pool lock and sufficient LP tokens.
     pub fn withdraw(&mut self, lp_amount: u64, min_x: u64, min_y: u64) -> Result<()> {
+        let (x_out, y_out, reserve_x, reserve_y) = self.withdraw_amounts(lp_amount)?;
+
+        require!(x_out > 0 && y_out > 0, AmmError::InvalidAmount);
+        require!(reserve_x >= x_out, AmmError::InsufficientLiquidity);
+        require!(reserve_y >= y_out, AmmError::InsufficientLiquidity);
+
+        // Accumulate the TWAP oracle with the reserves from before the withdrawal
+        self.config.update_oracle(reserve_x, reserve_y, Clock::get()?.unix_timestamp);
+
+        // Burn LP tokens from user
+        self.burn_lp_tokens(lp_amount)?;
+
+        // Transfer X and Y from vault to user
+        let received_x = self.withdraw_tokens(true, x_out)?;
+        let received_y = self.withdraw_tokens(false, y_out)?;
+
+        // Slippage protection, on what the user actually receives
+        require!(received_x >= min_x && received_y >= min_y, AmmError::SlippageExceeded);
+
+        self.emit_liquidity_removed(lp_amount, x_out, y_out)
+    }
+
+    /// Burns the user's LP tokens and pays out only token X (`is_x`) or only token Y.
+    /// The proportional share of the other token is swapped back through the curve at the pool fee.
+    pub fn withdraw_single(&mut self, lp_amount: u64, is_x: bool, min_out: u64) -> Result<()> {
+        let (x_out, y_out, reserve_x, reserve_y) = self.withdraw_amounts(lp_amount)?;
+
+        // Accumulate the TWAP oracle with the reserves from before the withdrawal
+        let now = Clock::get()?.unix_timestamp;
+        self.config.update_oracle(reserve_x, reserve_y, now);
+
+        let (keep_out, swap_in, reserve_keep, reserve_swap) = if is_x {
+            (x_out, y_out, reserve_x, reserve_y)
+        } else {
+            (y_out, x_out, reserve_y, reserve_x)
+        };
+
+        // Swap the unwanted side against the reserves left after the proportional withdrawal
+        let (pool_in, pool_out) = (reserve_swap - swap_in, reserve_keep - keep_out);
+        let (swap_out, fee_amount) = if swap_in > 0 {
+            require!(pool_in > 0 && pool_out > 0, AmmError::InsufficientLiquidity);
+            self.config.get_amount_out(swap_in, pool_in, pool_out, now)?
+        } else {
+            (0, 0)
+        };
+        let protocol_fee = self.config.accrue_protocol_fee(!is_x, fee_amount)?;
+
+        // The internal swap moves the price like any other, feeding the dynamic fee
+        if swap_in > 0 {
+            let swap_after = reserve_swap.checked_sub(protocol_fee).ok_or(AmmError::Underflow)?;
+            let keep_after = pool_out.checked_sub(swap_out).ok_or(AmmError::Underflow)?;
+            let (before, after) = if is_x {
+                ((pool_out, pool_in), (keep_after, swap_after))
+            } else {
+                ((pool_in, pool_out), (swap_after, keep_after))
+            };
+            self.config.record_volatility(before, after, now);
+        }
+
+        let amount_out = keep_out.checked_add(swap_out).ok_or(AmmError::Overflow)?;
+
+        require!(amount_out > 0, AmmError::InvalidAmount);
+        require!(reserve_keep >= amount_out, AmmError::InsufficientLiquidity);
+
+        self.burn_lp_tokens(lp_amount)?;
+        let received = self.withdraw_tokens(is_x, amount_out)?;
+
+        // Slippage protection, on what the user actually receives
+        require!(received >= min_out, AmmError::SlippageExceeded);
+
+        let (amount_x, amount_y) = if is_x { (amount_out, 0) } else { (0, amount_out) };
+        self.emit_liquidity_removed(lp_amount, amount_x, amount_y)
+    }
+
+    /// Emits a `LiquidityRemoved` event with the pool's LP reserves and LP supply after the withdrawal.
+    fn emit_liquidity_removed(&mut self, lp_amount: u64, amount_x: u64, amount_y: u64) -> Result<()> {
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        self.mint_lp.reload()?;
+        let (reserve_x, reserve_y) = self.config.lp_reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        emit!(LiquidityRemoved {
+            pool: self.config.key(),
+            seed: self.config.seed,
+            user: self.user.key(),
+            amount_x,
+            amount_y,
+            lp_amount,
+            reserve_x,
+            reserve_y,
+            lp_supply: self.mint_lp.supply,
+        });
+
+        Ok(())
+    }
+
+    /// Calculates the proportional amounts owed for `lp_amount` from the LP-owned reserves,
+    /// leaving unclaimed protocol fees in the vaults.
+    /// Returns `(x_out, y_out, reserve_x, reserve_y)`.
+    fn withdraw_amounts(&self, lp_amount: u64) -> Result<(u64, u64, u64, u64)> {
         // Check if pool is locked
         require!(!self.config.locked, AmmError::PoolLocked);
+        require!(self.config.flash_loan.is_none(), AmmError::FlashLoanActive);
         require!(lp_amount > 0, AmmError::InvalidAmount);
         require!(self.user_lp.amount >= lp_amount, AmmError::InsufficientFunds);
         require!(self.mint_lp.supply > 0, AmmError::NoLiquidityInPool);
 
-        // Calculate proportional amounts to withdraw
-        let total_lp = self.mint_lp.supply;
-        let x_out = (self.vault_x.amount as u128)
-            .checked_mul(lp_amount as u128)
-            .unwrap()
-            .checked_div(total_lp as u128)
-            .unwrap() as u64;
-        let y_out = (self.vault_y.amount as u128)
-            .checked_mul(lp_amount as u128)
-            .unwrap()
-            .checked_div(total_lp as u128)
-            .unwrap() as u64;
-
-        // Slippage protection (optional, but recommended)
-        require!(x_out >= min_x && y_out >= min_y, AmmError::SlippageExceeded);
-        require!(x_out > 0 && y_out > 0, AmmError::InvalidAmount);
-        require!(self.vault_x.amount >= x_out, AmmError::InsufficientLiquidity);
-        require!(self.vault_y.amount >= y_out, AmmError::InsufficientLiquidity);
+        let (reserve_x, reserve_y) = self.config.lp_reserves(self.vault_x.amount, self.vault_y.amount)?;
+        let amounts = quote_withdraw(lp_amount, reserve_x, reserve_y, self.mint_lp.supply).map_err(AmmError::from)?;
+
+        Ok((amounts.amount_x, amounts.amount_y, reserve_x, reserve_y))
+    }
+
+    /// Burns LP tokens from the user and takes them off the user's position.
+    pub fn burn_lp_tokens(&mut self, amount: u64) -> Result<()> {
+        if let Some(position) = self.position.as_mut() {
+            position.debit(amount);
+        }
 
-        // Burn LP tokens from user
         let cpi_program = self.token_program.to_account_info();
-        let burn_accounts = Burn {
+
+        let cpi_accounts = Burn {
             mint: self.mint_lp.to_account_info(),
             from: self.user_lp.to_account_info(),
             authority: self.user.to_account_info(),
         };
-        let burn_ctx = CpiContext::new(cpi_program.clone(), burn_accounts);
-        burn(burn_ctx, lp_amount)?;
 
-        // Transfer X from vault to user
-        let seeds = &[&b"config"[..], &self.config.seed.to_le_bytes(), &[self.config.config_bump]];
-        let signer_seeds = &[&seeds[..]];
-        let transfer_x_accounts = Transfer {
-            from: self.vault_x.to_account_info(),
-            to: self.user_x.to_account_info(),
-            authority: self.config.to_account_info(),
+        let ctx = CpiContext::new(cpi_program, cpi_accounts);
+        burn(ctx, amount)
+    }
+
+    /// Transfers tokens from the pool vaults to the user, using the config PDA as authority.
+    /// Returns the amount the user received, net of any transfer fee.
+    pub fn withdraw_tokens(&mut self, is_x: bool, amount: u64) -> Result<u64> {
+        let (from, to, mint, decimals) = match is_x {
+            true => (self.vault_x.to_account_info(), self.user_x.to_account_info(), self.mint_x.to_account_info(), self.mint_x.decimals),
+            false => (self.vault_y.to_account_info(), self.user_y.to_account_info(), self.mint_y.to_account_info(), self.mint_y.decimals),
         };
-        let transfer_x_ctx = CpiContext::new_with_signer(cpi_program.clone(), transfer_x_accounts, signer_seeds);
-        transfer(transfer_x_ctx, x_out)?;
+        let cpi_program = self.token_program.to_account_info();
 
-        // Transfer Y from vault to user
-        let transfer_y_accounts = Transfer {
-            from: self.vault_y.to_account_info(),
-            to: self.user_y.to_account_info(),
+        let cpi_accounts = TransferChecked {
+            from,
+            mint,
+            to,
             authority: self.config.to_account_info(),
         };
-        let transfer_y_ctx = CpiContext::new_with_signer(cpi_program, transfer_y_accounts, signer_seeds);
-        transfer(transfer_y_ctx, y_out)?;
 
-        Ok(())
+        let seeds = &[&b"config"[..], &self.config.seed.to_le_bytes(), &[self.config.config_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        transfer_checked(ctx, amount, decimals)?;
+
+        let user = if is_x { &mut self.user_x } else { &mut self.user_y };
+        let before = user.amount;
+        user.reload()?;
+        user.amount.checked_sub(before).ok_or(error!(AmmError::Underflow))
     }
-} 
\ No newline at end of file
+}
+
+#[event]
+pub struct LiquidityRemoved {
+    pub pool: Pubkey,
+    pub seed: u64,
+    pub user: Pubkey,
+    /// Token X sent from the vault.
+    pub amount_x: u64,
+    /// Token Y sent from the vault.
+    pub amount_y: u64,
+    /// LP burned from the user.
+    pub lp_amount: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    pub lp_supply: u64,
+}
@@ -1,17 +1,52 @@
 // This program implements a constant product Automated Market Maker (AMM) on Solana using Anchor.
+// Pools for pegged pairs can instead use the StableSwap curve, chosen at initialize.
 //
 // Features:
+// - initialize_factory / update_fee_tiers: Create the pool registry and manage its fee tiers.
 // - initialize: Sets up a new AMM pool with two token vaults, an LP mint, and configuration.
+//   Pools are registered in the factory, with one canonical pool per mint pair and fee tier.
+// - list_pools: Read-only view enumerating the pools registered in the factory.
 // - deposit: Allows users to add liquidity to the pool and mint LP tokens representing their share.
+// - deposit_single: Adds liquidity from a single token, swapping part of it internally.
 // - swap: Allows users to swap between the two tokens using the constant product formula (x*y=k).
+// - swap_exact_out: Swaps for an exact output amount, bounded by a maximum input.
+// - swap_route: Chains exact-input swaps across several pools passed as remaining accounts.
+// - flash_loan / flash_repay: Lend vault tokens within a single transaction, repaid with the pool fee.
 // - withdraw: Allows users to burn their LP tokens and withdraw their proportional share of the pool's tokens.
+// - withdraw_single: Burns LP tokens and pays out a single token, swapping the other side internally.
+// - lock / unlock / update_fee / transfer_authority: Admin instructions gated on the pool's authority.
+// - set_dynamic_fee / disable_dynamic_fee: Scale the swap fee with recent volatility, between two bounds.
+// - ramp_amp / stop_ramp_amp: Adjust a StableSwap pool's amplification coefficient over time.
+// - close_pool: Closes a drained pool's accounts and refunds their rent to the initializer.
+// - update_protocol_fee / set_treasury / collect_protocol_fees: Route a share of swap fees to the pool's treasury.
+// - position_info: Read-only view of an LP position's accrued fees and impermanent loss.
+//   Positions are created on an LP's first deposit and track what they paid in.
+// - cl_initialize / cl_init_tick_array: Set up a concentrated liquidity pool and its tick arrays.
+//   Concentrated pools are registered in the factory too, with one canonical pool per mint pair and fee tier.
+// - cl_lock / cl_unlock / cl_transfer_authority: Admin instructions gated on a concentrated pool's authority.
+// - cl_deposit / cl_withdraw / cl_collect: Provide liquidity over a price range, and collect its fees.
+// - cl_swap: Swaps through a concentrated pool, crossing ticks as the price moves.
+// - observe: Read-only view of the pool's TWAP price oracle, updated on every swap, deposit and withdraw.
+//
+// Deposits, swaps and withdrawals take an optional `expires_at` (a unix timestamp or a slot), after
+// which they fail with `Expired`, so a stale signed transaction can't land at a much later price.
+//
+// Every instruction that changes pool state emits an event (pool created, liquidity added or removed,
+// swaps, config changes) with the pool key and seed, so indexers don't need to parse raw transactions.
+//
+// All pricing math (swap quotes, proportional deposit/withdraw amounts, StableSwap invariant)
+// lives in the `amm_quote` crate, which off-chain clients can use to quote the exact same numbers.
 //
 // Each instruction is documented below and in the corresponding instruction module.
 
+#![allow(unexpected_cfgs)]
+#![allow(deprecated)]
+
 pub mod constants;
 pub mod error;
 pub mod instructions;
 pub mod state;
+pub mod utils;
 
 use anchor_lang::prelude::*;
 
@@ -25,40 +60,604 @@ declare_id!("BDHWofSWX2ap9CHYiANE2h7XrYNrkYDnSJ5LT8SR5zrV");
 pub mod amm {
     use super::*;
 
-    /// Initializes a new AMM pool with the given seed, fee, and optional authority.
+    /// Creates the pool factory with the given fee tiers enabled. Only callable by the program's upgrade authority,
+    /// who becomes the factory authority.
+    pub fn initialize_factory(ctx: Context<InitializeFactory>, fee_tiers: Vec<u16>) -> Result<()> {
+        ctx.accounts.initialize_factory(fee_tiers, ctx.bumps)
+    }
+
+    /// Replaces the fee tiers new pools may be created with. Only callable by the factory authority.
+    pub fn update_fee_tiers(ctx: Context<UpdateFactory>, fee_tiers: Vec<u16>) -> Result<()> {
+        ctx.accounts.update_fee_tiers(fee_tiers)
+    }
+
+    /// Initializes a new AMM pool with the given seed, fee, optional authority and curve.
     /// Creates the config, LP mint, and vaults for both tokens.
-    pub fn initialize(ctx: Context<Initialize>, seed: u64, fee: u16, authority: Option<Pubkey>) -> Result<()> {
-        ctx.accounts.init(seed, fee, authority, ctx.bumps)
+    /// `seed` must be the factory's `pool_count` and `fee` one of its fee tiers; the pool's
+    /// canonical PDA for the mint pair and fee tier is created too, so duplicate pools are rejected.
+    /// `amp` is the StableSwap amplification coefficient, and must be zero for constant product pools.
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        seed: u64,
+        fee: u16,
+        authority: Option<Pubkey>,
+        curve: CurveType,
+        amp: u64,
+    ) -> Result<()> {
+        ctx.accounts.init(seed, fee, authority, curve, amp, ctx.bumps)
     }
 
     /// Deposits tokens into the pool and mints LP tokens to the user.
     /// The user receives LP tokens representing their share of the pool.
-    pub fn deposit(ctx: Context<Deposit>, amount: u64, max_x: u64, max_y: u64) -> Result<()> {
+    /// The first deposit supplies exactly `max_x` and `max_y`, mints sqrt(x * y) LP and locks
+    /// `MINIMUM_LIQUIDITY` of it; `amount` is then the minimum LP the depositor accepts.
+    pub fn deposit(
+        ctx: Context<Deposit>,
+        amount: u64,
+        max_x: u64,
+        max_y: u64,
+        expires_at: Option<Expiry>,
+    ) -> Result<()> {
+        check_expiry(expires_at)?;
+        ctx.accounts.open_position(ctx.bumps.position)?;
         ctx.accounts.deposit(amount, max_x, max_y)
     }
 
-    /// Swaps tokens using the constant product formula (x*y=k).
+    /// Deposits only token X (`is_x`) or only token Y into the pool.
+    /// Part of the input is swapped at the pool fee and LP tokens are minted for the balanced result.
+    pub fn deposit_single(
+        ctx: Context<Deposit>,
+        is_x: bool,
+        amount_in: u64,
+        min_lp_out: u64,
+        expires_at: Option<Expiry>,
+    ) -> Result<()> {
+        check_expiry(expires_at)?;
+        ctx.accounts.open_position(ctx.bumps.position)?;
+        ctx.accounts.deposit_single(is_x, amount_in, min_lp_out)
+    }
+
+    /// Swaps tokens on the pool's curve: constant product (x*y=k) or StableSwap.
     /// The user provides the input amount, minimum output, and direction (x_to_y).
-    pub fn swap(ctx: Context<Swap>, amount_in: u64, min_amount_out: u64, x_to_y: bool) -> Result<()> {
+    pub fn swap(
+        ctx: Context<Swap>,
+        amount_in: u64,
+        min_amount_out: u64,
+        x_to_y: bool,
+        expires_at: Option<Expiry>,
+    ) -> Result<()> {
+        check_expiry(expires_at)?;
         ctx.accounts.swap(amount_in, min_amount_out, x_to_y)
     }
 
+    /// Swaps tokens for an exact `amount_out`, using the same fee and constant product math.
+    /// Fails if the required input exceeds `max_amount_in`.
+    pub fn swap_exact_out(
+        ctx: Context<Swap>,
+        amount_out: u64,
+        max_amount_in: u64,
+        x_to_y: bool,
+        expires_at: Option<Expiry>,
+    ) -> Result<()> {
+        check_expiry(expires_at)?;
+        ctx.accounts.swap_exact_out(amount_out, max_amount_in, x_to_y)
+    }
+
+    /// Swaps `amount_in` across an ordered list of pools, given as remaining accounts
+    /// (`[config, vault_in, vault_out]` per hop), with one overall `min_amount_out`.
+    pub fn swap_route<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+        expires_at: Option<Expiry>,
+    ) -> Result<()> {
+        check_expiry(expires_at)?;
+        ctx.accounts.swap_route(ctx.remaining_accounts, amount_in, min_amount_out)
+    }
+
+    /// Lends `amount_x` of token X and `amount_y` of token Y from the vaults.
+    /// A `flash_repay` for the same pool must follow in the same transaction; until then the pool is locked.
+    pub fn flash_loan(ctx: Context<FlashLoan>, amount_x: u64, amount_y: u64) -> Result<()> {
+        ctx.accounts.flash_loan(amount_x, amount_y)
+    }
+
+    /// Repays the outstanding flash loan plus the pool fee on the principal.
+    pub fn flash_repay(ctx: Context<FlashLoan>) -> Result<()> {
+        ctx.accounts.flash_repay()
+    }
+
     /// Withdraws liquidity by burning LP tokens and transferring the user's share of the pool tokens.
     /// The user receives their proportional share of both vault_x and vault_y.
-    pub fn withdraw(ctx: Context<Withdraw>, lp_amount: u64, min_x: u64, min_y: u64) -> Result<()> {
+    pub fn withdraw(
+        ctx: Context<Withdraw>,
+        lp_amount: u64,
+        min_x: u64,
+        min_y: u64,
+        expires_at: Option<Expiry>,
+    ) -> Result<()> {
+        check_expiry(expires_at)?;
         ctx.accounts.withdraw(lp_amount, min_x, min_y)
     }
+
+    /// Burns LP tokens and pays out only token X (`is_x`) or only token Y.
+    /// The other side of the user's share is swapped back through the curve at the pool fee.
+    pub fn withdraw_single(
+        ctx: Context<Withdraw>,
+        lp_amount: u64,
+        is_x: bool,
+        min_out: u64,
+        expires_at: Option<Expiry>,
+    ) -> Result<()> {
+        check_expiry(expires_at)?;
+        ctx.accounts.withdraw_single(lp_amount, is_x, min_out)
+    }
+
+    /// Returns the pool's cumulative prices as of the current timestamp.
+    /// Two observations give the time-weighted average price over the window between them.
+    pub fn observe(ctx: Context<Observe>) -> Result<Observation> {
+        ctx.accounts.observe()
+    }
+
+    /// Lists the pools with seeds starting at `start`, whose config (or `ClPool`) PDAs are given as remaining accounts.
+    pub fn list_pools<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ListPools<'info>>,
+        start: u64,
+    ) -> Result<PoolPage> {
+        ctx.accounts.list_pools(ctx.remaining_accounts, start)
+    }
+
+    /// Returns a liquidity provider's position valued at the current reserves:
+    /// the fees it has earned since entry and its impermanent loss against holding.
+    pub fn position_info(ctx: Context<PositionInfo>) -> Result<PositionReport> {
+        ctx.accounts.position_info()
+    }
+
+    /// Locks the pool so deposits, swaps and withdrawals are rejected.
+    /// Only callable by the pool authority.
+    pub fn lock(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.lock()
+    }
+
+    /// Unlocks a locked pool. Only callable by the pool authority.
+    pub fn unlock(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.unlock()
+    }
+
+    /// Updates the pool fee (in basis points). Only callable by the pool authority.
+    pub fn update_fee(ctx: Context<Update>, fee: u16) -> Result<()> {
+        ctx.accounts.update_fee(fee)
+    }
+
+    /// Updates the protocol's share of the swap fee (in basis points of the fee).
+    /// Only callable by the pool authority.
+    pub fn update_protocol_fee(ctx: Context<Update>, protocol_fee: u16) -> Result<()> {
+        ctx.accounts.update_protocol_fee(protocol_fee)
+    }
+
+    /// Switches the pool to a dynamic swap fee between `min_fee` and `max_fee` (in basis points),
+    /// scaled by recent price moves up to `max_volatility` basis points, decaying over `decay_period` seconds.
+    /// Only callable by the pool authority.
+    pub fn set_dynamic_fee(
+        ctx: Context<Update>,
+        min_fee: u16,
+        max_fee: u16,
+        max_volatility: u64,
+        decay_period: i64,
+    ) -> Result<()> {
+        ctx.accounts.set_dynamic_fee(min_fee, max_fee, max_volatility, decay_period)
+    }
+
+    /// Switches the pool back to its static swap fee. Only callable by the pool authority.
+    pub fn disable_dynamic_fee(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.disable_dynamic_fee()
+    }
+
+    /// Ramps a StableSwap pool's amplification coefficient linearly to `target_amp` by `stop_ts`.
+    /// Only callable by the pool authority.
+    pub fn ramp_amp(ctx: Context<Update>, target_amp: u64, stop_ts: i64) -> Result<()> {
+        ctx.accounts.ramp_amp(target_amp, stop_ts)
+    }
+
+    /// Stops an amplification ramp at the current value. Only callable by the pool authority.
+    pub fn stop_ramp_amp(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.stop_ramp_amp()
+    }
+
+    /// Sets the wallet whose token accounts receive the pool's protocol fees.
+    /// Only callable by the pool authority.
+    pub fn set_treasury(ctx: Context<Update>, treasury: Pubkey) -> Result<()> {
+        ctx.accounts.set_treasury(treasury)
+    }
+
+    /// Transfers the accrued protocol fees from the vaults to the treasury's token accounts.
+    /// Only callable by the pool authority.
+    pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
+        ctx.accounts.collect_protocol_fees()
+    }
+
+    /// Closes a drained pool: burns the locked minimum liquidity, sends any dust to the initializer
+    /// and closes the vaults, LP lock, config and canonical pool, refunding their rent to the initializer.
+    /// Only callable by the pool authority, once every LP has withdrawn and protocol fees are collected.
+    pub fn close_pool(ctx: Context<ClosePool>) -> Result<()> {
+        ctx.accounts.close_pool()
+    }
+
+    /// Initializes a concentrated liquidity pool priced at `sqrt_price` (Q64.64 sqrt of Y per X).
+    /// `seed` must be the factory's `pool_count` and `fee` one of its fee tiers; the pool's canonical
+    /// PDA for the mint pair and fee tier is created too, so duplicate concentrated pools are rejected.
+    /// Positions use ticks on multiples of `tick_spacing`.
+    pub fn cl_initialize(
+        ctx: Context<ClInitialize>,
+        seed: u64,
+        fee: u16,
+        authority: Option<Pubkey>,
+        tick_spacing: u16,
+        sqrt_price: u128,
+    ) -> Result<()> {
+        ctx.accounts.cl_initialize(seed, fee, authority, tick_spacing, sqrt_price, ctx.bumps)
+    }
+
+    /// Locks a concentrated pool so deposits, swaps, withdrawals and fee collection are rejected.
+    /// Only callable by the pool authority.
+    pub fn cl_lock(ctx: Context<ClUpdate>) -> Result<()> {
+        ctx.accounts.cl_lock()
+    }
+
+    /// Unlocks a locked concentrated pool. Only callable by the pool authority.
+    pub fn cl_unlock(ctx: Context<ClUpdate>) -> Result<()> {
+        ctx.accounts.cl_unlock()
+    }
+
+    /// Transfers a concentrated pool's authority to `new_authority`, or renounces it when `None`.
+    /// Only callable by the current pool authority.
+    pub fn cl_transfer_authority(ctx: Context<ClUpdate>, new_authority: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.cl_transfer_authority(new_authority)
+    }
+
+    /// Creates the tick array of a concentrated pool starting at `start_tick`.
+    pub fn cl_init_tick_array(ctx: Context<ClInitTickArray>, start_tick: i32) -> Result<()> {
+        ctx.accounts.cl_init_tick_array(start_tick)
+    }
+
+    /// Adds `liquidity` to the user's position over [tick_lower, tick_upper) in a concentrated pool,
+    /// paying at most `max_x` and `max_y`. The position is created on its first deposit.
+    pub fn cl_deposit(
+        ctx: Context<ClDeposit>,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u128,
+        max_x: u64,
+        max_y: u64,
+        expires_at: Option<Expiry>,
+    ) -> Result<()> {
+        check_expiry(expires_at)?;
+        ctx.accounts.open_position(tick_lower, tick_upper, ctx.bumps.position)?;
+        ctx.accounts.cl_deposit(liquidity, max_x, max_y)
+    }
+
+    /// Removes `liquidity` from a concentrated position, receiving at least `min_x` and `min_y`.
+    pub fn cl_withdraw(
+        ctx: Context<ClWithdraw>,
+        liquidity: u128,
+        min_x: u64,
+        min_y: u64,
+        expires_at: Option<Expiry>,
+    ) -> Result<()> {
+        check_expiry(expires_at)?;
+        ctx.accounts.cl_withdraw(liquidity, min_x, min_y)
+    }
+
+    /// Pays out the fees a concentrated position has earned.
+    pub fn cl_collect(ctx: Context<ClWithdraw>) -> Result<()> {
+        ctx.accounts.cl_collect()
+    }
+
+    /// Swaps up to `amount_in` through a concentrated pool, stopping at `sqrt_price_limit`.
+    /// The tick arrays the price may move through are passed as remaining accounts.
+    pub fn cl_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClSwap<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+        x_to_y: bool,
+        sqrt_price_limit: u128,
+        expires_at: Option<Expiry>,
+    ) -> Result<()> {
+        check_expiry(expires_at)?;
+        ctx.accounts.cl_swap(ctx.remaining_accounts, amount_in, min_amount_out, x_to_y, sqrt_price_limit)
+    }
+
+    /// Transfers the pool authority to `new_authority`, or renounces it when `None`.
+    /// Only callable by the current pool authority.
+    pub fn transfer_authority(ctx: Context<Update>, new_authority: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.transfer_authority(new_authority)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// A 0.3% constant product pool with every other field zeroed, for tests to override.
+    fn test_config() -> Config {
+        Config {
+            seed: 0,
+            authority: None,
+            initializer: Pubkey::default(),
+            mint_x: Pubkey::default(),
+            mint_y: Pubkey::default(),
+            fee: 30,
+            protocol_fee: 0,
+            protocol_fees_x: 0,
+            protocol_fees_y: 0,
+            treasury: Pubkey::default(),
+            locked: false,
+            config_bump: 0,
+            lp_bump: 0,
+            price_x_cumulative: 0,
+            price_y_cumulative: 0,
+            last_update_ts: 0,
+            curve: CurveType::ConstantProduct,
+            amp_initial: 0,
+            amp_target: 0,
+            amp_ramp_start_ts: 0,
+            amp_ramp_stop_ts: 0,
+            flash_loan: None,
+            dynamic_fee: None,
+        }
+    }
+
     #[test]
     fn test_fee_calculation() {
-        // Example test: replace with real logic
-        let fee = 30u16;
-        assert_eq!(fee, 30);
+        // 0.3% is taken from the input before pricing, rounding the fee up
+        let (amount_out, fee_amount) = get_amount_out(10_000, 1_000_000, 1_000_000, 30).unwrap();
+        assert_eq!(fee_amount, 30);
+        assert_eq!(amount_out, 9_970 * 1_000_000 / 1_009_970);
+
+        let (_, fee_amount) = get_amount_out(101, 1_000_000, 1_000_000, 30).unwrap();
+        assert_eq!(fee_amount, 1);
+    }
+
+    #[test]
+    fn test_swap_exact_out_rounds_in_favour_of_pool() {
+        let (reserve_in, reserve_out, fee) = (1_000_000, 2_000_000, 30);
+        for amount_out in [1, 7, 1_000, 123_457, 1_999_999] {
+            let (amount_in, _) = get_amount_in(amount_out, reserve_in, reserve_out, fee).unwrap();
+            // The quoted input always buys at least the requested output...
+            let (received, _) = get_amount_out(amount_in, reserve_in, reserve_out, fee).unwrap();
+            assert!(received >= amount_out);
+            // ...and one unit less never does
+            let (short, _) = get_amount_out(amount_in - 1, reserve_in, reserve_out, fee).unwrap();
+            assert!(short < amount_out);
+        }
+        assert!(get_amount_in(reserve_out, reserve_in, reserve_out, fee).is_err());
+    }
+
+    #[test]
+    fn test_single_sided_swap_leaves_balanced_deposit() {
+        let (reserve_in, reserve_out, fee) = (1_000_000u64, 2_000_000u64, 30);
+        let amount_in = 100_000u64;
+        let (swap_in, swap_out, _) = get_single_sided_swap(amount_in, reserve_in, reserve_out, fee).unwrap();
+
+        // Roughly half the input is swapped
+        assert!(swap_in > amount_in * 45 / 100 && swap_in < amount_in / 2);
+        // The remainder is at least as large as the post-swap ratio requires...
+        let remaining = (amount_in - swap_in) as u128;
+        assert!(remaining * (reserve_out - swap_out) as u128 >= swap_out as u128 * (reserve_in + swap_in) as u128);
+        // ...and swapping one more unit would leave too little
+        let (next_out, _) = get_amount_out(swap_in + 1, reserve_in, reserve_out, fee).unwrap();
+        let next_remaining = (amount_in - swap_in - 1) as u128;
+        assert!(next_remaining * ((reserve_out - next_out) as u128) < next_out as u128 * (reserve_in + swap_in + 1) as u128);
+    }
+
+    #[test]
+    fn test_oracle_accumulates_time_weighted_price() {
+        let mut config = Config { last_update_ts: 100, ..test_config() };
+
+        // Price of X is 2 Y for 10s, then 4 Y for 30s
+        config.update_oracle(1_000, 2_000, 110);
+        config.update_oracle(1_000, 4_000, 140);
+        let twap = config.price_x_cumulative / (140 - 100);
+        assert_eq!(twap >> 64, 3);
+        assert_eq!(config.last_update_ts, 140);
+
+        // No time elapsed, no accumulation
+        let before = config.price_y_cumulative;
+        config.update_oracle(1_000, 4_000, 140);
+        assert_eq!(config.price_y_cumulative, before);
+    }
+
+    #[test]
+    fn test_amp_ramps_linearly() {
+        let config = Config {
+            fee: 4,
+            curve: CurveType::StableSwap,
+            amp_initial: 100,
+            amp_target: 400,
+            amp_ramp_start_ts: 1_000,
+            amp_ramp_stop_ts: 2_000,
+            ..test_config()
+        };
+
+        assert_eq!(config.amp(0), 100);
+        assert_eq!(config.amp(1_500), 250);
+        assert_eq!(config.amp(2_000), 400);
+        assert_eq!(config.amp(5_000), 400);
+    }
+
+    #[test]
+    fn test_canonical_pool_seeds_ignore_mint_order() {
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let pool_for = |x: Pubkey, y: Pubkey, fee: u16| {
+            let (lower, higher) = CanonicalPool::sorted_mints(x, y);
+            Pubkey::find_program_address(&[b"pool", lower.as_ref(), higher.as_ref(), fee.to_le_bytes().as_ref()], &ID).0
+        };
+
+        assert_eq!(pool_for(mint_a, mint_b, 30), pool_for(mint_b, mint_a, 30));
+        assert_ne!(pool_for(mint_a, mint_b, 30), pool_for(mint_a, mint_b, 5));
+    }
+
+    #[test]
+    fn test_position_reports_fees_and_impermanent_loss() {
+        let config = test_config();
+        let mut position = Position {
+            owner: Pubkey::default(),
+            pool: Pubkey::default(),
+            lp_amount: 0,
+            cost_x: 0,
+            cost_y: 0,
+            entry_liquidity_per_lp: 0,
+            bump: 0,
+        };
+
+        // Enter with half of a 100_000 / 100_000 pool
+        let entry = config.liquidity_per_lp(100_000, 100_000, 100_000, 0).unwrap();
+        position.credit(50_000, 50_000, 50_000, entry).unwrap();
+
+        // Without trading there are no fees and no loss
+        let report = position_report(&position, 100_000, 100_000, 100_000, entry).unwrap();
+        assert_eq!((report.fees_x, report.fees_y, report.impermanent_loss), (0, 0, 0));
+
+        // Fees grow both reserves by 1% without minting LP
+        let grown = config.liquidity_per_lp(101_000, 101_000, 100_000, 0).unwrap();
+        let report = position_report(&position, 101_000, 101_000, 100_000, grown).unwrap();
+        // (fees round down, in favour of reporting less)
+        assert_eq!(report.amount_x, 50_500);
+        assert!(report.fees_x.abs_diff(500) <= 1 && report.fees_y.abs_diff(500) <= 1);
+        assert!(report.impermanent_loss.abs() <= 2);
+
+        // The price of X doubles (k unchanged): the position lags holding
+        let (x, y) = (70_711, 141_421);
+        let report = position_report(&position, x, y, 100_000, entry).unwrap();
+        assert_eq!(report.hold_value, 50_000 * y / x + 50_000);
+        // The textbook 2x loss is 1 - 2 * sqrt(2) / 3, about 5.72%
+        let loss_bps = report.impermanent_loss * 10_000 / report.hold_value as i64;
+        assert!((570..=574).contains(&loss_bps));
+
+        // Withdrawing half takes half the cost basis with it
+        position.debit(25_000);
+        assert_eq!((position.lp_amount, position.cost_x, position.cost_y), (25_000, 25_000, 25_000));
+    }
+
+    #[test]
+    fn test_expiry_is_inclusive() {
+        let clock = Clock { slot: 100, unix_timestamp: 1_000, ..Clock::default() };
+
+        assert!(!Expiry::Timestamp(1_000).is_expired(&clock));
+        assert!(Expiry::Timestamp(999).is_expired(&clock));
+        assert!(!Expiry::Slot(100).is_expired(&clock));
+        assert!(Expiry::Slot(99).is_expired(&clock));
+    }
+
+    #[test]
+    fn test_dynamic_fee_rises_with_volatility_and_decays() {
+        let mut dynamic_fee = DynamicFee {
+            min_fee: 10,
+            max_fee: 100,
+            max_volatility: 1_000,
+            decay_period: 100,
+            volatility: 0,
+            last_update_ts: 0,
+        };
+        assert_eq!(dynamic_fee.fee_at(0), 10);
+
+        // Price of X doubles from 2 to 4: a 10_000 bps move, capped at max_volatility
+        assert_eq!(price_move_bps((1_000, 2_000), (1_000, 4_000)), 10_000);
+        assert_eq!(price_move_bps((1_000, 2_000), (1_000, 1_900)), 500);
+        assert_eq!(price_move_bps((0, 2_000), (1_000, 1_900)), 0);
+
+        dynamic_fee.record(500, 0);
+        assert_eq!(dynamic_fee.fee_at(0), 55);
+        dynamic_fee.record(10_000, 0);
+        assert_eq!(dynamic_fee.volatility, 1_000);
+        assert_eq!(dynamic_fee.fee_at(0), 100);
+
+        // Decays linearly back to the minimum over the decay period
+        assert_eq!(dynamic_fee.fee_at(50), 55);
+        assert_eq!(dynamic_fee.fee_at(100), 10);
+        dynamic_fee.record(200, 50);
+        assert_eq!(dynamic_fee.volatility, 700);
+        assert_eq!(dynamic_fee.last_update_ts, 50);
+    }
+
+    #[test]
+    fn test_concentrated_swap_crosses_ticks_and_accrues_fees() {
+        use amm_quote::concentrated::sqrt_price_at_tick;
+
+        let mut pool = ClPool {
+            seed: 0,
+            authority: None,
+            locked: false,
+            mint_x: Pubkey::default(),
+            mint_y: Pubkey::default(),
+            fee: 30,
+            tick_spacing: 10,
+            sqrt_price: sqrt_price_at_tick(0).unwrap(),
+            tick_current: 0,
+            liquidity: 0,
+            fee_growth_global_x: 0,
+            fee_growth_global_y: 0,
+            bump: 0,
+        };
+        let tick_array = |start_tick: i32| TickArray {
+            pool: Pubkey::default(),
+            start_tick,
+            ticks: [Tick::default(); TICK_ARRAY_SIZE],
+        };
+        let position = |tick_lower: i32, tick_upper: i32| ClPosition {
+            owner: Pubkey::default(),
+            pool: Pubkey::default(),
+            tick_lower,
+            tick_upper,
+            liquidity: 0,
+            fee_growth_inside_last_x: 0,
+            fee_growth_inside_last_y: 0,
+            tokens_owed_x: 0,
+            tokens_owed_y: 0,
+            bump: 0,
+        };
+        let (mut below, mut above) = (tick_array(-160), tick_array(0));
+        let liquidity = 1_000_000_000i128;
+
+        // A wide position around the price, and a narrow one just below it (all token Y)
+        let mut wide = position(-100, 100);
+        let (wide_x, wide_y) = pool.modify_position(&mut wide, &mut below, Some(&mut above), liquidity).unwrap();
+        assert!(wide_x > 0 && wide_y > 0);
+        let mut narrow = position(-20, 0);
+        let (narrow_x, narrow_y) = pool.modify_position(&mut narrow, &mut below, Some(&mut above), liquidity).unwrap();
+        assert!(narrow_x == 0 && narrow_y > 0);
+        assert_eq!(pool.liquidity, liquidity as u128);
+
+        // Sell X down to tick -50: the narrow range becomes active at 0 and inactive again at -20
+        let limit = sqrt_price_at_tick(-50).unwrap();
+        let sold = pool.swap(&mut [&mut below, &mut above], 10_000_000, true, limit).unwrap();
+        assert!(sold.amount_in < 10_000_000 && sold.fee_amount > 0);
+        assert_eq!((pool.sqrt_price, pool.tick_current, pool.liquidity), (limit, -50, liquidity as u128));
+
+        // The wide range earned on the whole move, the narrow one only on its share of [-20, 0)
+        pool.modify_position(&mut wide, &mut below, Some(&mut above), 0).unwrap();
+        pool.modify_position(&mut narrow, &mut below, Some(&mut above), 0).unwrap();
+        assert!(wide.tokens_owed_x > narrow.tokens_owed_x && narrow.tokens_owed_x > 0);
+        let owed_x = wide.tokens_owed_x + narrow.tokens_owed_x;
+        assert!(owed_x <= sold.fee_amount && owed_x + 2 >= sold.fee_amount);
+
+        // Buy back up to tick 50, crossing both ticks the other way
+        let limit = sqrt_price_at_tick(50).unwrap();
+        let bought = pool.swap(&mut [&mut below, &mut above], 10_000_000, false, limit).unwrap();
+        assert_eq!((pool.tick_current, pool.liquidity), (50, liquidity as u128));
+
+        // Everyone leaves: the pool never pays out more than it took in
+        let (out_x, out_y) = pool.modify_position(&mut wide, &mut below, Some(&mut above), -liquidity).unwrap();
+        let (narrow_out_x, narrow_out_y) = pool.modify_position(&mut narrow, &mut below, Some(&mut above), -liquidity).unwrap();
+        assert_eq!(pool.liquidity, 0);
+        assert!(below.ticks.iter().chain(above.ticks.iter()).all(|tick| *tick == Tick::default()));
+
+        let paid_x = out_x + narrow_out_x + wide.tokens_owed_x + narrow.tokens_owed_x + bought.amount_out;
+        let paid_y = out_y + narrow_out_y + wide.tokens_owed_y + narrow.tokens_owed_y + sold.amount_out;
+        assert!(paid_x <= wide_x + narrow_x + sold.amount_in);
+        assert!(paid_y <= wide_y + narrow_y + bought.amount_in);
+
+        // Withdrawing more than the position holds fails
+        assert!(pool.modify_position(&mut wide, &mut below, Some(&mut above), -1).is_err());
     }
 
     // Add more unit tests for your pure Rust logic here
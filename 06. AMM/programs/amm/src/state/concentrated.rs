@@ -0,0 +1,372 @@
+// State for concentrated liquidity pools.
+//
+// A `ClPool` holds the current sqrt price and the liquidity active at that price. Liquidity is
+// added over tick ranges by `ClPosition`s; each range's edges are `Tick`s stored in `TickArray`
+// accounts of `TICK_ARRAY_SIZE` ticks each. A tick records how much liquidity becomes active or
+// inactive when the price crosses it, and the fee growth on its far side, from which each
+// position's share of the fees earned inside its range is derived.
+
+use amm_quote::concentrated::{
+    compute_swap_step, fee_growth_delta, fees_earned, liquidity_amounts, sqrt_price_at_tick, tick_at_sqrt_price,
+    MAX_SQRT_PRICE, MAX_TICK, MIN_SQRT_PRICE, MIN_TICK,
+};
+use anchor_lang::prelude::*;
+
+use crate::{ constants::TICK_ARRAY_SIZE, error::AmmError };
+
+/// A concentrated liquidity pool (PDA `["cl_pool", seed]`). Its vaults are the pool's ATAs.
+/// Its seed comes from the factory's `pool_count`, shared with constant product and StableSwap pools.
+#[account]
+#[derive(InitSpace)]
+pub struct ClPool {
+    pub seed: u64,
+    pub authority: Option<Pubkey>,
+    /// When set, deposits, swaps, withdrawals and fee collection are rejected.
+    pub locked: bool,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    /// Swap fee in basis points of the input, paid entirely to the in-range positions.
+    pub fee: u16,
+    /// Positions may only start and end on multiples of this tick spacing.
+    pub tick_spacing: u16,
+    /// Current sqrt price of X in Y, in Q64.64.
+    pub sqrt_price: u128,
+    /// The tick at or below the current sqrt price.
+    pub tick_current: i32,
+    /// Liquidity of the positions whose range contains the current price.
+    pub liquidity: u128,
+    /// Fees in token X earned per unit of liquidity over the pool's lifetime (Q64.64). Wraps on overflow.
+    pub fee_growth_global_x: u128,
+    /// Fees in token Y earned per unit of liquidity over the pool's lifetime (Q64.64). Wraps on overflow.
+    pub fee_growth_global_y: u128,
+    pub bump: u8,
+}
+
+/// The outcome of a swap through a concentrated pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClSwapResult {
+    /// Input consumed, fee included. Less than requested if the swap stopped at the price limit.
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+}
+
+impl ClPool {
+    /// Ensures `signer` is the pool's authority. Pools created without an authority can never be updated.
+    pub fn check_authority(&self, signer: &Pubkey) -> Result<()> {
+        match self.authority {
+            Some(authority) => {
+                require_keys_eq!(authority, *signer, AmmError::InvalidAuthority);
+                Ok(())
+            }
+            None => err!(AmmError::NoAuthoritySet),
+        }
+    }
+
+    /// Ensures `tick` is in range and on the pool's tick spacing.
+    pub fn check_tick(&self, tick: i32) -> Result<()> {
+        require!((MIN_TICK..=MAX_TICK).contains(&tick), AmmError::InvalidTick);
+        require!(tick % self.tick_spacing as i32 == 0, AmmError::InvalidTick);
+        Ok(())
+    }
+
+    /// Returns the first tick of the tick array containing `tick`.
+    pub fn tick_array_start(&self, tick: i32) -> i32 {
+        let ticks_per_array = self.tick_spacing as i32 * TICK_ARRAY_SIZE as i32;
+        tick.div_euclid(ticks_per_array) * ticks_per_array
+    }
+
+    /// Adds `liquidity_delta` to `position`, updating its range's ticks in `lower_array` and
+    /// `upper_array` (`None` when both ticks are in `lower_array`) and accruing the fees the position
+    /// earned so far. Returns the token amounts to pay in (rounded up) or out (rounded down).
+    pub fn modify_position(
+        &mut self,
+        position: &mut ClPosition,
+        lower_array: &mut TickArray,
+        upper_array: Option<&mut TickArray>,
+        liquidity_delta: i128,
+    ) -> Result<(u64, u64)> {
+        let (tick_lower, tick_upper) = (position.tick_lower, position.tick_upper);
+        let spacing = self.tick_spacing;
+        let (lower, upper) = match upper_array {
+            Some(upper_array) => {
+                // The same account passed twice would lose one of the updates
+                require!(upper_array.start_tick != lower_array.start_tick, AmmError::InvalidTickArray);
+                (lower_array.tick_mut(tick_lower, spacing)?, upper_array.tick_mut(tick_upper, spacing)?)
+            }
+            None => lower_array.tick_pair_mut(tick_lower, tick_upper, spacing)?,
+        };
+
+        let (global_x, global_y) = (self.fee_growth_global_x, self.fee_growth_global_y);
+        if liquidity_delta != 0 {
+            lower.update(tick_lower, self.tick_current, liquidity_delta, false, global_x, global_y)?;
+            upper.update(tick_upper, self.tick_current, liquidity_delta, true, global_x, global_y)?;
+        }
+        let (inside_x, inside_y) = self.fee_growth_inside(lower, tick_lower, upper, tick_upper);
+        position.update(liquidity_delta, inside_x, inside_y)?;
+        // Ticks no longer referenced by any position are cleared once their fee growth has been read
+        if lower.liquidity_gross == 0 {
+            *lower = Tick::default();
+        }
+        if upper.liquidity_gross == 0 {
+            *upper = Tick::default();
+        }
+
+        if (tick_lower..tick_upper).contains(&self.tick_current) {
+            self.liquidity = add_liquidity_delta(self.liquidity, liquidity_delta)?;
+        }
+
+        let sqrt_price_lower = sqrt_price_at_tick(tick_lower).map_err(AmmError::from)?;
+        let sqrt_price_upper = sqrt_price_at_tick(tick_upper).map_err(AmmError::from)?;
+        let amounts = liquidity_amounts(
+            self.sqrt_price,
+            sqrt_price_lower,
+            sqrt_price_upper,
+            liquidity_delta.unsigned_abs(),
+            liquidity_delta > 0,
+        )
+        .map_err(AmmError::from)?;
+        Ok(amounts)
+    }
+
+    /// Returns the fee growth per unit of liquidity (Q64.64) earned inside [tick_lower, tick_upper):
+    /// the global fee growth minus what was earned below the lower tick and above the upper tick.
+    pub fn fee_growth_inside(&self, lower: &Tick, tick_lower: i32, upper: &Tick, tick_upper: i32) -> (u128, u128) {
+        let (global_x, global_y) = (self.fee_growth_global_x, self.fee_growth_global_y);
+        let (below_x, below_y) = if self.tick_current >= tick_lower {
+            (lower.fee_growth_outside_x, lower.fee_growth_outside_y)
+        } else {
+            (global_x.wrapping_sub(lower.fee_growth_outside_x), global_y.wrapping_sub(lower.fee_growth_outside_y))
+        };
+        let (above_x, above_y) = if self.tick_current < tick_upper {
+            (upper.fee_growth_outside_x, upper.fee_growth_outside_y)
+        } else {
+            (global_x.wrapping_sub(upper.fee_growth_outside_x), global_y.wrapping_sub(upper.fee_growth_outside_y))
+        };
+        (
+            global_x.wrapping_sub(below_x).wrapping_sub(above_x),
+            global_y.wrapping_sub(below_y).wrapping_sub(above_y),
+        )
+    }
+
+    /// Swaps up to `amount_in` of token X (`x_to_y`) or Y, moving the price towards `sqrt_price_limit`
+    /// and crossing initialized ticks on the way. `tick_arrays` must hold every tick array the price
+    /// moves through, in any order; the swap fails if it needs one that isn't there.
+    pub fn swap(
+        &mut self,
+        tick_arrays: &mut [&mut TickArray],
+        amount_in: u64,
+        x_to_y: bool,
+        sqrt_price_limit: u128,
+    ) -> Result<ClSwapResult> {
+        if x_to_y {
+            require!(sqrt_price_limit > MIN_SQRT_PRICE && sqrt_price_limit < self.sqrt_price, AmmError::InvalidPrice);
+        } else {
+            require!(sqrt_price_limit < MAX_SQRT_PRICE && sqrt_price_limit > self.sqrt_price, AmmError::InvalidPrice);
+        }
+
+        let mut remaining = amount_in;
+        let (mut amount_out, mut fee_amount) = (0u64, 0u64);
+        while remaining > 0 && self.sqrt_price != sqrt_price_limit {
+            let next_tick = self.next_tick(tick_arrays, x_to_y)?;
+            let next_sqrt_price = sqrt_price_at_tick(next_tick).map_err(AmmError::from)?;
+            let target = if x_to_y { next_sqrt_price.max(sqrt_price_limit) } else { next_sqrt_price.min(sqrt_price_limit) };
+
+            let step = compute_swap_step(self.sqrt_price, target, self.liquidity, remaining, self.fee, x_to_y)
+                .map_err(AmmError::from)?;
+            remaining = remaining
+                .checked_sub(step.amount_in + step.fee_amount)
+                .ok_or(AmmError::Underflow)?;
+            amount_out = amount_out.checked_add(step.amount_out).ok_or(AmmError::Overflow)?;
+            fee_amount = fee_amount.checked_add(step.fee_amount).ok_or(AmmError::Overflow)?;
+
+            // The fee is shared by the liquidity active during the step
+            let growth = fee_growth_delta(step.fee_amount, self.liquidity);
+            if x_to_y {
+                self.fee_growth_global_x = self.fee_growth_global_x.wrapping_add(growth);
+            } else {
+                self.fee_growth_global_y = self.fee_growth_global_y.wrapping_add(growth);
+            }
+
+            if step.sqrt_price_next == next_sqrt_price {
+                // Moving up, the next tick may be the first of the following array
+                let start = self.tick_array_start(next_tick);
+                let array = tick_arrays
+                    .iter_mut()
+                    .find(|array| array.start_tick == start)
+                    .ok_or(AmmError::InvalidTickArray)?;
+                let tick = array.tick_mut(next_tick, self.tick_spacing)?;
+                if tick.is_initialized() {
+                    let liquidity_net = tick.cross(self.fee_growth_global_x, self.fee_growth_global_y);
+                    // Moving down, liquidity that starts at the tick becomes inactive
+                    let delta = if x_to_y { -liquidity_net } else { liquidity_net };
+                    self.liquidity = add_liquidity_delta(self.liquidity, delta)?;
+                }
+                self.tick_current = if x_to_y { next_tick - 1 } else { next_tick };
+            } else {
+                self.tick_current = tick_at_sqrt_price(step.sqrt_price_next).map_err(AmmError::from)?;
+            }
+            self.sqrt_price = step.sqrt_price_next;
+        }
+
+        Ok(ClSwapResult { amount_in: amount_in - remaining, amount_out, fee_amount })
+    }
+
+    /// Finds the next initialized tick the price reaches moving down (`x_to_y`) or up from the current
+    /// tick, within the current tick array. If there is none, returns the edge of the array.
+    fn next_tick(&self, tick_arrays: &[&mut TickArray], x_to_y: bool) -> Result<i32> {
+        let start = self.tick_array_start(self.tick_current);
+        let array = tick_arrays
+            .iter()
+            .find(|array| array.start_tick == start)
+            .ok_or(AmmError::InvalidTickArray)?;
+
+        let spacing = self.tick_spacing as i32;
+        let offset = (self.tick_current - start).div_euclid(spacing);
+        let found = if x_to_y {
+            (0..=offset).rev().find(|&i| array.ticks[i as usize].is_initialized())
+        } else {
+            (offset + 1..TICK_ARRAY_SIZE as i32).find(|&i| array.ticks[i as usize].is_initialized())
+        };
+        Ok(match found {
+            Some(i) => start + i * spacing,
+            None if x_to_y => start.max(MIN_TICK),
+            None => (start + TICK_ARRAY_SIZE as i32 * spacing).min(MAX_TICK),
+        })
+    }
+}
+
+/// The edge of one or more position ranges.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct Tick {
+    /// Liquidity added to the active liquidity when the price crosses the tick upwards
+    /// (removed when crossing downwards).
+    pub liquidity_net: i128,
+    /// Total liquidity of the positions starting or ending at the tick. Zero if no position uses it.
+    pub liquidity_gross: u128,
+    /// Fee growth in token X on the other side of the tick from the current price (Q64.64).
+    pub fee_growth_outside_x: u128,
+    /// Fee growth in token Y on the other side of the tick from the current price (Q64.64).
+    pub fee_growth_outside_y: u128,
+}
+
+impl Tick {
+    pub fn is_initialized(&self) -> bool {
+        self.liquidity_gross > 0
+    }
+
+    /// Adds `liquidity_delta` from a position starting (or ending, if `is_upper`) at `tick`.
+    pub fn update(
+        &mut self,
+        tick: i32,
+        tick_current: i32,
+        liquidity_delta: i128,
+        is_upper: bool,
+        fee_growth_global_x: u128,
+        fee_growth_global_y: u128,
+    ) -> Result<()> {
+        if !self.is_initialized() {
+            // By convention, all fees so far were earned below the tick
+            if tick <= tick_current {
+                self.fee_growth_outside_x = fee_growth_global_x;
+                self.fee_growth_outside_y = fee_growth_global_y;
+            }
+        }
+        self.liquidity_gross = add_liquidity_delta(self.liquidity_gross, liquidity_delta)?;
+        let net_delta = if is_upper { liquidity_delta.checked_neg() } else { Some(liquidity_delta) };
+        self.liquidity_net = net_delta
+            .and_then(|delta| self.liquidity_net.checked_add(delta))
+            .ok_or(AmmError::Overflow)?;
+        Ok(())
+    }
+
+    /// Crosses the tick, flipping its fee growth to the other side. Returns its `liquidity_net`.
+    pub fn cross(&mut self, fee_growth_global_x: u128, fee_growth_global_y: u128) -> i128 {
+        self.fee_growth_outside_x = fee_growth_global_x.wrapping_sub(self.fee_growth_outside_x);
+        self.fee_growth_outside_y = fee_growth_global_y.wrapping_sub(self.fee_growth_outside_y);
+        self.liquidity_net
+    }
+}
+
+/// `TICK_ARRAY_SIZE` consecutive ticks of a pool, spaced by its tick spacing
+/// (PDA `["tick_array", pool, start_tick]`).
+#[account]
+#[derive(InitSpace)]
+pub struct TickArray {
+    /// The pool the ticks belong to.
+    pub pool: Pubkey,
+    /// The first tick in the array, a multiple of `tick_spacing * TICK_ARRAY_SIZE`.
+    pub start_tick: i32,
+    pub ticks: [Tick; TICK_ARRAY_SIZE],
+}
+
+impl TickArray {
+    /// Returns the index of `tick` in the array.
+    fn offset(&self, tick: i32, tick_spacing: u16) -> Result<usize> {
+        let spacing = tick_spacing as i32;
+        let offset = tick - self.start_tick;
+        require!(offset >= 0 && offset % spacing == 0, AmmError::InvalidTickArray);
+        let index = (offset / spacing) as usize;
+        require!(index < TICK_ARRAY_SIZE, AmmError::InvalidTickArray);
+        Ok(index)
+    }
+
+    pub fn tick_mut(&mut self, tick: i32, tick_spacing: u16) -> Result<&mut Tick> {
+        let index = self.offset(tick, tick_spacing)?;
+        Ok(&mut self.ticks[index])
+    }
+
+    /// Returns the ticks at `tick_lower` and `tick_upper`, both in this array.
+    pub fn tick_pair_mut(&mut self, tick_lower: i32, tick_upper: i32, tick_spacing: u16) -> Result<(&mut Tick, &mut Tick)> {
+        let lower = self.offset(tick_lower, tick_spacing)?;
+        let upper = self.offset(tick_upper, tick_spacing)?;
+        require!(lower < upper, AmmError::InvalidTick);
+        let (below, above) = self.ticks.split_at_mut(upper);
+        Ok((&mut below[lower], &mut above[0]))
+    }
+}
+
+/// Liquidity provided by `owner` over [tick_lower, tick_upper)
+/// (PDA `["cl_position", pool, owner, tick_lower, tick_upper]`).
+#[account]
+#[derive(InitSpace)]
+pub struct ClPosition {
+    pub owner: Pubkey,
+    pub pool: Pubkey,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    /// Fee growth inside the range in token X (Q64.64) when the position's fees were last accrued.
+    pub fee_growth_inside_last_x: u128,
+    /// Fee growth inside the range in token Y (Q64.64) when the position's fees were last accrued.
+    pub fee_growth_inside_last_y: u128,
+    /// Fees in token X accrued and not yet collected.
+    pub tokens_owed_x: u64,
+    /// Fees in token Y accrued and not yet collected.
+    pub tokens_owed_y: u64,
+    pub bump: u8,
+}
+
+impl ClPosition {
+    /// Accrues the fees earned since the last update at the current fee growth inside the range,
+    /// then adds `liquidity_delta`.
+    pub fn update(&mut self, liquidity_delta: i128, fee_growth_inside_x: u128, fee_growth_inside_y: u128) -> Result<()> {
+        let earned_x = fees_earned(self.liquidity, fee_growth_inside_x.wrapping_sub(self.fee_growth_inside_last_x));
+        let earned_y = fees_earned(self.liquidity, fee_growth_inside_y.wrapping_sub(self.fee_growth_inside_last_y));
+        self.tokens_owed_x = self.tokens_owed_x.saturating_add(earned_x);
+        self.tokens_owed_y = self.tokens_owed_y.saturating_add(earned_y);
+        self.fee_growth_inside_last_x = fee_growth_inside_x;
+        self.fee_growth_inside_last_y = fee_growth_inside_y;
+        self.liquidity = add_liquidity_delta(self.liquidity, liquidity_delta)?;
+        Ok(())
+    }
+}
+
+/// Applies a signed liquidity change, failing if it would remove more than there is.
+fn add_liquidity_delta(liquidity: u128, delta: i128) -> Result<u128> {
+    if delta >= 0 {
+        liquidity.checked_add(delta as u128).ok_or(error!(AmmError::Overflow))
+    } else {
+        liquidity.checked_sub(delta.unsigned_abs()).ok_or(error!(AmmError::InsufficientLiquidity))
+    }
+}
@@ -1,16 +1,392 @@
+use amm_quote::{ initial_liquidity, mul_div, quote_swap_exact_in, quote_swap_exact_out, Curve };
 use anchor_lang::prelude::*;
 
+use crate::{ constants::MAX_FEE_TIERS, error::AmmError };
 
+pub mod concentrated;
+
+pub use concentrated::*;
+
+/// The pricing curve a pool trades on, fixed at `initialize`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum CurveType {
+    /// x * y = k, for uncorrelated pairs.
+    ConstantProduct,
+    /// The StableSwap invariant with an amplification coefficient, for pegged pairs.
+    StableSwap,
+}
+
+/// When a signed user instruction stops being valid, checked against the `Clock` sysvar.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Expiry {
+    /// Valid up to and including this unix timestamp.
+    Timestamp(i64),
+    /// Valid up to and including this slot.
+    Slot(u64),
+}
+
+impl Expiry {
+    pub fn is_expired(&self, clock: &Clock) -> bool {
+        match *self {
+            Expiry::Timestamp(expires_at) => clock.unix_timestamp > expires_at,
+            Expiry::Slot(expires_at) => clock.slot > expires_at,
+        }
+    }
+}
+
+/// Fails with `AmmError::Expired` if `expires_at` is set and has passed.
+pub fn check_expiry(expires_at: Option<Expiry>) -> Result<()> {
+    if let Some(expiry) = expires_at {
+        require!(!expiry.is_expired(&Clock::get()?), AmmError::Expired);
+    }
+    Ok(())
+}
 
 #[account]
 #[derive(InitSpace)]
 pub struct Config {
     pub seed: u64,
     pub authority: Option<Pubkey>,
+    /// The account that paid for the pool's accounts, refunded when the pool is closed.
+    pub initializer: Pubkey,
     pub mint_x: Pubkey,
     pub mint_y: Pubkey,
     pub fee: u16,
+    /// Share of each swap fee kept by the protocol, in basis points of the fee.
+    pub protocol_fee: u16,
+    /// Unclaimed protocol fees held in `vault_x`, excluded from LP reserves.
+    pub protocol_fees_x: u64,
+    /// Unclaimed protocol fees held in `vault_y`, excluded from LP reserves.
+    pub protocol_fees_y: u64,
+    /// Owner of the token accounts `collect_protocol_fees` pays out to. The initializer until the authority changes it.
+    pub treasury: Pubkey,
     pub locked: bool,
     pub config_bump: u8,
     pub lp_bump: u8,
-}
\ No newline at end of file
+    /// Running sum of the price of X in Y (Q64.64) multiplied by seconds elapsed. Wraps on overflow.
+    pub price_x_cumulative: u128,
+    /// Running sum of the price of Y in X (Q64.64) multiplied by seconds elapsed. Wraps on overflow.
+    pub price_y_cumulative: u128,
+    /// Unix timestamp of the last oracle update.
+    pub last_update_ts: i64,
+    /// The pool's pricing curve.
+    pub curve: CurveType,
+    /// StableSwap amplification coefficient at the start of the current ramp. Zero for constant product pools.
+    pub amp_initial: u64,
+    /// StableSwap amplification coefficient at the end of the current ramp.
+    pub amp_target: u64,
+    /// Unix timestamp the current amplification ramp started.
+    pub amp_ramp_start_ts: i64,
+    /// Unix timestamp the current amplification ramp ends.
+    pub amp_ramp_stop_ts: i64,
+    /// The flash loan currently outstanding, if any. The pool rejects every other operation until it is repaid.
+    pub flash_loan: Option<OutstandingLoan>,
+    /// When set, swaps are charged a fee that follows recent volatility instead of `fee`.
+    pub dynamic_fee: Option<DynamicFee>,
+}
+
+/// A swap fee that rises with recent price movement, between `min_fee` and `max_fee`.
+///
+/// Every swap, including the internal swaps of `deposit_single` and `withdraw_single`, adds the relative
+/// price move it caused to `volatility`, which decays linearly back to zero over `decay_period` seconds.
+/// The fee scales linearly from `min_fee` with no recent movement up to `max_fee` once the accumulated
+/// movement reaches `max_volatility`. Flash loans are charged it too.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct DynamicFee {
+    /// Fee charged in calm markets, in basis points.
+    pub min_fee: u16,
+    /// Fee charged once `volatility` reaches `max_volatility`, in basis points.
+    pub max_fee: u16,
+    /// Accumulated price movement, in basis points, at which the fee reaches `max_fee`.
+    pub max_volatility: u64,
+    /// Seconds for the accumulator to decay from any value back to zero.
+    pub decay_period: i64,
+    /// Accumulated price movement in basis points as of `last_update_ts`, capped at `max_volatility`.
+    pub volatility: u64,
+    /// Unix timestamp the accumulator was last updated.
+    pub last_update_ts: i64,
+}
+
+impl DynamicFee {
+    /// Returns the accumulated price movement at `now`, after decay.
+    pub fn volatility_at(&self, now: i64) -> u64 {
+        let elapsed = now.saturating_sub(self.last_update_ts).max(0);
+        if elapsed >= self.decay_period {
+            return 0;
+        }
+        let remaining = (self.decay_period - elapsed) as u128;
+        (self.volatility as u128 * remaining / self.decay_period as u128) as u64
+    }
+
+    /// Returns the fee charged at `now`, in basis points.
+    pub fn fee_at(&self, now: i64) -> u16 {
+        let volatility = self.volatility_at(now).min(self.max_volatility) as u128;
+        let range = (self.max_fee - self.min_fee) as u128;
+        self.min_fee + (range * volatility / self.max_volatility as u128) as u16
+    }
+
+    /// Adds a price move of `price_move` basis points at `now` to the accumulator.
+    pub fn record(&mut self, price_move: u64, now: i64) {
+        self.volatility = self.volatility_at(now).saturating_add(price_move).min(self.max_volatility);
+        self.last_update_ts = now;
+    }
+}
+
+/// A flash loan that has been lent out but not yet repaid.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct OutstandingLoan {
+    /// Vault balance of token X before the loan.
+    pub balance_x: u64,
+    /// Vault balance of token Y before the loan.
+    pub balance_y: u64,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    /// Fee owed on top of `amount_x`, in token X.
+    pub fee_x: u64,
+    /// Fee owed on top of `amount_y`, in token Y.
+    pub fee_y: u64,
+}
+
+impl Config {
+    /// Ensures `signer` is the pool's update authority.
+    /// Pools created without an authority can never be updated.
+    pub fn check_authority(&self, signer: &Pubkey) -> Result<()> {
+        match self.authority {
+            Some(authority) => {
+                require_keys_eq!(authority, *signer, AmmError::InvalidAuthority);
+                Ok(())
+            }
+            None => err!(AmmError::NoAuthoritySet),
+        }
+    }
+
+    /// Returns the vault balances that belong to liquidity providers,
+    /// i.e. excluding protocol fees that have not been collected yet.
+    pub fn lp_reserves(&self, vault_x: u64, vault_y: u64) -> Result<(u64, u64)> {
+        let x = vault_x.checked_sub(self.protocol_fees_x).ok_or(AmmError::Underflow)?;
+        let y = vault_y.checked_sub(self.protocol_fees_y).ok_or(AmmError::Underflow)?;
+        Ok((x, y))
+    }
+
+    /// Records the protocol's share of `fee_amount`, charged in token X when `is_x`.
+    /// Returns the amount set aside for the protocol.
+    pub fn accrue_protocol_fee(&mut self, is_x: bool, fee_amount: u64) -> Result<u64> {
+        let share = (fee_amount as u128 * self.protocol_fee as u128 / 10_000) as u64;
+        let balance = if is_x { &mut self.protocol_fees_x } else { &mut self.protocol_fees_y };
+        *balance = balance.checked_add(share).ok_or(AmmError::Overflow)?;
+        Ok(share)
+    }
+
+    /// Returns the StableSwap amplification coefficient at `now`, interpolated linearly along the current ramp.
+    pub fn amp(&self, now: i64) -> u64 {
+        if now >= self.amp_ramp_stop_ts {
+            return self.amp_target;
+        }
+        if now <= self.amp_ramp_start_ts {
+            return self.amp_initial;
+        }
+        let elapsed = (now - self.amp_ramp_start_ts) as u128;
+        let duration = (self.amp_ramp_stop_ts - self.amp_ramp_start_ts) as u128;
+        if self.amp_target >= self.amp_initial {
+            self.amp_initial + ((self.amp_target - self.amp_initial) as u128 * elapsed / duration) as u64
+        } else {
+            self.amp_initial - ((self.amp_initial - self.amp_target) as u128 * elapsed / duration) as u64
+        }
+    }
+
+    /// Returns the pool's curve, with the amplification coefficient at `now`, for the quote library.
+    pub fn quote_curve(&self, now: i64) -> Curve {
+        match self.curve {
+            CurveType::ConstantProduct => Curve::ConstantProduct,
+            CurveType::StableSwap => Curve::StableSwap { amp: self.amp(now) },
+        }
+    }
+
+    /// Returns the swap fee charged at `now`, in basis points: the dynamic fee if enabled, otherwise `fee`.
+    pub fn effective_fee(&self, now: i64) -> u16 {
+        self.dynamic_fee.map_or(self.fee, |dynamic_fee| dynamic_fee.fee_at(now))
+    }
+
+    /// Feeds the price move between LP reserves `before` and `after` a swap, as `(x, y)` pairs,
+    /// into the dynamic fee's volatility accumulator. Does nothing if the dynamic fee is off.
+    pub fn record_volatility(&mut self, before: (u64, u64), after: (u64, u64), now: i64) {
+        if let Some(dynamic_fee) = self.dynamic_fee.as_mut() {
+            dynamic_fee.record(price_move_bps(before, after), now);
+        }
+    }
+
+    /// Prices an exact-input swap on the pool's curve. Returns `(amount_out, fee_amount)`.
+    pub fn get_amount_out(&self, amount_in: u64, reserve_in: u64, reserve_out: u64, now: i64) -> Result<(u64, u64)> {
+        let quote = quote_swap_exact_in(self.quote_curve(now), amount_in, reserve_in, reserve_out, self.effective_fee(now))
+            .map_err(AmmError::from)?;
+        Ok((quote.amount_out, quote.fee_amount))
+    }
+
+    /// Prices an exact-output swap on the pool's curve. Returns `(amount_in, fee_amount)`.
+    pub fn get_amount_in(&self, amount_out: u64, reserve_in: u64, reserve_out: u64, now: i64) -> Result<(u64, u64)> {
+        let quote = quote_swap_exact_out(self.quote_curve(now), amount_out, reserve_in, reserve_out, self.effective_fee(now))
+            .map_err(AmmError::from)?;
+        Ok((quote.amount_in, quote.fee_amount))
+    }
+
+    /// Returns the liquidity measure of reserves `x` and `y` used to size the first deposit:
+    /// sqrt(x * y) for constant product pools and the invariant D for StableSwap pools.
+    pub fn liquidity(&self, x: u64, y: u64, now: i64) -> Result<u64> {
+        Ok(initial_liquidity(self.quote_curve(now), x, y).map_err(AmmError::from)?)
+    }
+
+    /// Returns the pool's liquidity per LP token (Q64.64): sqrt(x * y) or D, divided by the LP supply.
+    /// Deposits and withdrawals leave it unchanged, while swap and flash loan fees grow the reserves
+    /// without minting LP, so its increase between two readings is the fee income per LP token.
+    /// On StableSwap pools an amplification ramp also moves D, and with it this value.
+    pub fn liquidity_per_lp(&self, reserve_x: u64, reserve_y: u64, lp_supply: u64, now: i64) -> Result<u128> {
+        if lp_supply == 0 || reserve_x == 0 || reserve_y == 0 {
+            return Ok(0);
+        }
+        let liquidity = self.liquidity(reserve_x, reserve_y, now)?;
+        Ok(((liquidity as u128) << 64) / lp_supply as u128)
+    }
+
+    /// Returns the cumulative prices as of `now`, extending the last values by the
+    /// spot price of `reserve_x` / `reserve_y` for the time elapsed since the last update.
+    /// A TWAP over a window is `(cumulative_end - cumulative_start) / (t_end - t_start)`,
+    /// using wrapping subtraction.
+    pub fn cumulative_prices(&self, reserve_x: u64, reserve_y: u64, now: i64) -> (u128, u128) {
+        let elapsed = now.saturating_sub(self.last_update_ts);
+        if elapsed <= 0 || reserve_x == 0 || reserve_y == 0 {
+            return (self.price_x_cumulative, self.price_y_cumulative);
+        }
+        let elapsed = elapsed as u128;
+        let price_x = ((reserve_y as u128) << 64) / reserve_x as u128;
+        let price_y = ((reserve_x as u128) << 64) / reserve_y as u128;
+        (
+            self.price_x_cumulative.wrapping_add(price_x.wrapping_mul(elapsed)),
+            self.price_y_cumulative.wrapping_add(price_y.wrapping_mul(elapsed)),
+        )
+    }
+
+    /// Accumulates the time-weighted prices up to `now`.
+    /// Must be called with the LP reserves from *before* an operation changes them.
+    pub fn update_oracle(&mut self, reserve_x: u64, reserve_y: u64, now: i64) {
+        let (price_x_cumulative, price_y_cumulative) = self.cumulative_prices(reserve_x, reserve_y, now);
+        self.price_x_cumulative = price_x_cumulative;
+        self.price_y_cumulative = price_y_cumulative;
+        self.last_update_ts = now;
+    }
+}
+
+/// Returns how far the price of X in Y moved between reserves `before` and `after`,
+/// in basis points of the price before (saturating).
+pub fn price_move_bps(before: (u64, u64), after: (u64, u64)) -> u64 {
+    if before.0 == 0 || before.1 == 0 || after.0 == 0 {
+        return 0;
+    }
+    let price_before = ((before.1 as u128) << 64) / before.0 as u128;
+    let price_after = ((after.1 as u128) << 64) / after.0 as u128;
+    mul_div(price_before.abs_diff(price_after), 10_000, price_before).map_or(u64::MAX, |bps| bps.min(u64::MAX as u128) as u64)
+}
+
+/// A liquidity provider's position in a pool (PDA `["position", config, owner]`), created on their
+/// first deposit. It tracks what was paid in for the LP minted through `deposit`, so fees and
+/// impermanent loss can be reported. LP tokens moved in or out by plain token transfers are not tracked.
+#[account]
+#[derive(InitSpace)]
+pub struct Position {
+    pub owner: Pubkey,
+    /// The pool's config PDA.
+    pub pool: Pubkey,
+    /// LP tokens minted to the owner by deposits, net of withdrawals.
+    pub lp_amount: u64,
+    /// Token X paid in for `lp_amount`.
+    pub cost_x: u64,
+    /// Token Y paid in for `lp_amount`.
+    pub cost_y: u64,
+    /// The pool's liquidity per LP token (Q64.64) at entry, averaged over deposits weighted by LP.
+    pub entry_liquidity_per_lp: u128,
+    pub bump: u8,
+}
+
+impl Position {
+    /// Adds a deposit of `amount_x` and `amount_y` for `lp_amount` LP, entered at `liquidity_per_lp`.
+    pub fn credit(&mut self, amount_x: u64, amount_y: u64, lp_amount: u64, liquidity_per_lp: u128) -> Result<()> {
+        let total = self.lp_amount.checked_add(lp_amount).ok_or(AmmError::Overflow)?;
+        if total > 0 {
+            // Move the average towards the new entry by the new LP's share of the total
+            let entry = self.entry_liquidity_per_lp;
+            let step = mul_div(entry.abs_diff(liquidity_per_lp), lp_amount as u128, total as u128).ok_or(AmmError::Overflow)?;
+            self.entry_liquidity_per_lp = if liquidity_per_lp >= entry { entry + step } else { entry - step };
+        }
+        self.lp_amount = total;
+        self.cost_x = self.cost_x.checked_add(amount_x).ok_or(AmmError::Overflow)?;
+        self.cost_y = self.cost_y.checked_add(amount_y).ok_or(AmmError::Overflow)?;
+        Ok(())
+    }
+
+    /// Removes `lp_amount` withdrawn LP, and the same share of the cost basis.
+    /// LP beyond what the position tracks (e.g. received by transfer) only empties it.
+    pub fn debit(&mut self, lp_amount: u64) {
+        let removed = lp_amount.min(self.lp_amount);
+        if removed == 0 {
+            return;
+        }
+        let remaining = (self.lp_amount - removed) as u128;
+        self.cost_x = (self.cost_x as u128 * remaining / self.lp_amount as u128) as u64;
+        self.cost_y = (self.cost_y as u128 * remaining / self.lp_amount as u128) as u64;
+        self.lp_amount -= removed;
+    }
+}
+
+/// The program-wide pool registry (PDA `["factory"]`).
+/// Every pool is registered here; pool seeds are assigned sequentially from `pool_count`,
+/// so pool `i` is always the config PDA `["config", i]`, or the `ClPool` PDA `["cl_pool", i]`
+/// for a concentrated pool.
+#[account]
+#[derive(InitSpace)]
+pub struct Factory {
+    /// May change the enabled fee tiers.
+    pub authority: Pubkey,
+    /// Number of pools created so far, and the seed of the next pool.
+    pub pool_count: u64,
+    /// Pool fees (in basis points) new pools may be created with.
+    #[max_len(MAX_FEE_TIERS)]
+    pub fee_tiers: Vec<u16>,
+    pub bump: u8,
+}
+
+impl Factory {
+    /// Ensures `signer` is the factory authority.
+    pub fn check_authority(&self, signer: &Pubkey) -> Result<()> {
+        require_keys_eq!(self.authority, *signer, AmmError::InvalidAuthority);
+        Ok(())
+    }
+}
+
+/// The canonical pool for a mint pair and fee tier
+/// (PDA `["pool", lower_mint, higher_mint, fee_tier]`, with the mints sorted by key).
+/// It is created alongside the pool, so there can only be one pool per pair and tier,
+/// and clients can find it from the mints alone, in either order.
+/// Concentrated pools have their own, under `["cl_canonical", lower_mint, higher_mint, fee_tier]`.
+#[account]
+#[derive(InitSpace)]
+pub struct CanonicalPool {
+    /// The pool's config PDA, or its `ClPool` PDA for a concentrated pool.
+    pub config: Pubkey,
+    /// The pool's seed, i.e. its index in the factory.
+    pub seed: u64,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    /// The fee tier the pool was created in. The pool's fee may be changed later, but its tier is fixed.
+    pub fee_tier: u16,
+    pub bump: u8,
+}
+
+impl CanonicalPool {
+    /// Returns the two mints ordered by key, as used in the canonical pool seeds.
+    pub fn sorted_mints(mint_a: Pubkey, mint_b: Pubkey) -> (Pubkey, Pubkey) {
+        if mint_a < mint_b {
+            (mint_a, mint_b)
+        } else {
+            (mint_b, mint_a)
+        }
+    }
+}
@@ -0,0 +1,35 @@
+// Token-2022 transfer-fee helpers shared by the AMM instructions.
+//
+// Mints with the transfer-fee extension withhold part of every transfer, so the amount that
+// leaves an account is not the amount that arrives. These helpers read the mint's fee config
+// for the current epoch; they return no fee for classic SPL mints and Token-2022 mints without
+// the extension.
+
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_2022::spl_token_2022::{
+        self,
+        extension::{ transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions },
+    },
+    token_interface::Mint,
+};
+
+use crate::error::AmmError;
+
+/// Returns how much of `mint` must be sent so that exactly `amount` arrives after the transfer fee.
+pub fn get_amount_with_transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID || amount == 0 {
+        return Ok(amount);
+    }
+
+    let data = mint_info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let fee = match state.get_extension::<TransferFeeConfig>() {
+        Ok(config) => config
+            .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(AmmError::Overflow)?,
+        Err(_) => 0,
+    };
+    amount.checked_add(fee).ok_or(error!(AmmError::Overflow))
+}
@@ -1,7 +1,7 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Amm } from "../target/types/amm";
-import { PublicKey, SystemProgram } from "@solana/web3.js";
+import { PublicKey, SystemProgram, SYSVAR_INSTRUCTIONS_PUBKEY, Transaction } from "@solana/web3.js";
 import {
   TOKEN_PROGRAM_ID,
   ASSOCIATED_TOKEN_PROGRAM_ID,
@@ -22,16 +22,34 @@ describe("amm", () => {
   let initializer: anchor.web3.Keypair;
   let user: anchor.web3.Keypair;
   let mintX: PublicKey, mintY: PublicKey;
-  let config: PublicKey, mintLp: PublicKey, vaultX: PublicKey, vaultY: PublicKey;
+  let factory: PublicKey;
+  let config: PublicKey, mintLp: PublicKey, lpLock: PublicKey, vaultX: PublicKey, vaultY: PublicKey;
   let seed: anchor.BN;
   let fee: number;
 
+  // Pool seeds are the factory's sequential pool index
+  const nextSeed = async () => (await program.account.factory.fetch(factory)).poolCount;
+
+  // Position PDA: ["position", config, owner]
+  const positionFor = (poolConfig: PublicKey, owner: PublicKey) =>
+    PublicKey.findProgramAddressSync([Buffer.from("position"), poolConfig.toBuffer(), owner.toBuffer()], program.programId)[0];
+
+  // Canonical pool PDA: ["pool", lower_mint, higher_mint, fee_tier]
+  const canonicalPoolFor = (mintA: PublicKey, mintB: PublicKey, feeTier: number) => {
+    const [lower, higher] = Buffer.compare(mintA.toBuffer(), mintB.toBuffer()) < 0 ? [mintA, mintB] : [mintB, mintA];
+    const feeBytes = Buffer.alloc(2);
+    feeBytes.writeUInt16LE(feeTier);
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("pool"), lower.toBuffer(), higher.toBuffer(), feeBytes],
+      program.programId
+    )[0];
+  };
+
   before(async () => {
     // Generate keypairs for the pool initializer and a user
     initializer = anchor.web3.Keypair.generate();
     user = anchor.web3.Keypair.generate();
-    seed = new anchor.BN(123456789); // Arbitrary pool seed
-    fee = 500; // Arbitrary fee (basis points)
+    fee = 500; // Arbitrary fee tier (basis points)
 
     // Fund both accounts with SOL for transactions
     for (const kp of [initializer, user]) {
@@ -46,6 +64,35 @@ describe("amm", () => {
     mintX = await createMint(anchor.getProvider().connection, initializer, initializer.publicKey, null, 6);
     mintY = await createMint(anchor.getProvider().connection, initializer, initializer.publicKey, null, 6);
 
+    // Factory PDA: ["factory"], created once with the fee tiers used by these tests.
+    // Only the program's upgrade authority, the wallet that deployed it, may create it.
+    [factory] = PublicKey.findProgramAddressSync([Buffer.from("factory")], program.programId);
+    const [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    const factoryAccounts = {
+      factory,
+      program: program.programId,
+      programData,
+      systemProgram: SystemProgram.programId,
+    };
+    try {
+      await program.methods
+        .initializeFactory([4, 30, fee])
+        .accounts({ ...factoryAccounts, authority: initializer.publicKey })
+        .signers([initializer])
+        .rpc();
+      throw new Error("Factory created by a non-upgrade authority");
+    } catch (err) {
+      if (!err.toString().includes("InvalidAuthority")) throw err;
+    }
+    await program.methods
+      .initializeFactory([4, 30, fee])
+      .accounts({ ...factoryAccounts, authority: anchor.getProvider().publicKey })
+      .rpc();
+    seed = await nextSeed();
+
     // Derive all PDAs as per the program's logic
     // Config PDA: ["config", seed]
     [config] = await PublicKey.findProgramAddress(
@@ -57,6 +104,11 @@ describe("amm", () => {
       [Buffer.from("lp"), config.toBuffer()],
       program.programId
     );
+    // LP lock PDA: ["lock", config], holds the permanently locked minimum liquidity
+    [lpLock] = await PublicKey.findProgramAddress(
+      [Buffer.from("lock"), config.toBuffer()],
+      program.programId
+    );
     // Vaults: ATAs owned by config PDA
     vaultX = await getAssociatedTokenAddress(mintX, config, true);
     vaultY = await getAssociatedTokenAddress(mintY, config, true);
@@ -65,12 +117,15 @@ describe("amm", () => {
   it("Should initialize AMM pool successfully", async () => {
     // The pool initializer sets up the config, LP mint, and vaults in a single transaction
     await program.methods
-      .initialize(seed, fee, null)
+      .initialize(seed, fee, initializer.publicKey, { constantProduct: {} }, new anchor.BN(0))
       .accounts({
         initializer: initializer.publicKey,
         mintX,
         mintY,
+        factory,
+        canonicalPool: canonicalPoolFor(mintX, mintY, fee),
         mintLp,
+        lpLock,
         config,
         vaultX,
         vaultY,
@@ -82,6 +137,50 @@ describe("amm", () => {
       .rpc();
   });
 
+  it("Should reject a second pool for the same pair and fee tier", async () => {
+    // The pair given the other way round still maps to the same canonical pool
+    const duplicateSeed = await nextSeed();
+    const [duplicateConfig] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config"), duplicateSeed.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [duplicateMintLp] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lp"), duplicateConfig.toBuffer()],
+      program.programId
+    );
+    const [duplicateLpLock] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lock"), duplicateConfig.toBuffer()],
+      program.programId
+    );
+    try {
+      await program.methods
+        .initialize(duplicateSeed, fee, null, { constantProduct: {} }, new anchor.BN(0))
+        .accounts({
+          initializer: initializer.publicKey,
+          mintX: mintY,
+          mintY: mintX,
+          factory,
+          canonicalPool: canonicalPoolFor(mintY, mintX, fee),
+          mintLp: duplicateMintLp,
+          lpLock: duplicateLpLock,
+          config: duplicateConfig,
+          vaultX: await getAssociatedTokenAddress(mintY, duplicateConfig, true),
+          vaultY: await getAssociatedTokenAddress(mintX, duplicateConfig, true),
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([initializer])
+        .rpc();
+      throw new Error("Duplicate pool was created");
+    } catch (err) {
+      if (err.message === "Duplicate pool was created") throw err;
+    }
+
+    const canonical = await program.account.canonicalPool.fetch(canonicalPoolFor(mintY, mintX, fee));
+    if (!canonical.config.equals(config)) throw new Error("Canonical pool points at the wrong config");
+  });
+
   it("Should deposit initial liquidity successfully", async () => {
     // User creates ATAs for X, Y, and LP tokens
     const userAtaX = (await getOrCreateAssociatedTokenAccount(
@@ -109,7 +208,7 @@ describe("amm", () => {
 
     // Deposit: user provides X and Y, receives LP tokens
     await program.methods
-      .deposit(new anchor.BN(100_000), new anchor.BN(100_000), new anchor.BN(200_000))
+      .deposit(new anchor.BN(100_000), new anchor.BN(100_000), new anchor.BN(200_000), null)
       .accounts({
         user: user.publicKey,
         mintX,
@@ -118,15 +217,23 @@ describe("amm", () => {
         vaultX,
         vaultY,
         mintLp,
+        lpLock,
         userX: userAtaX,
         userY: userAtaY,
         userLp: userAtaLp,
+        position: positionFor(config, user.publicKey),
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
       })
       .signers([user])
       .rpc();
+
+    // sqrt(100_000 * 200_000) LP was minted, with the minimum liquidity locked in the pool
+    const locked = BigInt((await program.provider.connection.getTokenAccountBalance(lpLock)).value.amount);
+    const minted = BigInt((await program.provider.connection.getTokenAccountBalance(userAtaLp)).value.amount);
+    if (locked !== BigInt(1_000)) throw new Error("Minimum liquidity was not locked");
+    if (minted !== BigInt(141_421 - 1_000)) throw new Error(`Unexpected first-deposit LP: ${minted}`);
   });
 
   it("Should swap X for Y successfully", async () => {
@@ -141,7 +248,7 @@ describe("amm", () => {
     // Swap X for Y (xToY = true)
     // Only use account names required by the Anchor-generated types
     await program.methods
-      .swap(new anchor.BN(50_000), new anchor.BN(1), true)
+      .swap(new anchor.BN(50_000), new anchor.BN(1), true, null)
       .accounts({
         user: user.publicKey,
         mintX,
@@ -174,7 +281,7 @@ describe("amm", () => {
 
     // Swap Y for X (xToY = false)
     await program.methods
-      .swap(new anchor.BN(50_000), new anchor.BN(1), false)
+      .swap(new anchor.BN(50_000), new anchor.BN(1), false, null)
       .accounts({
         user: user.publicKey,
         mintX,
@@ -196,6 +303,727 @@ describe("amm", () => {
     if (xAfter <= xBefore) throw new Error("Swap did not increase X balance");
   });
 
+  it("Should reject a swap after its expiry", async () => {
+    const connection = program.provider.connection;
+    const swapAccounts = {
+      user: user.publicKey,
+      mintX,
+      mintY,
+      config,
+      vaultX,
+      vaultY,
+      userX: await getAssociatedTokenAddress(mintX, user.publicKey),
+      userY: await getAssociatedTokenAddress(mintY, user.publicKey),
+      tokenProgram: TOKEN_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    };
+
+    try {
+      await program.methods
+        .swap(new anchor.BN(1_000), new anchor.BN(1), true, { timestamp: [new anchor.BN(1)] })
+        .accounts(swapAccounts)
+        .signers([user])
+        .rpc();
+      throw new Error("Expired swap should have failed");
+    } catch (e) {
+      if (!e.toString().includes("Expired")) throw e;
+    }
+
+    // Still valid until a slot well in the future
+    const slot = await connection.getSlot();
+    await program.methods
+      .swap(new anchor.BN(1_000), new anchor.BN(1), true, { slot: [new anchor.BN(slot + 1_000)] })
+      .accounts(swapAccounts)
+      .signers([user])
+      .rpc();
+  });
+
+  it("Should observe the TWAP oracle", async () => {
+    const observation = await program.methods
+      .observe()
+      .accounts({ mintX, mintY, config, vaultX, vaultY, tokenProgram: TOKEN_PROGRAM_ID })
+      .view();
+
+    const configAccount = await program.account.config.fetch(config);
+    if (observation.timestamp.lt(configAccount.lastUpdateTs)) throw new Error("Observation is older than the last update");
+    if (observation.reserveX.isZero() || observation.reserveY.isZero()) throw new Error("Observation has no reserves");
+  });
+
+  it("Should swap X for an exact amount of Y", async () => {
+    const userAtaX = await getAssociatedTokenAddress(mintX, user.publicKey);
+    const userAtaY = await getAssociatedTokenAddress(mintY, user.publicKey);
+    const yBefore = BigInt((await program.provider.connection.getTokenAccountBalance(userAtaY)).value.amount);
+
+    // Receive exactly 1_000 Y, paying at most 10_000 X
+    await program.methods
+      .swapExactOut(new anchor.BN(1_000), new anchor.BN(10_000), true, null)
+      .accounts({
+        user: user.publicKey,
+        mintX,
+        mintY,
+        config,
+        vaultX,
+        vaultY,
+        userX: userAtaX,
+        userY: userAtaY,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    const yAfter = BigInt((await program.provider.connection.getTokenAccountBalance(userAtaY)).value.amount);
+    if (yAfter - yBefore !== BigInt(1_000)) throw new Error("Exact-out swap did not deliver the requested amount");
+
+    // A maximum input of 1 can never buy 1_000 Y
+    try {
+      await program.methods
+        .swapExactOut(new anchor.BN(1_000), new anchor.BN(1), true, null)
+        .accounts({
+          user: user.publicKey,
+          mintX,
+          mintY,
+          config,
+          vaultX,
+          vaultY,
+          userX: userAtaX,
+          userY: userAtaY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+      throw new Error("Exact-out swap should have failed due to slippage");
+    } catch (e) {
+      if (!e.toString().includes("SlippageExceeded")) throw e;
+    }
+  });
+
+  it("Should swap X for Z through a two-hop route", async () => {
+    const connection = anchor.getProvider().connection;
+
+    // A second pool pairing Y with a new token Z
+    const mintZ = await createMint(connection, initializer, initializer.publicKey, null, 6);
+    const seedYZ = await nextSeed();
+    const [configYZ] = await PublicKey.findProgramAddress(
+      [Buffer.from("config"), seedYZ.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [mintLpYZ] = await PublicKey.findProgramAddress(
+      [Buffer.from("lp"), configYZ.toBuffer()],
+      program.programId
+    );
+    const [lpLockYZ] = await PublicKey.findProgramAddress(
+      [Buffer.from("lock"), configYZ.toBuffer()],
+      program.programId
+    );
+    const vaultYZ_Y = await getAssociatedTokenAddress(mintY, configYZ, true);
+    const vaultYZ_Z = await getAssociatedTokenAddress(mintZ, configYZ, true);
+
+    await program.methods
+      .initialize(seedYZ, 30, null, { constantProduct: {} }, new anchor.BN(0))
+      .accounts({
+        initializer: initializer.publicKey,
+        mintX: mintY,
+        mintY: mintZ,
+        factory,
+        canonicalPool: canonicalPoolFor(mintY, mintZ, 30),
+        mintLp: mintLpYZ,
+        lpLock: lpLockYZ,
+        config: configYZ,
+        vaultX: vaultYZ_Y,
+        vaultY: vaultYZ_Z,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([initializer])
+      .rpc();
+
+    const userAtaX = await getAssociatedTokenAddress(mintX, user.publicKey);
+    const userAtaY = await getAssociatedTokenAddress(mintY, user.publicKey);
+    const userAtaZ = (await getOrCreateAssociatedTokenAccount(connection, user, mintZ, user.publicKey)).address;
+    const userAtaLpYZ = await getAssociatedTokenAddress(mintLpYZ, user.publicKey);
+    await mintTo(connection, initializer, mintZ, userAtaZ, initializer, 500_000);
+
+    await program.methods
+      .deposit(new anchor.BN(1), new anchor.BN(100_000), new anchor.BN(100_000), null)
+      .accounts({
+        user: user.publicKey,
+        mintX: mintY,
+        mintY: mintZ,
+        config: configYZ,
+        vaultX: vaultYZ_Y,
+        vaultY: vaultYZ_Z,
+        mintLp: mintLpYZ,
+        lpLock: lpLockYZ,
+        userX: userAtaY,
+        userY: userAtaZ,
+        userLp: userAtaLpYZ,
+        position: positionFor(configYZ, user.publicKey),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    const zBefore = BigInt((await connection.getTokenAccountBalance(userAtaZ)).value.amount);
+
+    // X -> Y in the main pool, then Y -> Z in the new pool
+    await program.methods
+      .swapRoute(new anchor.BN(5_000), new anchor.BN(1), null)
+      .accounts({
+        user: user.publicKey,
+        mintIn: mintX,
+        userSrc: userAtaX,
+        userDst: userAtaZ,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts([
+        { pubkey: config, isSigner: false, isWritable: true },
+        { pubkey: vaultX, isSigner: false, isWritable: true },
+        { pubkey: vaultY, isSigner: false, isWritable: true },
+        { pubkey: mintY, isSigner: false, isWritable: false },
+        { pubkey: configYZ, isSigner: false, isWritable: true },
+        { pubkey: vaultYZ_Y, isSigner: false, isWritable: true },
+        { pubkey: vaultYZ_Z, isSigner: false, isWritable: true },
+        { pubkey: mintZ, isSigner: false, isWritable: false },
+      ])
+      .signers([user])
+      .rpc();
+
+    const zAfter = BigInt((await connection.getTokenAccountBalance(userAtaZ)).value.amount);
+    if (zAfter <= zBefore) throw new Error("Route did not deliver token Z");
+  });
+
+  it("Should swap a pegged pair on a StableSwap pool", async () => {
+    const connection = anchor.getProvider().connection;
+
+    // A StableSwap pool pairing two new pegged tokens A and B
+    const mintA = await createMint(connection, initializer, initializer.publicKey, null, 6);
+    const mintB = await createMint(connection, initializer, initializer.publicKey, null, 6);
+    const seedAB = await nextSeed();
+    const [configAB] = await PublicKey.findProgramAddress(
+      [Buffer.from("config"), seedAB.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [mintLpAB] = await PublicKey.findProgramAddress(
+      [Buffer.from("lp"), configAB.toBuffer()],
+      program.programId
+    );
+    const [lpLockAB] = await PublicKey.findProgramAddress(
+      [Buffer.from("lock"), configAB.toBuffer()],
+      program.programId
+    );
+    const vaultAB_A = await getAssociatedTokenAddress(mintA, configAB, true);
+    const vaultAB_B = await getAssociatedTokenAddress(mintB, configAB, true);
+
+    await program.methods
+      .initialize(seedAB, 4, initializer.publicKey, { stableSwap: {} }, new anchor.BN(100))
+      .accounts({
+        initializer: initializer.publicKey,
+        mintX: mintA,
+        mintY: mintB,
+        factory,
+        canonicalPool: canonicalPoolFor(mintA, mintB, 4),
+        mintLp: mintLpAB,
+        lpLock: lpLockAB,
+        config: configAB,
+        vaultX: vaultAB_A,
+        vaultY: vaultAB_B,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([initializer])
+      .rpc();
+
+    const userAtaA = (await getOrCreateAssociatedTokenAccount(connection, user, mintA, user.publicKey)).address;
+    const userAtaB = (await getOrCreateAssociatedTokenAccount(connection, user, mintB, user.publicKey)).address;
+    const userAtaLpAB = await getAssociatedTokenAddress(mintLpAB, user.publicKey);
+    await mintTo(connection, initializer, mintA, userAtaA, initializer, 200_000);
+    await mintTo(connection, initializer, mintB, userAtaB, initializer, 200_000);
+
+    await program.methods
+      .deposit(new anchor.BN(1), new anchor.BN(100_000), new anchor.BN(100_000), null)
+      .accounts({
+        user: user.publicKey,
+        mintX: mintA,
+        mintY: mintB,
+        config: configAB,
+        vaultX: vaultAB_A,
+        vaultY: vaultAB_B,
+        mintLp: mintLpAB,
+        lpLock: lpLockAB,
+        userX: userAtaA,
+        userY: userAtaB,
+        userLp: userAtaLpAB,
+        position: positionFor(configAB, user.publicKey),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    // The first StableSwap deposit mints D = x + y for a balanced pool
+    const lp = await connection.getTokenAccountBalance(userAtaLpAB);
+    if (lp.value.amount !== (200_000 - 1_000).toString()) throw new Error("Unexpected StableSwap LP amount");
+
+    const bBefore = BigInt((await connection.getTokenAccountBalance(userAtaB)).value.amount);
+    await program.methods
+      .swap(new anchor.BN(10_000), new anchor.BN(1), true, null)
+      .accounts({
+        user: user.publicKey,
+        mintX: mintA,
+        mintY: mintB,
+        config: configAB,
+        vaultX: vaultAB_A,
+        vaultY: vaultAB_B,
+        userX: userAtaA,
+        userY: userAtaB,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+    const bAfter = BigInt((await connection.getTokenAccountBalance(userAtaB)).value.amount);
+
+    // x*y=k would pay about 9_087 here; the StableSwap curve stays close to 1:1
+    if (bAfter - bBefore < 9_900n) throw new Error("StableSwap slippage is too high");
+
+    // Amplification ramps must last at least a day
+    const now = Math.floor(Date.now() / 1000);
+    try {
+      await program.methods
+        .rampAmp(new anchor.BN(200), new anchor.BN(now + 60))
+        .accounts({ authority: initializer.publicKey, config: configAB })
+        .signers([initializer])
+        .rpc();
+      throw new Error("Short amplification ramp should have failed");
+    } catch (e) {
+      if (!e.toString().includes("InvalidRamp")) throw e;
+    }
+  });
+
+  it("Should list the pools registered in the factory", async () => {
+    const poolCount = await nextSeed();
+    const configs = [];
+    for (let i = 0; i < poolCount.toNumber(); i++) {
+      const [poolConfig] = PublicKey.findProgramAddressSync(
+        [Buffer.from("config"), new anchor.BN(i).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      configs.push({ pubkey: poolConfig, isSigner: false, isWritable: false });
+    }
+
+    const page = await program.methods
+      .listPools(new anchor.BN(0))
+      .accounts({ factory })
+      .remainingAccounts(configs)
+      .view();
+    if (!page.poolCount.eq(poolCount)) throw new Error("Unexpected pool count");
+    if (page.pools.length !== configs.length) throw new Error("Not every pool was listed");
+    const listed = page.pools.find((pool) => pool.pool.equals(config));
+    if (!listed || !listed.mintX.equals(mintX) || !listed.mintY.equals(mintY)) {
+      throw new Error("The first pool is missing from the listing");
+    }
+  });
+
+  it("Should deposit liquidity from token X only", async () => {
+    const userAtaX = await getAssociatedTokenAddress(mintX, user.publicKey);
+    const userAtaY = await getAssociatedTokenAddress(mintY, user.publicKey);
+    const userAtaLp = await getAssociatedTokenAddress(mintLp, user.publicKey);
+    const yBefore = BigInt((await program.provider.connection.getTokenAccountBalance(userAtaY)).value.amount);
+    const lpBefore = BigInt((await program.provider.connection.getTokenAccountBalance(userAtaLp)).value.amount);
+
+    const sig = await program.methods
+      .depositSingle(true, new anchor.BN(20_000), new anchor.BN(1), null)
+      .accounts({
+        user: user.publicKey,
+        mintX,
+        mintY,
+        config,
+        vaultX,
+        vaultY,
+        mintLp,
+        lpLock,
+        userX: userAtaX,
+        userY: userAtaY,
+        userLp: userAtaLp,
+        position: positionFor(config, user.publicKey),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+
+    // LP was minted and no Y was taken from the user
+    const yAfter = BigInt((await program.provider.connection.getTokenAccountBalance(userAtaY)).value.amount);
+    const lpAfter = BigInt((await program.provider.connection.getTokenAccountBalance(userAtaLp)).value.amount);
+    if (lpAfter <= lpBefore) throw new Error("Single-sided deposit did not mint LP tokens");
+    if (yAfter !== yBefore) throw new Error("Single-sided deposit should not take token Y");
+
+    // The LiquidityAdded event carries the pool state after the deposit
+    const tx = await program.provider.connection.getTransaction(sig, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+    const added = [...parser.parseLogs(tx.meta.logMessages)].find((e) => e.name === "liquidityAdded");
+    if (!added) throw new Error("LiquidityAdded event not emitted");
+    const supply = (await program.provider.connection.getTokenSupply(mintLp)).value.amount;
+    if (!added.data.seed.eq(seed)) throw new Error("Event has the wrong pool seed");
+    if (!added.data.amountY.isZero()) throw new Error("Event should record no token Y");
+    if (added.data.lpAmount.toString() !== (lpAfter - lpBefore).toString()) throw new Error("Event LP amount mismatch");
+    if (added.data.lpSupply.toString() !== supply) throw new Error("Event LP supply mismatch");
+  });
+
+  it("Should lend and repay a flash loan within one transaction", async () => {
+    const connection = program.provider.connection;
+    const userAtaX = await getAssociatedTokenAddress(mintX, user.publicKey);
+    const userAtaY = await getAssociatedTokenAddress(mintY, user.publicKey);
+    const accounts = {
+      user: user.publicKey,
+      mintX,
+      mintY,
+      config,
+      vaultX,
+      vaultY,
+      userX: userAtaX,
+      userY: userAtaY,
+      instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+    const vaultBefore = BigInt((await connection.getTokenAccountBalance(vaultX)).value.amount);
+
+    // A loan without a matching repayment later in the transaction is rejected
+    try {
+      await program.methods.flashLoan(new anchor.BN(10_000), new anchor.BN(0)).accounts(accounts).signers([user]).rpc();
+      throw new Error("Unrepaid flash loan succeeded");
+    } catch (err) {
+      if (!err.toString().includes("FlashLoanNotRepaid")) throw err;
+    }
+
+    // The TWAP oracle can't be read from the drained vaults mid-loan
+    try {
+      const observeTx = new Transaction().add(
+        await program.methods.flashLoan(new anchor.BN(10_000), new anchor.BN(0)).accounts(accounts).instruction(),
+        await program.methods
+          .observe()
+          .accounts({ mintX, mintY, config, vaultX, vaultY, tokenProgram: TOKEN_PROGRAM_ID })
+          .instruction(),
+        await program.methods.flashRepay().accounts(accounts).instruction()
+      );
+      await anchor.web3.sendAndConfirmTransaction(connection, observeTx, [user]);
+      throw new Error("Observe during a flash loan succeeded");
+    } catch (err) {
+      if (!err.toString().includes("FlashLoanActive")) throw err;
+    }
+
+    const tx = new Transaction().add(
+      await program.methods.flashLoan(new anchor.BN(10_000), new anchor.BN(0)).accounts(accounts).instruction(),
+      await program.methods.flashRepay().accounts(accounts).instruction()
+    );
+    await anchor.web3.sendAndConfirmTransaction(connection, tx, [user]);
+
+    // The vault got the principal back plus the 5% pool fee, and the pool is open again
+    const vaultAfter = BigInt((await connection.getTokenAccountBalance(vaultX)).value.amount);
+    if (vaultAfter !== vaultBefore + 500n) throw new Error("Flash loan fee was not paid into the vault");
+    const configAccount = await program.account.config.fetch(config);
+    if (configAccount.flashLoan !== null) throw new Error("Flash loan still outstanding");
+  });
+
+  it("Should report the position's fees and impermanent loss", async () => {
+    const positionInfo = () =>
+      program.methods.positionInfo().accounts({
+        mintX,
+        mintY,
+        config,
+        vaultX,
+        vaultY,
+        mintLp,
+        position: positionFor(config, user.publicKey),
+        tokenProgram: TOKEN_PROGRAM_ID,
+      });
+    const report = await positionInfo().view();
+
+    // The user's deposits are tracked, and the swaps and flash loan so far have earned fees
+    const userAtaLp = await getAssociatedTokenAddress(mintLp, user.publicKey);
+    const lp = (await program.provider.connection.getTokenAccountBalance(userAtaLp)).value.amount;
+    if (report.lpAmount.toString() !== lp) throw new Error("Position LP does not match the user's LP balance");
+    if (report.feesX.isZero() && report.feesY.isZero()) throw new Error("No fees reported");
+    if (report.costX.isZero() || report.costY.isZero()) throw new Error("Cost basis not recorded");
+
+    // Mid-loan the vaults are drained, so no report is given
+    const userAtaX = await getAssociatedTokenAddress(mintX, user.publicKey);
+    const userAtaY = await getAssociatedTokenAddress(mintY, user.publicKey);
+    const loanAccounts = {
+      user: user.publicKey,
+      mintX,
+      mintY,
+      config,
+      vaultX,
+      vaultY,
+      userX: userAtaX,
+      userY: userAtaY,
+      instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+    try {
+      const tx = new Transaction().add(
+        await program.methods.flashLoan(new anchor.BN(10_000), new anchor.BN(0)).accounts(loanAccounts).instruction(),
+        await positionInfo().instruction(),
+        await program.methods.flashRepay().accounts(loanAccounts).instruction()
+      );
+      await anchor.web3.sendAndConfirmTransaction(program.provider.connection, tx, [user]);
+      throw new Error("Position info during a flash loan succeeded");
+    } catch (err) {
+      if (!err.toString().includes("FlashLoanActive")) throw err;
+    }
+  });
+
+  it("Should reject admin instructions from a non-authority", async () => {
+    try {
+      await program.methods
+        .lock()
+        .accounts({ authority: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      throw new Error("Lock by non-authority should have failed");
+    } catch (e) {
+      if (!e.toString().includes("InvalidAuthority")) throw e;
+    }
+  });
+
+  it("Should lock and unlock the pool", async () => {
+    await program.methods
+      .lock()
+      .accounts({ authority: initializer.publicKey, config })
+      .signers([initializer])
+      .rpc();
+
+    // Swaps are rejected while the pool is locked
+    const userAtaX = await getAssociatedTokenAddress(mintX, user.publicKey);
+    const userAtaY = await getAssociatedTokenAddress(mintY, user.publicKey);
+    try {
+      await program.methods
+        .swap(new anchor.BN(1_000), new anchor.BN(1), true, null)
+        .accounts({
+          user: user.publicKey,
+          mintX,
+          mintY,
+          config,
+          vaultX,
+          vaultY,
+          userX: userAtaX,
+          userY: userAtaY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+      throw new Error("Swap on a locked pool should have failed");
+    } catch (e) {
+      if (!e.toString().includes("PoolLocked")) throw e;
+    }
+
+    await program.methods
+      .unlock()
+      .accounts({ authority: initializer.publicKey, config })
+      .signers([initializer])
+      .rpc();
+    if ((await program.account.config.fetch(config)).locked) throw new Error("Pool is still locked");
+  });
+
+  it("Should update the pool fee", async () => {
+    await program.methods
+      .updateFee(300)
+      .accounts({ authority: initializer.publicKey, config })
+      .signers([initializer])
+      .rpc();
+    if ((await program.account.config.fetch(config)).fee !== 300) throw new Error("Fee was not updated");
+  });
+
+  it("Should raise the dynamic fee after a volatile swap", async () => {
+    // 0.1% in calm markets, up to 5% once the price has moved 10% recently
+    await program.methods
+      .setDynamicFee(10, 500, new anchor.BN(1_000), new anchor.BN(3_600))
+      .accounts({ authority: initializer.publicKey, config })
+      .signers([initializer])
+      .rpc();
+
+    const userAtaX = await getAssociatedTokenAddress(mintX, user.publicKey);
+    const userAtaY = await getAssociatedTokenAddress(mintY, user.publicKey);
+    await mintTo(anchor.getProvider().connection, initializer, mintX, userAtaX, initializer, 100_000);
+    const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+
+    const swapFee = async () => {
+      const sig = await program.methods
+        .swap(new anchor.BN(20_000), new anchor.BN(1), true, null)
+        .accounts({
+          user: user.publicKey,
+          mintX,
+          mintY,
+          config,
+          vaultX,
+          vaultY,
+          userX: userAtaX,
+          userY: userAtaY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc({ commitment: "confirmed" });
+      const tx = await program.provider.connection.getTransaction(sig, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      const swapped = [...parser.parseLogs(tx.meta.logMessages)].find((e) => e.name === "swapEvent");
+      if (!swapped) throw new Error("SwapEvent not emitted");
+      return swapped.data.fee as number;
+    };
+
+    // The first swap is charged the minimum; the price move it causes raises the next swap's fee
+    const calmFee = await swapFee();
+    if (calmFee !== 10) throw new Error(`Expected the minimum fee, got ${calmFee}`);
+    const volatileFee = await swapFee();
+    if (volatileFee <= calmFee) throw new Error("Dynamic fee did not rise after a volatile swap");
+
+    // Back to the static fee
+    await program.methods
+      .disableDynamicFee()
+      .accounts({ authority: initializer.publicKey, config })
+      .signers([initializer])
+      .rpc();
+    if ((await program.account.config.fetch(config)).dynamicFee !== null) throw new Error("Dynamic fee still enabled");
+  });
+
+  it("Should accrue and collect protocol fees", async () => {
+    // Route 20% of every swap fee to the protocol
+    await program.methods
+      .updateProtocolFee(2_000)
+      .accounts({ authority: initializer.publicKey, config })
+      .signers([initializer])
+      .rpc();
+
+    const userAtaX = await getAssociatedTokenAddress(mintX, user.publicKey);
+    const userAtaY = await getAssociatedTokenAddress(mintY, user.publicKey);
+    await program.methods
+      .swap(new anchor.BN(10_000), new anchor.BN(1), true, null)
+      .accounts({
+        user: user.publicKey,
+        mintX,
+        mintY,
+        config,
+        vaultX,
+        vaultY,
+        userX: userAtaX,
+        userY: userAtaY,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    // 10_000 * 3% fee * 20% protocol share
+    const accrued = (await program.account.config.fetch(config)).protocolFeesX;
+    if (accrued.toNumber() !== 60) throw new Error(`Unexpected protocol fees: ${accrued}`);
+
+    const treasuryX = (await getOrCreateAssociatedTokenAccount(
+      anchor.getProvider().connection,
+      initializer,
+      mintX,
+      initializer.publicKey
+    )).address;
+    const treasuryY = (await getOrCreateAssociatedTokenAccount(
+      anchor.getProvider().connection,
+      initializer,
+      mintY,
+      initializer.publicKey
+    )).address;
+
+    const collectAccounts = {
+      authority: initializer.publicKey,
+      mintX,
+      mintY,
+      config,
+      vaultX,
+      vaultY,
+      treasuryX,
+      treasuryY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    // Fees only go to token accounts owned by the pool's treasury, the initializer by default
+    try {
+      await program.methods
+        .collectProtocolFees()
+        .accounts({ ...collectAccounts, treasuryX: userAtaX })
+        .signers([initializer])
+        .rpc();
+      throw new Error("Fees were sent outside the treasury");
+    } catch (e) {
+      if (!e.toString().includes("InvalidTreasury")) throw e;
+    }
+
+    await program.methods
+      .collectProtocolFees()
+      .accounts(collectAccounts)
+      .signers([initializer])
+      .rpc();
+
+    const treasuryBalance = BigInt((await program.provider.connection.getTokenAccountBalance(treasuryX)).value.amount);
+    if (treasuryBalance !== BigInt(60)) throw new Error("Treasury did not receive protocol fees");
+    if ((await program.account.config.fetch(config)).protocolFeesX.toNumber() !== 0) {
+      throw new Error("Protocol fees were not reset");
+    }
+  });
+
+  it("Should withdraw liquidity into token Y only", async () => {
+    const userAtaX = await getAssociatedTokenAddress(mintX, user.publicKey);
+    const userAtaY = await getAssociatedTokenAddress(mintY, user.publicKey);
+    const userAtaLp = await getAssociatedTokenAddress(mintLp, user.publicKey);
+    const xBefore = BigInt((await program.provider.connection.getTokenAccountBalance(userAtaX)).value.amount);
+    const yBefore = BigInt((await program.provider.connection.getTokenAccountBalance(userAtaY)).value.amount);
+
+    await program.methods
+      .withdrawSingle(new anchor.BN(10_000), false, new anchor.BN(1), null)
+      .accounts({
+        user: user.publicKey,
+        mintX,
+        mintY,
+        config,
+        vaultX,
+        vaultY,
+        mintLp,
+        userX: userAtaX,
+        userY: userAtaY,
+        userLp: userAtaLp,
+        position: positionFor(config, user.publicKey),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    // Only token Y was paid out
+    const xAfter = BigInt((await program.provider.connection.getTokenAccountBalance(userAtaX)).value.amount);
+    const yAfter = BigInt((await program.provider.connection.getTokenAccountBalance(userAtaY)).value.amount);
+    if (yAfter <= yBefore) throw new Error("Single-sided withdraw did not pay out Y");
+    if (xAfter !== xBefore) throw new Error("Single-sided withdraw should not pay out X");
+  });
+
   it("Should withdraw liquidity successfully", async () => {
     // User withdraws all LP tokens for their share of X and Y
     const userAtaX = await getAssociatedTokenAddress(mintX, user.publicKey);
@@ -209,7 +1037,7 @@ describe("amm", () => {
 
     // Withdraw all LP tokens (minX/minY = 0 for test)
     await program.methods
-      .withdraw(new anchor.BN(lpBefore), new anchor.BN(0), new anchor.BN(0))
+      .withdraw(new anchor.BN(lpBefore), new anchor.BN(0), new anchor.BN(0), null)
       .accounts({
         user: user.publicKey,
         mintX,
@@ -221,6 +1049,7 @@ describe("amm", () => {
         userX: userAtaX,
         userY: userAtaY,
         userLp: userAtaLp,
+        position: positionFor(config, user.publicKey),
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
@@ -234,4 +1063,228 @@ describe("amm", () => {
     if (xAfter <= xBefore) throw new Error("Withdraw did not increase X balance");
     if (yAfter <= yBefore) throw new Error("Withdraw did not increase Y balance");
   });
+
+  it("Should close a drained pool and refund its rent", async () => {
+    const connection = program.provider.connection;
+    const mintC = await createMint(connection, initializer, initializer.publicKey, null, 6);
+    const mintD = await createMint(connection, initializer, initializer.publicKey, null, 6);
+    const seedCD = await nextSeed();
+    const [configCD] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config"), seedCD.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [mintLpCD] = PublicKey.findProgramAddressSync([Buffer.from("lp"), configCD.toBuffer()], program.programId);
+    const [lpLockCD] = PublicKey.findProgramAddressSync([Buffer.from("lock"), configCD.toBuffer()], program.programId);
+    const vaultCD_C = await getAssociatedTokenAddress(mintC, configCD, true);
+    const vaultCD_D = await getAssociatedTokenAddress(mintD, configCD, true);
+    const canonicalPool = canonicalPoolFor(mintC, mintD, 30);
+
+    await program.methods
+      .initialize(seedCD, 30, initializer.publicKey, { constantProduct: {} }, new anchor.BN(0))
+      .accounts({
+        initializer: initializer.publicKey,
+        mintX: mintC,
+        mintY: mintD,
+        factory,
+        canonicalPool,
+        mintLp: mintLpCD,
+        lpLock: lpLockCD,
+        config: configCD,
+        vaultX: vaultCD_C,
+        vaultY: vaultCD_D,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([initializer])
+      .rpc();
+
+    const initializerC = (await getOrCreateAssociatedTokenAccount(connection, initializer, mintC, initializer.publicKey)).address;
+    const initializerD = (await getOrCreateAssociatedTokenAccount(connection, initializer, mintD, initializer.publicKey)).address;
+    const lamportsBefore = await connection.getBalance(initializer.publicKey);
+
+    await program.methods
+      .closePool()
+      .accounts({
+        authority: initializer.publicKey,
+        initializer: initializer.publicKey,
+        mintX: mintC,
+        mintY: mintD,
+        config: configCD,
+        canonicalPool,
+        mintLp: mintLpCD,
+        lpLock: lpLockCD,
+        vaultX: vaultCD_C,
+        vaultY: vaultCD_D,
+        initializerX: initializerC,
+        initializerY: initializerD,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([initializer])
+      .rpc();
+
+    // Every closed account is gone and its rent went back to the initializer
+    for (const account of [configCD, canonicalPool, vaultCD_C, vaultCD_D, lpLockCD]) {
+      if (await connection.getAccountInfo(account)) throw new Error("Pool account was not closed");
+    }
+    if ((await connection.getBalance(initializer.publicKey)) <= lamportsBefore) throw new Error("Rent was not refunded");
+  });
+
+  it("Should provide concentrated liquidity, swap through it and collect fees", async () => {
+    const connection = program.provider.connection;
+    const mintE = await createMint(connection, initializer, initializer.publicKey, null, 6);
+    const mintF = await createMint(connection, initializer, initializer.publicKey, null, 6);
+    const userE = (await getOrCreateAssociatedTokenAccount(connection, user, mintE, user.publicKey)).address;
+    const userF = (await getOrCreateAssociatedTokenAccount(connection, user, mintF, user.publicKey)).address;
+    await mintTo(connection, initializer, mintE, userE, initializer, 1_000_000);
+    await mintTo(connection, initializer, mintF, userF, initializer, 1_000_000);
+
+    // Pool PDA: ["cl_pool", seed], vaults are its ATAs; tick arrays: ["tick_array", pool, start_tick]
+    // Concentrated pools take their seed from the factory too, and have their own canonical PDA
+    const clSeed = await nextSeed();
+    const [pool] = PublicKey.findProgramAddressSync(
+      [Buffer.from("cl_pool"), clSeed.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [lowerMint, higherMint] = Buffer.compare(mintE.toBuffer(), mintF.toBuffer()) < 0 ? [mintE, mintF] : [mintF, mintE];
+    const feeBytes = Buffer.alloc(2);
+    feeBytes.writeUInt16LE(30);
+    const [clCanonicalPool] = PublicKey.findProgramAddressSync(
+      [Buffer.from("cl_canonical"), lowerMint.toBuffer(), higherMint.toBuffer(), feeBytes],
+      program.programId
+    );
+    const poolE = await getAssociatedTokenAddress(mintE, pool, true);
+    const poolF = await getAssociatedTokenAddress(mintF, pool, true);
+    const tickBytes = (tick: number) => {
+      const bytes = Buffer.alloc(4);
+      bytes.writeInt32LE(tick);
+      return bytes;
+    };
+    const tickArrayFor = (startTick: number) =>
+      PublicKey.findProgramAddressSync([Buffer.from("tick_array"), pool.toBuffer(), tickBytes(startTick)], program.programId)[0];
+    const [tickLower, tickUpper] = [-100, 100];
+    const [position] = PublicKey.findProgramAddressSync(
+      [Buffer.from("cl_position"), pool.toBuffer(), user.publicKey.toBuffer(), tickBytes(tickLower), tickBytes(tickUpper)],
+      program.programId
+    );
+
+    // Price 1 (sqrt price 2^64), ticks every 10, so each array of 16 ticks covers 160 ticks
+    await program.methods
+      .clInitialize(clSeed, 30, initializer.publicKey, 10, new anchor.BN(1).shln(64))
+      .accounts({
+        initializer: initializer.publicKey,
+        mintX: mintE,
+        mintY: mintF,
+        factory,
+        canonicalPool: clCanonicalPool,
+        pool,
+        vaultX: poolE,
+        vaultY: poolF,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([initializer])
+      .rpc();
+    for (const startTick of [-160, 0]) {
+      await program.methods
+        .clInitTickArray(startTick)
+        .accounts({ payer: user.publicKey, pool, tickArray: tickArrayFor(startTick), systemProgram: SystemProgram.programId })
+        .signers([user])
+        .rpc();
+    }
+
+    const liquidityAccounts = {
+      user: user.publicKey,
+      mintX: mintE,
+      mintY: mintF,
+      pool,
+      vaultX: poolE,
+      vaultY: poolF,
+      userX: userE,
+      userY: userF,
+      tickArrayLower: tickArrayFor(-160),
+      tickArrayUpper: tickArrayFor(0),
+      position,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+    const liquidity = new anchor.BN(10_000_000);
+    await program.methods
+      .clDeposit(tickLower, tickUpper, liquidity, new anchor.BN(100_000), new anchor.BN(100_000), null)
+      .accounts({ ...liquidityAccounts, associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID, systemProgram: SystemProgram.programId })
+      .signers([user])
+      .rpc();
+
+    // The pool is listed with the others, under its factory seed
+    const page = await program.methods
+      .listPools(clSeed)
+      .accounts({ factory })
+      .remainingAccounts([{ pubkey: pool, isSigner: false, isWritable: false }])
+      .view();
+    if (page.pools.length !== 1 || !page.pools[0].concentrated) throw new Error("Concentrated pool was not listed");
+
+    // Sell X for Y; the price may fall as far as the lowest valid sqrt price
+    const sellX = () =>
+      program.methods
+        .clSwap(new anchor.BN(10_000), new anchor.BN(9_000), true, new anchor.BN("4295048017"), null)
+        .accounts({
+          user: user.publicKey,
+          mintX: mintE,
+          mintY: mintF,
+          pool,
+          vaultX: poolE,
+          vaultY: poolF,
+          userX: userE,
+          userY: userF,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts([-160, 0].map((startTick) => ({ pubkey: tickArrayFor(startTick), isSigner: false, isWritable: true })))
+        .signers([user])
+        .rpc();
+
+    // The authority can pause the pool like any other
+    await program.methods.clLock().accounts({ authority: initializer.publicKey, pool }).signers([initializer]).rpc();
+    try {
+      await sellX();
+      throw new Error("Swap on a locked pool should have failed");
+    } catch (e) {
+      if (!e.toString().includes("PoolLocked")) throw e;
+    }
+    await program.methods.clUnlock().accounts({ authority: initializer.publicKey, pool }).signers([initializer]).rpc();
+    await sellX();
+
+    // The only position earned the whole fee (30 bps of 10_000, less rounding)
+    const balanceOf = async (account: PublicKey) => BigInt((await connection.getTokenAccountBalance(account)).value.amount);
+    const beforeCollect = await balanceOf(userE);
+    await program.methods.clCollect().accounts(liquidityAccounts).signers([user]).rpc();
+    const collected = (await balanceOf(userE)) - beforeCollect;
+    if (collected < BigInt(28) || collected > BigInt(31)) throw new Error(`Unexpected fees collected: ${collected}`);
+
+    await program.methods
+      .clWithdraw(liquidity, new anchor.BN(0), new anchor.BN(0), null)
+      .accounts(liquidityAccounts)
+      .signers([user])
+      .rpc();
+    const state = await program.account.clPool.fetch(pool);
+    if (!state.liquidity.isZero()) throw new Error("Liquidity left in the pool after withdrawing");
+  });
+
+  it("Should make the pool immutable once authority is renounced", async () => {
+    await program.methods
+      .transferAuthority(null)
+      .accounts({ authority: initializer.publicKey, config })
+      .signers([initializer])
+      .rpc();
+
+    try {
+      await program.methods
+        .lock()
+        .accounts({ authority: initializer.publicKey, config })
+        .signers([initializer])
+        .rpc();
+      throw new Error("Lock without an authority should have failed");
+    } catch (e) {
+      if (!e.toString().includes("NoAuthoritySet")) throw e;
+    }
+  });
 });
\ No newline at end of file
@@ -0,0 +1,391 @@
+// This crate runs the AMM program in an in-process SVM (LiteSVM), with no validator or network.
+// It loads the program compiled by `anchor build` from target/deploy/amm.so, so run that first:
+//
+//     anchor build && anchor run test-svm
+//
+// The program is deployed through the upgradeable loader, so it has the program data account
+// `initialize_factory` checks the upgrade authority against.
+//
+// `TestPool` sets up a factory and a constant product pool between two fresh SPL token mints,
+// funds a user with both tokens, and wraps each instruction in a method that sends it in its own
+// transaction. Instructions are built from the program's own Anchor client types
+// (`amm::instruction` and `amm::accounts`), so they always match the program's interface.
+
+#![allow(deprecated)]
+// LiteSVM's transaction error carries the full logs and metadata; tests only inspect it.
+#![allow(clippy::result_large_err)]
+
+use amm::{ error::AmmError, state::{ CanonicalPool, Config, CurveType } };
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{
+        bpf_loader_upgradeable::{ self, UpgradeableLoaderState },
+        instruction::{ Instruction, InstructionError },
+        program_pack::Pack,
+        system_instruction,
+        system_program,
+    },
+    AccountDeserialize,
+    InstructionData,
+    ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{ self, get_associated_token_address, spl_associated_token_account },
+    token::{ spl_token, Mint, TokenAccount },
+};
+use litesvm::{ types::TransactionResult, LiteSVM };
+use solana_account::Account;
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+use solana_transaction_error::TransactionError;
+
+/// Path of the program binary produced by `anchor build`.
+pub const PROGRAM_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../target/deploy/amm.so");
+
+/// Amount of each token minted to the test user.
+pub const USER_FUNDS: u64 = 1_000_000_000;
+
+/// A factory with a single constant product pool, and a funded user.
+pub struct TestPool {
+    pub svm: LiteSVM,
+    /// Factory and pool authority.
+    pub admin: Keypair,
+    /// Liquidity provider and swapper.
+    pub user: Keypair,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub config: Pubkey,
+    pub mint_lp: Pubkey,
+    pub lp_lock: Pubkey,
+    pub vault_x: Pubkey,
+    pub vault_y: Pubkey,
+    pub user_x: Pubkey,
+    pub user_y: Pubkey,
+    pub user_lp: Pubkey,
+    pub position: Pubkey,
+}
+
+impl TestPool {
+    /// Loads the program and creates a pool charging `fee` basis points, owned by `admin`.
+    /// `admin` is also the program's upgrade authority, and creates the factory.
+    pub fn new(fee: u16) -> Self {
+        let admin = Keypair::new();
+        let user = Keypair::new();
+        let mut svm = svm_with_program(&admin.pubkey());
+        svm.airdrop(&admin.pubkey(), 10_000_000_000).unwrap();
+        svm.airdrop(&user.pubkey(), 10_000_000_000).unwrap();
+
+        let mint_x = create_mint(&mut svm, &admin);
+        let mint_y = create_mint(&mut svm, &admin);
+        let user_x = create_funded_ata(&mut svm, &admin, &user.pubkey(), &mint_x, USER_FUNDS);
+        let user_y = create_funded_ata(&mut svm, &admin, &user.pubkey(), &mint_y, USER_FUNDS);
+
+        let seed = 0u64;
+        let (factory, _) = Pubkey::find_program_address(&[b"factory"], &amm::ID);
+        let (config, _) = Pubkey::find_program_address(&[b"config", &seed.to_le_bytes()], &amm::ID);
+        let (mint_lp, _) = Pubkey::find_program_address(&[b"lp", config.as_ref()], &amm::ID);
+        let (lp_lock, _) = Pubkey::find_program_address(&[b"lock", config.as_ref()], &amm::ID);
+        let (sorted_a, sorted_b) = CanonicalPool::sorted_mints(mint_x, mint_y);
+        let (canonical_pool, _) = Pubkey::find_program_address(
+            &[b"pool", sorted_a.as_ref(), sorted_b.as_ref(), &fee.to_le_bytes()],
+            &amm::ID,
+        );
+        let (position, _) = Pubkey::find_program_address(&[b"position", config.as_ref(), user.pubkey().as_ref()], &amm::ID);
+        let vault_x = get_associated_token_address(&config, &mint_x);
+        let vault_y = get_associated_token_address(&config, &mint_y);
+
+        let initialize_factory = initialize_factory_ix(&admin.pubkey(), vec![fee]);
+        let initialize = Instruction {
+            program_id: amm::ID,
+            accounts: amm::accounts::Initialize {
+                initializer: admin.pubkey(),
+                mint_x,
+                mint_y,
+                factory,
+                canonical_pool,
+                mint_lp,
+                lp_lock,
+                config,
+                vault_x,
+                vault_y,
+                token_program: spl_token::ID,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: amm::instruction::Initialize {
+                seed,
+                fee,
+                authority: Some(admin.pubkey()),
+                curve: CurveType::ConstantProduct,
+                amp: 0,
+            }
+            .data(),
+        };
+        send(&mut svm, &[initialize_factory, initialize], &[&admin]).unwrap();
+
+        Self {
+            svm,
+            admin,
+            user_lp: get_associated_token_address(&user.pubkey(), &mint_lp),
+            user,
+            mint_x,
+            mint_y,
+            config,
+            mint_lp,
+            lp_lock,
+            vault_x,
+            vault_y,
+            user_x,
+            user_y,
+            position,
+        }
+    }
+
+    /// Deposits for exactly `lp_amount` LP (at least, on the first deposit), spending at most `max_x` and `max_y`.
+    pub fn deposit(&mut self, lp_amount: u64, max_x: u64, max_y: u64) -> TransactionResult {
+        let ix = Instruction {
+            program_id: amm::ID,
+            accounts: amm::accounts::Deposit {
+                user: self.user.pubkey(),
+                mint_x: self.mint_x,
+                mint_y: self.mint_y,
+                config: self.config,
+                vault_x: self.vault_x,
+                vault_y: self.vault_y,
+                mint_lp: self.mint_lp,
+                lp_lock: self.lp_lock,
+                user_x: self.user_x,
+                user_y: self.user_y,
+                user_lp: self.user_lp,
+                position: self.position,
+                token_program: spl_token::ID,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: amm::instruction::Deposit { amount: lp_amount, max_x, max_y, expires_at: None }.data(),
+        };
+        send(&mut self.svm, &[ix], &[&self.user])
+    }
+
+    /// Swaps exactly `amount_in` of X (`x_to_y`) or Y for at least `min_amount_out`.
+    pub fn swap(&mut self, amount_in: u64, min_amount_out: u64, x_to_y: bool) -> TransactionResult {
+        let data = amm::instruction::Swap { amount_in, min_amount_out, x_to_y, expires_at: None }.data();
+        self.send_swap(data)
+    }
+
+    /// Swaps for exactly `amount_out` of Y (`x_to_y`) or X, spending at most `max_amount_in`.
+    pub fn swap_exact_out(&mut self, amount_out: u64, max_amount_in: u64, x_to_y: bool) -> TransactionResult {
+        let data = amm::instruction::SwapExactOut { amount_out, max_amount_in, x_to_y, expires_at: None }.data();
+        self.send_swap(data)
+    }
+
+    fn send_swap(&mut self, data: Vec<u8>) -> TransactionResult {
+        let ix = Instruction {
+            program_id: amm::ID,
+            accounts: amm::accounts::Swap {
+                user: self.user.pubkey(),
+                mint_x: self.mint_x,
+                mint_y: self.mint_y,
+                config: self.config,
+                vault_x: self.vault_x,
+                vault_y: self.vault_y,
+                user_x: self.user_x,
+                user_y: self.user_y,
+                token_program: spl_token::ID,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data,
+        };
+        send(&mut self.svm, &[ix], &[&self.user])
+    }
+
+    /// Burns `lp_amount` LP for at least `min_x` and `min_y`.
+    pub fn withdraw(&mut self, lp_amount: u64, min_x: u64, min_y: u64) -> TransactionResult {
+        let ix = Instruction {
+            program_id: amm::ID,
+            accounts: amm::accounts::Withdraw {
+                user: self.user.pubkey(),
+                mint_x: self.mint_x,
+                mint_y: self.mint_y,
+                config: self.config,
+                vault_x: self.vault_x,
+                vault_y: self.vault_y,
+                mint_lp: self.mint_lp,
+                user_x: self.user_x,
+                user_y: self.user_y,
+                user_lp: self.user_lp,
+                position: Some(self.position),
+                token_program: spl_token::ID,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: amm::instruction::Withdraw { lp_amount, min_x, min_y, expires_at: None }.data(),
+        };
+        send(&mut self.svm, &[ix], &[&self.user])
+    }
+
+    /// Locks the pool, signed by the admin.
+    pub fn lock(&mut self) -> TransactionResult {
+        let data = amm::instruction::Lock {}.data();
+        self.send_update(data)
+    }
+
+    /// Unlocks the pool, signed by the admin.
+    pub fn unlock(&mut self) -> TransactionResult {
+        let data = amm::instruction::Unlock {}.data();
+        self.send_update(data)
+    }
+
+    fn send_update(&mut self, data: Vec<u8>) -> TransactionResult {
+        let ix = Instruction {
+            program_id: amm::ID,
+            accounts: amm::accounts::Update { authority: self.admin.pubkey(), config: self.config }.to_account_metas(None),
+            data,
+        };
+        send(&mut self.svm, &[ix], &[&self.admin])
+    }
+
+    /// Returns the balance of a token account.
+    pub fn balance(&self, token_account: &Pubkey) -> u64 {
+        let account = self.svm.get_account(token_account).expect("token account not found");
+        TokenAccount::try_deserialize(&mut account.data.as_slice()).unwrap().amount
+    }
+
+    /// Returns the LP mint's supply.
+    pub fn lp_supply(&self) -> u64 {
+        let account = self.svm.get_account(&self.mint_lp).expect("LP mint not found");
+        Mint::try_deserialize(&mut account.data.as_slice()).unwrap().supply
+    }
+
+    /// Returns the pool's config.
+    pub fn config(&self) -> Config {
+        let account = self.svm.get_account(&self.config).expect("config not found");
+        Config::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    /// Returns the vault balances, as `(x, y)`.
+    pub fn reserves(&self) -> (u64, u64) {
+        (self.balance(&self.vault_x), self.balance(&self.vault_y))
+    }
+
+    /// Returns the user's token balances, as `(x, y)`.
+    pub fn user_balances(&self) -> (u64, u64) {
+        (self.balance(&self.user_x), self.balance(&self.user_y))
+    }
+}
+
+/// Creates an SVM with the program deployed through the upgradeable loader, as `anchor deploy` does,
+/// with `upgrade_authority` as its upgrade authority.
+pub fn svm_with_program(upgrade_authority: &Pubkey) -> LiteSVM {
+    let mut svm = LiteSVM::new();
+    let elf = std::fs::read(PROGRAM_PATH)
+        .unwrap_or_else(|err| panic!("failed to load {PROGRAM_PATH} ({err}), run `anchor build` first"));
+
+    // The program data account must exist before the program account pointing at it
+    let program_data = program_data_address();
+    let mut data = bincode::serialize(&UpgradeableLoaderState::ProgramData {
+        slot: 0,
+        upgrade_authority_address: Some(*upgrade_authority),
+    })
+    .unwrap();
+    data.extend_from_slice(&elf);
+    let lamports = svm.minimum_balance_for_rent_exemption(data.len());
+    svm.set_account(
+        program_data,
+        Account { lamports, data, owner: bpf_loader_upgradeable::ID, executable: false, rent_epoch: 0 },
+    )
+    .unwrap();
+
+    let data = bincode::serialize(&UpgradeableLoaderState::Program { programdata_address: program_data }).unwrap();
+    let lamports = svm.minimum_balance_for_rent_exemption(data.len());
+    svm.set_account(
+        amm::ID,
+        Account { lamports, data, owner: bpf_loader_upgradeable::ID, executable: true, rent_epoch: 0 },
+    )
+    .unwrap();
+    svm
+}
+
+/// The program's program data account, holding its upgrade authority.
+pub fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[amm::ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+/// Builds the instruction creating the factory with `fee_tiers`, signed by `authority`.
+pub fn initialize_factory_ix(authority: &Pubkey, fee_tiers: Vec<u16>) -> Instruction {
+    let (factory, _) = Pubkey::find_program_address(&[b"factory"], &amm::ID);
+    Instruction {
+        program_id: amm::ID,
+        accounts: amm::accounts::InitializeFactory {
+            authority: *authority,
+            factory,
+            program: amm::ID,
+            program_data: program_data_address(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: amm::instruction::InitializeFactory { fee_tiers }.data(),
+    }
+}
+
+/// Asserts that a transaction failed in its first instruction with the given program error.
+pub fn assert_amm_error(result: TransactionResult, error: AmmError) {
+    let code = u32::from(error);
+    match result {
+        Ok(_) => panic!("expected error {code}, but the transaction succeeded"),
+        Err(failed) => assert_eq!(
+            failed.err,
+            TransactionError::InstructionError(0, InstructionError::Custom(code)),
+            "logs: {:#?}",
+            failed.meta.logs
+        ),
+    }
+}
+
+/// Signs `instructions` with `signers`, the first of which pays, and sends them in one transaction.
+/// The blockhash is refreshed first, so sending the same instructions twice isn't rejected as a duplicate.
+pub fn send(svm: &mut LiteSVM, instructions: &[Instruction], signers: &[&Keypair]) -> TransactionResult {
+    svm.expire_blockhash();
+    let tx = Transaction::new_signed_with_payer(instructions, Some(&signers[0].pubkey()), signers, svm.latest_blockhash());
+    svm.send_transaction(tx)
+}
+
+/// Creates an SPL token mint with 6 decimals and `authority` as mint authority.
+fn create_mint(svm: &mut LiteSVM, authority: &Keypair) -> Pubkey {
+    let mint = Keypair::new();
+    let lamports = svm.minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN);
+    let instructions = [
+        system_instruction::create_account(
+            &authority.pubkey(),
+            &mint.pubkey(),
+            lamports,
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(&spl_token::ID, &mint.pubkey(), &authority.pubkey(), None, 6).unwrap(),
+    ];
+    send(svm, &instructions, &[authority, &mint]).unwrap();
+    mint.pubkey()
+}
+
+/// Creates `owner`'s associated token account for `mint` and mints `amount` into it.
+fn create_funded_ata(svm: &mut LiteSVM, authority: &Keypair, owner: &Pubkey, mint: &Pubkey, amount: u64) -> Pubkey {
+    let ata = get_associated_token_address(owner, mint);
+    let instructions = [
+        spl_associated_token_account::instruction::create_associated_token_account(
+            &authority.pubkey(),
+            owner,
+            mint,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::mint_to(&spl_token::ID, mint, &ata, &authority.pubkey(), &[], amount).unwrap(),
+    ];
+    send(svm, &instructions, &[authority]).unwrap();
+    ata
+}
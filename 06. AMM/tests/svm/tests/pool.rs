@@ -0,0 +1,182 @@
+// Integration tests for the constant product pool, run against the compiled program in LiteSVM.
+// Covers factory creation, the initialize -> deposit -> swap -> withdraw lifecycle, slippage and lock failures,
+// and the rounding edge cases where the pool must never lose value to the user.
+
+use amm::{ error::AmmError, get_amount_in, get_amount_out, MINIMUM_LIQUIDITY };
+use amm_svm_tests::{ assert_amm_error, initialize_factory_ix, send, svm_with_program, TestPool, USER_FUNDS };
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+
+const FEE: u16 = 30;
+
+/// A pool seeded by the user with `x` and `y`.
+fn seeded_pool(x: u64, y: u64) -> TestPool {
+    let mut pool = TestPool::new(FEE);
+    pool.deposit(1, x, y).unwrap();
+    pool
+}
+
+#[test]
+fn test_initialize_deposit_swap_withdraw() {
+    let mut pool = TestPool::new(FEE);
+    let config = pool.config();
+    assert_eq!(config.mint_x, pool.mint_x);
+    assert_eq!(config.mint_y, pool.mint_y);
+    assert_eq!(config.fee, FEE);
+    assert!(!config.locked);
+    assert_eq!(pool.lp_supply(), 0);
+
+    // First deposit mints sqrt(x * y) LP and locks MINIMUM_LIQUIDITY of it
+    pool.deposit(1, 1_000_000, 4_000_000).unwrap();
+    assert_eq!(pool.reserves(), (1_000_000, 4_000_000));
+    assert_eq!(pool.balance(&pool.lp_lock), MINIMUM_LIQUIDITY);
+    assert_eq!(pool.balance(&pool.user_lp), 2_000_000 - MINIMUM_LIQUIDITY);
+    assert_eq!(pool.lp_supply(), 2_000_000);
+
+    // Swap X for Y at the quoted price
+    let (expected_out, _) = get_amount_out(10_000, 1_000_000, 4_000_000, FEE).unwrap();
+    let (_, y_before) = pool.user_balances();
+    pool.swap(10_000, expected_out, true).unwrap();
+    let (_, y_after) = pool.user_balances();
+    assert_eq!(y_after - y_before, expected_out);
+    assert_eq!(pool.reserves(), (1_010_000, 4_000_000 - expected_out));
+
+    // Withdrawing every LP the user holds leaves only the locked share in the vaults
+    let (reserve_x, reserve_y) = pool.reserves();
+    let lp = pool.balance(&pool.user_lp);
+    pool.withdraw(lp, 1, 1).unwrap();
+    assert_eq!(pool.balance(&pool.user_lp), 0);
+    assert_eq!(pool.lp_supply(), MINIMUM_LIQUIDITY);
+    let (left_x, left_y) = pool.reserves();
+    assert_eq!(left_x, (reserve_x * MINIMUM_LIQUIDITY).div_ceil(2_000_000));
+    assert_eq!(left_y, (reserve_y * MINIMUM_LIQUIDITY).div_ceil(2_000_000));
+    assert_eq!(pool.user_balances(), (USER_FUNDS - left_x, USER_FUNDS - left_y));
+}
+
+#[test]
+fn test_swap_rejects_output_below_minimum() {
+    let mut pool = seeded_pool(1_000_000, 1_000_000);
+    let (quote, _) = get_amount_out(10_000, 1_000_000, 1_000_000, FEE).unwrap();
+    let before = pool.user_balances();
+
+    assert_amm_error(pool.swap(10_000, quote + 1, true), AmmError::SlippageExceeded);
+    assert_eq!(pool.user_balances(), before);
+    assert_eq!(pool.reserves(), (1_000_000, 1_000_000));
+}
+
+#[test]
+fn test_swap_exact_out_rejects_input_above_maximum() {
+    let mut pool = seeded_pool(1_000_000, 1_000_000);
+    let (quote, _) = get_amount_in(10_000, 1_000_000, 1_000_000, FEE).unwrap();
+
+    assert_amm_error(pool.swap_exact_out(10_000, quote - 1, true), AmmError::SlippageExceeded);
+    pool.swap_exact_out(10_000, quote, true).unwrap();
+    assert_eq!(pool.reserves(), (1_000_000 + quote, 990_000));
+}
+
+#[test]
+fn test_deposit_and_withdraw_reject_slippage() {
+    let mut pool = seeded_pool(1_000_000, 1_000_000);
+
+    // 1_000 LP of a 1_000_000 supply costs 1_000 of each token
+    assert_amm_error(pool.deposit(1_000, 999, 1_000), AmmError::SlippageExceeded);
+    assert_amm_error(pool.deposit(1_000, 1_000, 999), AmmError::SlippageExceeded);
+    pool.deposit(1_000, 1_000, 1_000).unwrap();
+
+    assert_amm_error(pool.withdraw(1_000, 1_001, 1_000), AmmError::SlippageExceeded);
+    assert_amm_error(pool.withdraw(1_000, 1_000, 1_001), AmmError::SlippageExceeded);
+    pool.withdraw(1_000, 1_000, 1_000).unwrap();
+    assert_eq!(pool.reserves(), (1_000_000, 1_000_000));
+
+    // The first deposit's slippage bound is on the LP minted
+    let mut empty = TestPool::new(FEE);
+    assert_amm_error(empty.deposit(1_000_000, 1_000_000, 1_000_000), AmmError::SlippageExceeded);
+}
+
+#[test]
+fn test_only_the_upgrade_authority_initializes_the_factory() {
+    let admin = Keypair::new();
+    let intruder = Keypair::new();
+    let mut svm = svm_with_program(&admin.pubkey());
+    svm.airdrop(&admin.pubkey(), 10_000_000_000).unwrap();
+    svm.airdrop(&intruder.pubkey(), 10_000_000_000).unwrap();
+
+    let result = send(&mut svm, &[initialize_factory_ix(&intruder.pubkey(), vec![FEE])], &[&intruder]);
+    assert_amm_error(result, AmmError::InvalidAuthority);
+    send(&mut svm, &[initialize_factory_ix(&admin.pubkey(), vec![FEE])], &[&admin]).unwrap();
+}
+
+#[test]
+fn test_locked_pool_rejects_deposit_swap_and_withdraw() {
+    let mut pool = seeded_pool(1_000_000, 1_000_000);
+    pool.lock().unwrap();
+    assert!(pool.config().locked);
+
+    assert_amm_error(pool.deposit(1_000, 1_000, 1_000), AmmError::PoolLocked);
+    assert_amm_error(pool.swap(10_000, 1, true), AmmError::PoolLocked);
+    assert_amm_error(pool.swap_exact_out(10_000, u64::MAX, false), AmmError::PoolLocked);
+    assert_amm_error(pool.withdraw(1_000, 1, 1), AmmError::PoolLocked);
+
+    pool.unlock().unwrap();
+    pool.swap(10_000, 1, true).unwrap();
+}
+
+#[test]
+fn test_first_deposit_must_exceed_minimum_liquidity() {
+    let mut pool = TestPool::new(FEE);
+
+    // sqrt(1_000 * 1_000) leaves nothing after the locked minimum
+    assert_amm_error(pool.deposit(1, 1_000, 1_000), AmmError::LiquidityLessThanMinimum);
+    pool.deposit(1, 1_001, 1_001).unwrap();
+    assert_eq!(pool.balance(&pool.user_lp), 1);
+}
+
+#[test]
+fn test_dust_swap_rounds_output_down_to_nothing() {
+    let mut pool = seeded_pool(1_000_000, 1_000_000);
+
+    // One token in, after the fee, is worth less than one token out
+    assert_eq!(get_amount_out(1, 1_000_000, 1_000_000, FEE).unwrap().0, 0);
+    assert_amm_error(pool.swap(1, 0, true), AmmError::InvalidAmount);
+    assert_eq!(pool.reserves(), (1_000_000, 1_000_000));
+}
+
+#[test]
+fn test_round_trip_swaps_never_lose_pool_value() {
+    let mut pool = seeded_pool(1_000_000, 3_000_000);
+    let (x0, y0) = pool.reserves();
+    let (user_x0, user_y0) = pool.user_balances();
+
+    // Tiny exact-output swaps, where rounding the input up matters most
+    pool.swap_exact_out(1, u64::MAX, true).unwrap();
+    pool.swap_exact_out(7, u64::MAX, false).unwrap();
+
+    // Swap some X to Y, then every Y gained back to X
+    pool.swap(333, 0, true).unwrap();
+    let gained_y = pool.user_balances().1 - user_y0;
+    pool.swap(gained_y, 0, false).unwrap();
+
+    // Every quote rounds in the pool's favour, so the invariant only grows and the user ends down
+    let (x1, y1) = pool.reserves();
+    assert!(x1 as u128 * y1 as u128 > x0 as u128 * y0 as u128);
+    assert_eq!(pool.user_balances().1, user_y0);
+    assert!(pool.user_balances().0 < user_x0);
+}
+
+#[test]
+fn test_lp_rounding_favours_the_pool() {
+    // 31_622 LP over 500 Y: one LP is worth less than one Y
+    let mut pool = seeded_pool(2_000_000, 500);
+    let supply = pool.lp_supply();
+    assert_eq!(supply, 31_622);
+
+    // Depositing rounds the amounts owed up...
+    let before = pool.user_balances();
+    pool.deposit(1, u64::MAX, u64::MAX).unwrap();
+    let after = pool.user_balances();
+    assert_eq!(before.0 - after.0, 2_000_000u64.div_ceil(supply));
+    assert_eq!(before.1 - after.1, 1);
+
+    // ...and withdrawing rounds the amounts paid down, so a single LP can't take any Y out
+    assert_amm_error(pool.withdraw(1, 0, 0), AmmError::InvalidAmount);
+}